@@ -0,0 +1,747 @@
+//! ## Addressbook
+//!
+//! `addressbook` collects the people we write with from mail headers, ranked by how often
+//! and how recently they show up, saved as json in the data dir
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::compose::{mailbox, single_addresses};
+use crate::vcard::Card;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+// a mail we sent counts as much as this many mails we got
+const SENT_WEIGHT: f64 = 5.0;
+// most completions offered for a recipient
+const MAX_COMPLETIONS: usize = 20;
+
+/// How often an address showed up, the last time, and the name it had then
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seen {
+    #[serde(rename = "n")]
+    pub name: String,
+    #[serde(rename = "c")]
+    pub count: u32,
+    #[serde(rename = "l")]
+    pub last: i64,
+}
+
+impl Seen {
+    fn add(&mut self, name: &str, date: i64) {
+        self.count += 1;
+        if date >= self.last {
+            self.last = date;
+            if !name.trim().is_empty() {
+                self.name = name.trim().to_string();
+            }
+        } else if self.name.is_empty() {
+            self.name = name.trim().to_string();
+        }
+    }
+}
+
+/// An address of the address book, with all it was seen in merged
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    pub name: String,
+    pub address: String,
+    /// mails it was seen in
+    pub count: u32,
+    /// mails we sent to it
+    pub sent: u32,
+    pub last: i64,
+}
+
+impl Contact {
+    /// ### `mailbox`
+    ///
+    /// Name and address, as written in a recipient header
+    pub fn mailbox(&self) -> String {
+        mailbox(&self.name, &self.address)
+    }
+
+    pub fn rank(&self, now: i64) -> f64 {
+        rank(self.count, self.sent, self.last, now)
+    }
+}
+
+/// Someone of the address book: a vCard with all its addresses, or an address of no card
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Person {
+    pub name: String,
+    pub addresses: Vec<String>,
+    pub count: u32,
+    pub sent: u32,
+    pub last: i64,
+    /// uid of the card
+    pub uid: Option<String>,
+}
+
+impl Person {
+    /// ### `mailbox`
+    ///
+    /// Name and first address, as written in a recipient header
+    pub fn mailbox(&self) -> String {
+        mailbox(
+            &self.name,
+            self.addresses.first().map_or("", String::as_str),
+        )
+    }
+}
+
+/// ### `rank`
+///
+/// How likely we are to write to someone: mails, sent ones counting more, fading with time
+fn rank(count: u32, sent: u32, last: i64, now: i64) -> f64 {
+    let days = now.saturating_sub(last) / 86400;
+    let recency = match days {
+        i64::MIN..=4 => 1.0,
+        5..=14 => 0.7,
+        15..=31 => 0.5,
+        32..=90 => 0.3,
+        _ => 0.1,
+    };
+    SENT_WEIGHT.mul_add(f64::from(sent), f64::from(count)) * recency
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressBook {
+    /// contacts imported from vCards
+    people: Vec<Card>,
+    /// names set by hand, by address
+    names: BTreeMap<String, String>,
+    /// addresses removed by hand, not collected again
+    removed: BTreeSet<String>,
+    /// addresses in the headers of each folder, collected again each time it is read
+    folders: BTreeMap<String, BTreeMap<String, Seen>>,
+    /// addresses we sent mails to
+    sent: BTreeMap<String, Seen>,
+    /// read from the saved file, or there was none, so that saving can't lose it
+    #[serde(skip)]
+    loaded: bool,
+}
+
+pub fn address_book_path() -> Result<PathBuf> {
+    let mut path = dirs_next::data_dir().ok_or_else(|| anyhow!("failed to find data dir."))?;
+    path.push("termail");
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+    }
+    path.push("addressbook.json");
+    Ok(path)
+}
+
+impl AddressBook {
+    /// ### `load`
+    ///
+    /// Load the saved address book, or start an empty one when there is none. A file which
+    /// can't be read is moved aside, to start anew without losing it
+    pub fn load() -> Result<Self> {
+        let path = address_book_path()?;
+        if !path.exists() {
+            return Ok(Self {
+                loaded: true,
+                ..Self::default()
+            });
+        }
+        let error = match fs::read(&path) {
+            Ok(data) => match serde_json::from_slice::<Self>(&data) {
+                Ok(mut book) => {
+                    book.loaded = true;
+                    return Ok(book);
+                }
+                Err(e) => anyhow!(e),
+            },
+            Err(e) => anyhow!(e),
+        };
+        let aside = path.with_extension(format!("json.bad-{}", chrono::Local::now().timestamp()));
+        match fs::rename(&path, &aside) {
+            Ok(()) => Err(anyhow!(
+                "Failed to read address book {}: {error}. It was moved to {}",
+                path.display(),
+                aside.display()
+            )),
+            Err(e) => Err(anyhow!(
+                "Failed to read address book {}: {error}. It won't be saved over: {e}",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = address_book_path()?;
+        if !self.loaded && path.exists() {
+            return Err(anyhow!(
+                "{} was not loaded, not saving over it",
+                path.display()
+            ));
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// ### `collect`
+    ///
+    /// Take the addresses of all the mails of `folder`, as address headers with the date
+    /// of their mail, in place of the ones taken from it before. Tells if anything changed
+    pub fn collect<'a>(
+        &mut self,
+        folder: &str,
+        headers: impl Iterator<Item = (&'a str, i64)>,
+    ) -> bool {
+        let mut seen: BTreeMap<String, Seen> = BTreeMap::new();
+        for (value, date) in headers {
+            self.add(&mut seen, value, date);
+        }
+        if self.folders.get(folder) == Some(&seen) {
+            return false;
+        }
+        if seen.is_empty() {
+            self.folders.remove(folder);
+        } else {
+            self.folders.insert(folder.to_string(), seen);
+        }
+        true
+    }
+
+    /// ### `sent_to`
+    ///
+    /// Note a mail sent to the addresses of `value`
+    pub fn sent_to(&mut self, value: &str, date: i64) {
+        let mut sent = std::mem::take(&mut self.sent);
+        self.add(&mut sent, value, date);
+        self.sent = sent;
+    }
+
+    fn add(&self, seen: &mut BTreeMap<String, Seen>, value: &str, date: i64) {
+        for info in single_addresses(value) {
+            let address = info.addr.trim().to_lowercase();
+            if !address.contains('@') || self.removed.contains(&address) {
+                continue;
+            }
+            seen.entry(address)
+                .or_default()
+                .add(info.display_name.as_deref().unwrap_or_default(), date);
+        }
+    }
+
+    /// ### `contacts`
+    ///
+    /// Every address, most likely to be written to first
+    pub fn contacts(&self, now: i64) -> Vec<Contact> {
+        let mut merged: BTreeMap<String, Contact> = BTreeMap::new();
+        let all = self
+            .folders
+            .values()
+            .flatten()
+            .map(|seen| (seen, false))
+            .chain(self.sent.iter().map(|seen| (seen, true)));
+        for card in &self.people {
+            for email in &card.emails {
+                let address = email.to_lowercase();
+                if !self.removed.contains(&address) {
+                    merged.entry(address.clone()).or_insert_with(|| Contact {
+                        name: String::new(),
+                        address,
+                        count: 0,
+                        sent: 0,
+                        last: 0,
+                    });
+                }
+            }
+        }
+        for ((address, seen), sent) in all {
+            let contact = merged.entry(address.clone()).or_insert_with(|| Contact {
+                name: String::new(),
+                address: address.clone(),
+                count: 0,
+                sent: 0,
+                last: i64::MIN,
+            });
+            if sent {
+                contact.sent += seen.count;
+            } else {
+                contact.count += seen.count;
+            }
+            if (seen.last >= contact.last && !seen.name.is_empty()) || contact.name.is_empty() {
+                contact.name.clone_from(&seen.name);
+            }
+            contact.last = contact.last.max(seen.last);
+        }
+        let mut contacts: Vec<Contact> = merged
+            .into_values()
+            .map(|mut contact| {
+                if let Some(name) = self.names.get(&contact.address) {
+                    contact.name.clone_from(name);
+                } else if let Some(card) = self.card_of(&contact.address) {
+                    contact.name.clone_from(&card.name);
+                }
+                contact
+            })
+            .collect();
+        contacts.sort_by(|a, b| b.rank(now).total_cmp(&a.rank(now)));
+        contacts
+    }
+
+    /// ### `complete`
+    ///
+    /// Contacts matching what was typed of an address: a word starting with it first, then
+    /// containing it, then containing its letters in order
+    pub fn complete(&self, typed: &str, now: i64) -> Vec<Contact> {
+        let typed = typed.trim().to_lowercase();
+        if typed.is_empty() {
+            return Vec::new();
+        }
+        let mut found: Vec<(u8, Contact)> = self
+            .contacts(now)
+            .into_iter()
+            .filter_map(|contact| {
+                let text = format!("{} {}", contact.name, contact.address).to_lowercase();
+                let quality = if text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|word| word.starts_with(&typed))
+                {
+                    0
+                } else if text.contains(&typed) {
+                    1
+                } else if is_subsequence(&typed, &text) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((quality, contact))
+            })
+            .collect();
+        // stable, so the rank decides among matches as good
+        found.sort_by_key(|(quality, _)| *quality);
+        found
+            .into_iter()
+            .take(MAX_COMPLETIONS)
+            .map(|(_, contact)| contact)
+            .collect()
+    }
+
+    /// ### `people`
+    ///
+    /// Everyone, with the addresses of a card together, most likely to be written to first
+    pub fn people(&self, now: i64) -> Vec<Person> {
+        let mut alone: Vec<Person> = Vec::new();
+        let mut people: Vec<Person> = self
+            .people
+            .iter()
+            .map(|card| Person {
+                name: card.name.clone(),
+                addresses: card.emails.clone(),
+                count: 0,
+                sent: 0,
+                last: 0,
+                uid: Some(card.uid.clone()),
+            })
+            .collect();
+        for contact in self.contacts(now) {
+            let card = self.people.iter().position(|card| {
+                card.emails
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(&contact.address))
+            });
+            match card {
+                Some(card) => {
+                    let person = &mut people[card];
+                    person.count += contact.count;
+                    person.sent += contact.sent;
+                    person.last = person.last.max(contact.last);
+                }
+                None => alone.push(Person {
+                    name: contact.name,
+                    addresses: vec![contact.address],
+                    count: contact.count,
+                    sent: contact.sent,
+                    last: contact.last,
+                    uid: None,
+                }),
+            }
+        }
+        people.append(&mut alone);
+        people.sort_by(|a, b| {
+            rank(b.count, b.sent, b.last, now).total_cmp(&rank(a.count, a.sent, a.last, now))
+        });
+        people
+    }
+
+    fn card_of(&self, address: &str) -> Option<&Card> {
+        self.people
+            .iter()
+            .find(|card| card.emails.iter().any(|e| e.eq_ignore_ascii_case(address)))
+    }
+
+    /// ### `import`
+    ///
+    /// Take in vCards, in place of the cards having the same uid or an address in common
+    pub fn import(&mut self, cards: Vec<Card>) -> usize {
+        let count = cards.len();
+        for mut card in cards {
+            if card.uid.is_empty() {
+                card.uid = new_uid(&card);
+            }
+            for email in &card.emails {
+                self.removed.remove(&email.to_lowercase());
+            }
+            // the card may stand for several known ones, which are all replaced
+            let same: Vec<usize> = self
+                .people
+                .iter()
+                .enumerate()
+                .filter(|(_, known)| {
+                    known.uid == card.uid
+                        || known
+                            .emails
+                            .iter()
+                            .any(|e| card.emails.iter().any(|c| c.eq_ignore_ascii_case(e)))
+                })
+                .map(|(i, _)| i)
+                .collect();
+            match same.split_first() {
+                Some((&first, rest)) => {
+                    self.people[first] = card;
+                    for &i in rest.iter().rev() {
+                        self.people.remove(i);
+                    }
+                }
+                None => self.people.push(card),
+            }
+        }
+        count
+    }
+
+    /// ### `export`
+    ///
+    /// Everyone as vCards: the imported cards, and a new card for each address of none
+    pub fn export(&self, now: i64) -> Vec<Card> {
+        let mut cards = self.people.clone();
+        for person in self.people(now) {
+            if person.uid.is_none() {
+                let mut card = Card {
+                    name: person.name,
+                    emails: person.addresses,
+                    ..Card::default()
+                };
+                card.uid = new_uid(&card);
+                cards.push(card);
+            }
+        }
+        cards
+    }
+
+    /// ### `rename_person`
+    ///
+    /// Give a card or an address alone the name `name`
+    pub fn rename_person(&mut self, person: &Person, name: &str) {
+        match person
+            .uid
+            .as_ref()
+            .and_then(|uid| self.people.iter_mut().find(|card| &card.uid == uid))
+        {
+            Some(card) if !name.trim().is_empty() => card.name = name.trim().to_string(),
+            Some(_) => {}
+            None => {
+                for address in &person.addresses {
+                    self.rename(address, name);
+                }
+            }
+        }
+    }
+
+    /// ### `remove_person`
+    ///
+    /// Forget a card and all its addresses
+    pub fn remove_person(&mut self, person: &Person) {
+        if let Some(uid) = &person.uid {
+            self.people.retain(|card| &card.uid != uid);
+        }
+        for address in &person.addresses {
+            self.remove(address);
+        }
+    }
+
+    /// ### `rename`
+    ///
+    /// Give an address the name `name`, or the one from its mails again when it is empty
+    pub fn rename(&mut self, address: &str, name: &str) {
+        let address = address.to_lowercase();
+        if name.trim().is_empty() {
+            self.names.remove(&address);
+        } else {
+            self.names.insert(address, name.trim().to_string());
+        }
+    }
+
+    /// ### `remove`
+    ///
+    /// Forget an address, and leave it out from now on
+    pub fn remove(&mut self, address: &str) {
+        let address = address.to_lowercase();
+        for seen in self.folders.values_mut() {
+            seen.remove(&address);
+        }
+        self.sent.remove(&address);
+        self.names.remove(&address);
+        self.removed.insert(address);
+    }
+}
+
+/// Tab completion going on in a recipient field
+#[derive(Clone, Debug)]
+pub struct Completion {
+    /// the field before the address being completed
+    head: String,
+    candidates: Vec<String>,
+    index: usize,
+    /// the field as last completed, to tell a second <TAB> from more typing
+    value: String,
+}
+
+impl Completion {
+    /// ### `next`
+    ///
+    /// Complete the address typed last in `value`, or go to the next candidate when `previous`
+    /// just completed it. None when nothing matches
+    pub fn next(previous: Option<Self>, value: &str, book: &AddressBook, now: i64) -> Option<Self> {
+        let mut completion = match previous {
+            Some(mut completion) if completion.value == value => {
+                completion.index = (completion.index + 1) % completion.candidates.len();
+                completion
+            }
+            _ => {
+                // only the address after the last comma is completed
+                let (head, typed) = value.rfind(',').map_or_else(
+                    || (String::new(), value),
+                    |comma| {
+                        (
+                            format!("{}, ", value[..comma].trim_end()),
+                            &value[comma + 1..],
+                        )
+                    },
+                );
+                let candidates: Vec<String> = book
+                    .complete(typed, now)
+                    .iter()
+                    .map(Contact::mailbox)
+                    .collect();
+                if candidates.is_empty() {
+                    return None;
+                }
+                Self {
+                    head,
+                    candidates,
+                    index: 0,
+                    value: String::new(),
+                }
+            }
+        };
+        completion.value = format!(
+            "{}{}",
+            completion.head, completion.candidates[completion.index]
+        );
+        Some(completion)
+    }
+
+    /// The field with the address completed
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Number of the candidate shown, from 1, and how many there are
+    pub const fn position(&self) -> (usize, usize) {
+        (self.index + 1, self.candidates.len())
+    }
+}
+
+/// Uid of a card made by termail, from its address
+fn new_uid(card: &Card) -> String {
+    let base = card.emails.first().unwrap_or(&card.name);
+    let base: String = base
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("termail-{base}")
+}
+
+fn is_subsequence(typed: &str, text: &str) -> bool {
+    let mut chars = text.chars();
+    typed.chars().all(|c| chars.any(|t| t == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const DAY: i64 = 86400;
+
+    fn names(contacts: &[Contact]) -> Vec<String> {
+        contacts.iter().map(Contact::mailbox).collect()
+    }
+
+    #[test]
+    fn test_address_book_collect() {
+        let mut book = AddressBook::default();
+        let inbox = [
+            ("Ann <ann@example.com>", 10 * DAY),
+            ("ann@example.com, Bob Stone <bob@example.com>", 20 * DAY),
+            ("Ann Lee <ANN@example.com>", 30 * DAY),
+            ("undisclosed-recipients:;", 30 * DAY),
+        ];
+        assert!(book.collect("/mail/INBOX", inbox.iter().copied()));
+        // reading it again counts nothing twice
+        assert!(!book.collect("/mail/INBOX", inbox.iter().copied()));
+        book.sent_to("Bob Stone <bob@example.com>", 31 * DAY);
+
+        let contacts = book.contacts(31 * DAY);
+        assert_eq!(
+            names(&contacts),
+            vec!["Bob Stone <bob@example.com>", "Ann Lee <ann@example.com>"]
+        );
+        assert_eq!((contacts[1].count, contacts[1].sent), (3, 0));
+        assert_eq!((contacts[0].count, contacts[0].sent), (1, 1));
+
+        book.rename("Ann@example.com", "Ann, from work");
+        book.remove("bob@example.com");
+        book.collect("/mail/INBOX", inbox.iter().copied());
+        assert_eq!(
+            names(&book.contacts(31 * DAY)),
+            vec!["\"Ann, from work\" <ann@example.com>"]
+        );
+    }
+
+    #[test]
+    fn test_address_book_people() {
+        let mut book = AddressBook::default();
+        let mails = [
+            ("Ann <ann@home.example>", 10 * DAY),
+            ("Ann Lee <ann@work.example>", 20 * DAY),
+            ("Bob <bob@example.com>", 20 * DAY),
+        ];
+        book.collect("/mail/INBOX", mails.iter().copied());
+        let card = Card {
+            name: "Ann Lee".to_string(),
+            emails: vec![
+                "ann@work.example".to_string(),
+                "ANN@home.example".to_string(),
+            ],
+            ..Card::default()
+        };
+        assert_eq!(book.import(vec![card]), 1);
+
+        let people = book.people(20 * DAY);
+        assert_eq!(people.len(), 2);
+        assert_eq!(people[0].mailbox(), "Ann Lee <ann@work.example>");
+        assert_eq!(people[0].count, 2);
+        assert_eq!(people[0].uid.as_deref(), Some("termail-ann-work-example"));
+        assert_eq!(
+            names(&book.complete("ann", 20 * DAY)),
+            vec!["Ann Lee <ann@work.example>", "Ann Lee <ann@home.example>"]
+        );
+
+        // the same card again replaces it
+        let card = Card {
+            uid: "termail-ann-work-example".to_string(),
+            name: "Ann".to_string(),
+            emails: vec!["ann@work.example".to_string()],
+            ..Card::default()
+        };
+        book.import(vec![card]);
+        let exported = book.export(20 * DAY);
+        assert_eq!(exported.len(), 3);
+        assert_eq!(exported[0].name, "Ann");
+        assert!(exported.iter().any(|c| c.emails == ["bob@example.com"]));
+
+        book.remove_person(&book.people(20 * DAY)[0].clone());
+        assert_eq!(book.export(20 * DAY).len(), 2);
+
+        // a card with the addresses of two cards takes the place of both
+        let cards = ["ann@home.example", "ann@work.example"].map(|email| Card {
+            name: "Ann".to_string(),
+            emails: vec![email.to_string()],
+            ..Card::default()
+        });
+        book.import(cards.to_vec());
+        let card = Card {
+            name: "Ann Lee".to_string(),
+            emails: vec![
+                "ann@work.example".to_string(),
+                "ann@home.example".to_string(),
+            ],
+            ..Card::default()
+        };
+        book.import(vec![card.clone()]);
+        let anns: Vec<Card> = book
+            .export(20 * DAY)
+            .into_iter()
+            .filter(|c| c.name.starts_with("Ann"))
+            .collect();
+        assert_eq!(anns.len(), 1);
+        assert_eq!(anns[0].emails, card.emails);
+    }
+
+    #[test]
+    fn test_address_book_complete() {
+        let mut book = AddressBook::default();
+        let mails = [
+            ("Ann Lee <ann@example.com>", 100 * DAY),
+            ("Joanna Smith <jsmith@example.org>", 100 * DAY),
+            ("Joanna Smith <jsmith@example.org>", 101 * DAY),
+            ("Anders <anders@example.net>", 0),
+        ];
+        book.collect("/mail/INBOX", mails.iter().copied());
+        let complete = |typed| names(&book.complete(typed, 101 * DAY));
+        assert_eq!(
+            complete("an"),
+            vec![
+                "Ann Lee <ann@example.com>",
+                "Anders <anders@example.net>",
+                "Joanna Smith <jsmith@example.org>",
+            ]
+        );
+        assert_eq!(complete("smth"), vec!["Joanna Smith <jsmith@example.org>"]);
+        assert_eq!(complete(" "), Vec::<String>::new());
+
+        let first = Completion::next(None, "bob@example.com, an", &book, 101 * DAY).unwrap();
+        assert_eq!(first.value(), "bob@example.com, Ann Lee <ann@example.com>");
+        let value = first.value().to_string();
+        let second = Completion::next(Some(first), &value, &book, 101 * DAY).unwrap();
+        assert_eq!(
+            second.value(),
+            "bob@example.com, Anders <anders@example.net>"
+        );
+        assert_eq!(second.position(), (2, 3));
+        assert!(Completion::next(Some(second), "zzz", &book, 101 * DAY).is_none());
+    }
+}
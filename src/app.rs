@@ -22,7 +22,7 @@
  * SOFTWARE.
  */
 use super::ui::{
-    activity::{main::TermailActivity, Activity, ExitReason},
+    activity::{compose::ComposeActivity, main::TermailActivity, Activity, ExitReason},
     context::Context,
 };
 use crate::config::TermailConfig;
@@ -64,12 +64,17 @@ impl App {
         main_activity.on_create(ctx);
         loop {
             main_activity.update_maillist();
+            main_activity.update_mail_body();
+            main_activity.update_limit();
+            main_activity.update_message();
+            main_activity.update_outbox();
             // Draw activity
             main_activity.on_draw();
             // Check if activity has terminated
-            if let Some(ExitReason::Quit) = main_activity.will_umount() {
-                // info!("SetupActivity terminated due to 'Quit'");
-                break;
+            match main_activity.will_umount() {
+                Some(ExitReason::Quit) => break,
+                Some(ExitReason::Compose) => self.run_compose(&mut main_activity),
+                None => {}
             }
             // Sleep for ticks
             sleep(Duration::from_millis(20));
@@ -79,4 +84,25 @@ impl App {
 
         drop(self.context.take());
     }
+
+    /// ### `run_compose`
+    ///
+    /// Write the mail of the main activity in the compose activity, then hand it back
+    fn run_compose(&self, main_activity: &mut TermailActivity) {
+        let Some((draft, ctx)) = main_activity.suspend_for_compose() else {
+            error!("Failed to start ComposeActivity: nothing to compose");
+            return;
+        };
+        let mut compose_activity = ComposeActivity::new(draft, &self.config);
+        compose_activity.on_create(ctx);
+        loop {
+            compose_activity.on_draw();
+            if compose_activity.will_umount().is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(20));
+        }
+        let ctx = compose_activity.on_destroy();
+        main_activity.resume_from_compose(ctx, compose_activity.draft());
+    }
 }
@@ -0,0 +1,825 @@
+//! ## Compose
+//!
+//! `compose` turns the text edited in `$EDITOR` into a mail, and a mail back into that text
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::index::text_body;
+use crate::markdown;
+use crate::mime::{encode_addresses, fold_header, Attachment, MessageBuilder};
+use crate::threading::normalize_subject;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use mailparse::{
+    addrparse, parse_mail, DispositionType, MailAddr, MailHeaderMap, ParsedMail, SingleInfo,
+};
+use std::fmt::Write;
+
+/// Header marking a postponed draft as Markdown
+const MARKDOWN_HEADER: &str = "X-Termail-Markdown";
+
+/// A mail being written, as edited by the user
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Draft {
+    pub from: String,
+    pub to: String,
+    pub cc: String,
+    /// blind copies, sent to but left out of the mail
+    pub bcc: String,
+    pub subject: String,
+    /// headers other than the ones above, kept in order
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// files sent along, not shown in the editor
+    pub attachments: Vec<Attachment>,
+    /// the body is Markdown, sent along with its HTML rendering
+    pub markdown: bool,
+}
+
+/// Who a reply goes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyKind {
+    Sender,
+    All,
+    List,
+}
+
+impl Draft {
+    /// ### `template`
+    ///
+    /// Text handed to the editor: headers, an empty line and the body
+    pub fn template(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "From: {}", self.from);
+        let _ = writeln!(text, "To: {}", self.to);
+        let _ = writeln!(text, "Cc: {}", self.cc);
+        if !self.bcc.is_empty() {
+            let _ = writeln!(text, "Bcc: {}", self.bcc);
+        }
+        let _ = writeln!(text, "Subject: {}", self.subject);
+        for (name, value) in &self.headers {
+            let _ = writeln!(text, "{name}: {value}");
+        }
+        text.push('\n');
+        text.push_str(&self.body);
+        text
+    }
+
+    /// ### `parse`
+    ///
+    /// Read back the text written by the editor
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut draft = Self::default();
+        let mut lines = text.lines().enumerate();
+        let mut last: Option<String> = None;
+        for (number, line) in &mut lines {
+            if line.trim().is_empty() {
+                break;
+            }
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // folded header
+                let name = last
+                    .clone()
+                    .ok_or_else(|| anyhow!("line {}: no header to continue", number + 1))?;
+                let value = draft.header_mut(&name);
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("line {}: not a header: {line}", number + 1))?;
+            let name = name.trim().to_string();
+            if name.is_empty() || name.contains(' ') {
+                return Err(anyhow!("line {}: not a header: {line}", number + 1));
+            }
+            *draft.header_mut(&name) = value.trim().to_string();
+            last = Some(name);
+        }
+        draft.body = lines.map(|(_, line)| line).collect::<Vec<_>>().join("\n");
+        if text.ends_with('\n') && !draft.body.is_empty() {
+            draft.body.push('\n');
+        }
+        Ok(draft)
+    }
+
+    /// ### `from_message`
+    ///
+    /// Draft back from a mail saved by `to_message`, with its headers and attachments
+    pub fn from_message(raw: &[u8]) -> Result<Self> {
+        let mail = parse_mail(raw)?;
+        let mut draft = Self::default();
+        for header in &mail.headers {
+            let name = header.get_key();
+            // written anew when the mail is saved or sent
+            let generated = [
+                "Date",
+                "MIME-Version",
+                "Content-Type",
+                "Content-Transfer-Encoding",
+            ];
+            if generated.iter().any(|g| g.eq_ignore_ascii_case(&name)) {
+                continue;
+            }
+            if name.eq_ignore_ascii_case(MARKDOWN_HEADER) {
+                draft.markdown = true;
+                continue;
+            }
+            *draft.header_mut(&name) = header.get_value();
+        }
+        let parts = if mail.subparts.is_empty() {
+            vec![&mail]
+        } else {
+            mail.subparts.iter().collect()
+        };
+        for (n, part) in parts.into_iter().enumerate() {
+            let mimetype = part.ctype.mimetype.to_lowercase();
+            if n == 0 && mimetype == "text/plain" {
+                draft.body = part.get_body()?.replace("\r\n", "\n");
+                continue;
+            }
+            let disposition = part.get_content_disposition();
+            let filename = disposition
+                .params
+                .get("filename")
+                .or_else(|| part.ctype.params.get("name"))
+                .cloned()
+                .unwrap_or_else(|| "attachment".to_string());
+            draft.attachments.push(Attachment {
+                filename,
+                mimetype,
+                data: part.get_body_raw()?,
+            });
+        }
+        Ok(draft)
+    }
+
+    fn header_mut(&mut self, name: &str) -> &mut String {
+        match name.to_lowercase().as_str() {
+            "from" => &mut self.from,
+            "to" => &mut self.to,
+            "cc" => &mut self.cc,
+            "bcc" => &mut self.bcc,
+            "subject" => &mut self.subject,
+            _ => {
+                let position = self
+                    .headers
+                    .iter()
+                    .position(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .unwrap_or_else(|| {
+                        self.headers.push((name.to_string(), String::new()));
+                        self.headers.len() - 1
+                    });
+                &mut self.headers[position].1
+            }
+        }
+    }
+
+    /// ### `header`
+    ///
+    /// Value of one of the extra headers
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// ### `recipients`
+    ///
+    /// Addresses of the To, Cc and Bcc headers
+    pub fn recipients(&self) -> Result<Vec<String>> {
+        let mut recipients = Vec::new();
+        for value in &[&self.to, &self.cc, &self.bcc] {
+            if value.trim().is_empty() {
+                continue;
+            }
+            for addr in addrparse(value)?.iter() {
+                match addr {
+                    MailAddr::Single(info) => recipients.push(info.addr.clone()),
+                    MailAddr::Group(group) => {
+                        recipients.extend(group.addrs.iter().map(|i| i.addr.clone()));
+                    }
+                }
+            }
+        }
+        Ok(recipients)
+    }
+
+    /// ### `sender`
+    ///
+    /// Address of the From header
+    pub fn sender(&self) -> Result<String> {
+        single_addresses(&self.from)
+            .into_iter()
+            .next()
+            .map(|info| info.addr)
+            .ok_or_else(|| anyhow!("The mail has no From address"))
+    }
+
+    /// ### `check`
+    ///
+    /// Make sure the mail can be sent
+    pub fn check(&self) -> Result<()> {
+        self.sender()?;
+        if self.recipients()?.is_empty() {
+            return Err(anyhow!("The mail has no recipient"));
+        }
+        Ok(())
+    }
+
+    /// ### `set_signature`
+    ///
+    /// Swap the signature `old` at the end of the body for `new`
+    pub fn set_signature(&mut self, old: &str, new: &str) {
+        let old = signature_block(old);
+        if !old.is_empty() && self.body.ends_with(&old) {
+            self.body.truncate(self.body.len() - old.len());
+        }
+        self.body.push_str(&signature_block(new));
+    }
+
+    /// ### `summary`
+    ///
+    /// Lines shown before the mail is sent
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("From:    {}", self.from),
+            format!("To:      {}", self.to),
+        ];
+        if !self.cc.is_empty() {
+            lines.push(format!("Cc:      {}", self.cc));
+        }
+        if !self.bcc.is_empty() {
+            lines.push(format!("Bcc:     {}", self.bcc));
+        }
+        lines.push(format!("Subject: {}", self.subject));
+        lines.push(format!(
+            "{} lines of {}",
+            self.body.lines().count(),
+            if self.markdown {
+                "Markdown, sent with an HTML part"
+            } else {
+                "text"
+            }
+        ));
+        lines
+    }
+
+    /// ### `to_message`
+    ///
+    /// The mail in RFC 5322 format, dated `now`, without its blind copies
+    pub fn to_message(&self, now: DateTime<Local>) -> Vec<u8> {
+        self.build(now, false)
+    }
+
+    /// ### `to_postponed`
+    ///
+    /// The mail as saved to Drafts, keeping the Bcc header and the Markdown mark for when
+    /// it is resumed
+    pub fn to_postponed(&self, now: DateTime<Local>) -> Vec<u8> {
+        self.build(now, true)
+    }
+
+    /// ### `bounce`
+    ///
+    /// The mail `raw` resent unchanged from `from` to `to`, with the Resent headers on top
+    /// (RFC 5322 3.6.6)
+    pub fn bounce(&self, raw: &[u8], now: DateTime<Local>) -> Vec<u8> {
+        // the new headers end their lines like the mail does
+        let newline = if raw.windows(2).any(|w| w == b"\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut message = String::new();
+        for (name, value) in [
+            ("Resent-From", encode_addresses(&self.from)),
+            ("Resent-To", encode_addresses(&self.to)),
+            ("Resent-Date", now.to_rfc2822()),
+            ("Resent-Message-ID", self.new_message_id(now)),
+        ] {
+            message.push_str(&fold_header(name, &value).replace("\r\n", newline));
+        }
+        let mut message = message.into_bytes();
+        message.extend_from_slice(raw);
+        message
+    }
+
+    /// Message-ID for a mail sent now, in the domain of the sender
+    fn new_message_id(&self, now: DateTime<Local>) -> String {
+        let domain = self
+            .from
+            .rsplit('@')
+            .next()
+            .map(|d| d.trim_end_matches('>').trim())
+            .filter(|d| !d.is_empty() && self.from.contains('@'))
+            .unwrap_or("localhost");
+        format!(
+            "<{}.{}@{}>",
+            now.timestamp_nanos_opt().unwrap_or_default(),
+            std::process::id(),
+            domain
+        )
+    }
+
+    fn build(&self, now: DateTime<Local>, postponed: bool) -> Vec<u8> {
+        let mut builder = MessageBuilder::default();
+        builder
+            .add_address_header("From", &self.from)
+            .add_address_header("To", &self.to);
+        if !self.cc.is_empty() {
+            builder.add_address_header("Cc", &self.cc);
+        }
+        if postponed && !self.bcc.is_empty() {
+            builder.add_address_header("Bcc", &self.bcc);
+        }
+        builder
+            .add_header("Subject", &self.subject)
+            .add_header("Date", &now.to_rfc2822());
+        if self.header("Message-ID").is_none() {
+            builder.add_header("Message-ID", &self.new_message_id(now));
+        }
+        for (name, value) in &self.headers {
+            builder.add_header(name, value);
+        }
+        builder.set_text(&self.body);
+        if self.markdown && postponed {
+            builder.add_header(MARKDOWN_HEADER, "yes");
+        } else if self.markdown {
+            builder.set_html(&markdown::to_html(&self.body));
+        }
+        for attachment in &self.attachments {
+            builder.add_attachment(attachment.clone());
+        }
+        builder.build(&format!(
+            "termail-{}",
+            now.timestamp_nanos_opt().unwrap_or_default()
+        ))
+    }
+}
+
+/// ### `signature_block`
+///
+/// Signature as put below the body, after the `-- ` separator
+pub fn signature_block(signature: &str) -> String {
+    let signature = signature.trim_end();
+    if signature.is_empty() {
+        return String::new();
+    }
+    format!("\n-- \n{signature}\n")
+}
+
+/// ### `reply`
+///
+/// Draft answering `mail`, quoting its text below `attribution`. `own` addresses are left
+/// out of group replies, and the one the mail was sent to becomes the sender.
+pub fn reply(
+    mail: &ParsedMail,
+    kind: ReplyKind,
+    own: &[String],
+    attribution: &str,
+) -> Result<Draft> {
+    let header = |name: &str| mail.headers.get_first_value(name).unwrap_or_default();
+    let reply_to = header("Reply-To");
+    let sender = if reply_to.trim().is_empty() {
+        header("From")
+    } else {
+        reply_to
+    };
+    let is_own = |info: &SingleInfo| own.iter().any(|o| o.eq_ignore_ascii_case(&info.addr));
+    let (to, cc) = match kind {
+        ReplyKind::Sender => (sender, String::new()),
+        ReplyKind::All => {
+            let mut seen = Vec::new();
+            let to = join_addresses(&[&sender, &header("To")], &is_own, &mut seen);
+            let cc = join_addresses(&[&header("Cc")], &is_own, &mut seen);
+            (to, cc)
+        }
+        ReplyKind::List => {
+            let list = list_post(&header("List-Post"))
+                .ok_or_else(|| anyhow!("This mail was not sent through a mailing list"))?;
+            (list, String::new())
+        }
+    };
+    // answer from the address the mail was sent to
+    let from = ["To", "Cc", "Delivered-To"]
+        .iter()
+        .flat_map(|name| single_addresses(&header(name)))
+        .find(|info| is_own(info))
+        .map(|info| info.to_string())
+        .unwrap_or_default();
+
+    let mut headers = Vec::new();
+    let message_id = header("Message-ID").trim().to_string();
+    if !message_id.is_empty() {
+        let references = header("References");
+        let references = references
+            .split_whitespace()
+            .chain(Some(message_id.as_str()));
+        headers.push(("In-Reply-To".to_string(), message_id.clone()));
+        headers.push((
+            "References".to_string(),
+            references.collect::<Vec<_>>().join(" "),
+        ));
+    }
+
+    let mut body = format!("\n\n{}\n", expand_attribution(attribution, mail));
+    for line in original_text(mail).lines() {
+        if line.is_empty() || line.starts_with('>') {
+            let _ = writeln!(body, ">{line}");
+        } else {
+            let _ = writeln!(body, "> {line}");
+        }
+    }
+    Ok(Draft {
+        from,
+        to,
+        cc,
+        bcc: String::new(),
+        subject: format!("Re: {}", normalize_subject(&header("Subject")).0),
+        headers,
+        body,
+        attachments: Vec::new(),
+        markdown: false,
+    })
+}
+
+/// ### `forward`
+///
+/// Draft passing `mail` on, with its text inline or the whole of `raw` attached
+pub fn forward(mail: &ParsedMail, raw: &[u8], attached: bool) -> Draft {
+    let header = |name: &str| mail.headers.get_first_value(name).unwrap_or_default();
+    let subject = header("Subject");
+    let mut draft = Draft {
+        subject: format!("Fwd: {}", normalize_subject(&subject).0),
+        ..Draft::default()
+    };
+    if attached {
+        let name: String = normalize_subject(&subject)
+            .0
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        draft.attachments.push(Attachment {
+            filename: format!("{}.eml", if name.is_empty() { "mail" } else { &name }),
+            mimetype: "message/rfc822".to_string(),
+            data: raw.to_vec(),
+        });
+    } else {
+        let mut body = "\n\n---------- Forwarded message ----------\n".to_string();
+        for name in &["From", "Date", "Subject", "To", "Cc"] {
+            let value = header(name);
+            if !value.is_empty() {
+                let _ = writeln!(body, "{name}: {value}");
+            }
+        }
+        body.push('\n');
+        body.push_str(&original_text(mail));
+        draft.body = body;
+    }
+    draft
+}
+
+/// Fill in `%n` (sender name), `%a` (sender address), `%d` (date) and `%s` (subject)
+fn expand_attribution(attribution: &str, mail: &ParsedMail) -> String {
+    let header = |name: &str| mail.headers.get_first_value(name).unwrap_or_default();
+    let sender = single_addresses(&header("From")).into_iter().next();
+    let address = sender.as_ref().map(|s| s.addr.clone()).unwrap_or_default();
+    let name = sender
+        .and_then(|s| s.display_name)
+        .unwrap_or_else(|| address.clone());
+    let mut line = String::new();
+    let mut chars = attribution.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            line.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => line.push_str(&name),
+            Some('a') => line.push_str(&address),
+            Some('d') => line.push_str(header("Date").trim()),
+            Some('s') => line.push_str(header("Subject").trim()),
+            Some(other) => line.push(other),
+            None => line.push('%'),
+        }
+    }
+    line
+}
+
+/// The plain text of a mail if it has one, or the text of its html
+fn original_text(mail: &ParsedMail) -> String {
+    fn plain(mail: &ParsedMail) -> Option<String> {
+        if mail.get_content_disposition().disposition == DispositionType::Attachment {
+            return None;
+        }
+        if mail.subparts.is_empty() {
+            return (mail.ctype.mimetype.eq_ignore_ascii_case("text/plain"))
+                .then(|| mail.get_body().ok())
+                .flatten();
+        }
+        mail.subparts.iter().find_map(plain)
+    }
+    plain(mail)
+        .unwrap_or_else(|| text_body(mail))
+        .replace("\r\n", "\n")
+}
+
+/// ### `single_addresses`
+///
+/// Addresses of a header, with the members of groups
+pub fn single_addresses(value: &str) -> Vec<SingleInfo> {
+    if value.trim().is_empty() {
+        return Vec::new();
+    }
+    addrparse(value).map_or_else(
+        |_| Vec::new(),
+        |list| {
+            list.iter()
+                .flat_map(|addr| match addr {
+                    MailAddr::Single(info) => vec![info.clone()],
+                    MailAddr::Group(group) => group.addrs.clone(),
+                })
+                .collect()
+        },
+    )
+}
+
+/// ### `mailbox`
+///
+/// Name and address as written in an address header, the name quoted when it needs to be
+pub fn mailbox(name: &str, address: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        return address.to_string();
+    }
+    if name.contains(|c| "()<>[]:;@\\,.\"".contains(c)) {
+        let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+        return format!("\"{quoted}\" <{address}>");
+    }
+    format!("{name} <{address}>")
+}
+
+/// Addresses of `values` which are not our own or listed already
+fn join_addresses(
+    values: &[&str],
+    is_own: &dyn Fn(&SingleInfo) -> bool,
+    seen: &mut Vec<String>,
+) -> String {
+    let mut kept = Vec::new();
+    for info in values.iter().flat_map(|value| single_addresses(value)) {
+        let addr = info.addr.to_lowercase();
+        if is_own(&info) || seen.contains(&addr) {
+            continue;
+        }
+        seen.push(addr);
+        kept.push(info.to_string());
+    }
+    kept.join(", ")
+}
+
+/// Posting address of a `List-Post` header like `<mailto:list@example.com>`
+fn list_post(value: &str) -> Option<String> {
+    value.split(',').find_map(|part| {
+        let part = part.trim().trim_start_matches('<').trim_end_matches('>');
+        let addr = part.strip_prefix("mailto:")?;
+        Some(addr.split('?').next().unwrap_or(addr).to_string())
+    })
+}
+
+/// ### `editor`
+///
+/// Editor command from `$VISUAL` or `$EDITOR`
+pub fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_draft_roundtrip() {
+        let draft = Draft {
+            from: "Me <me@example.com>".to_string(),
+            to: "you@example.com".to_string(),
+            cc: String::new(),
+            bcc: "boss@example.com".to_string(),
+            subject: "Lunch".to_string(),
+            headers: vec![("In-Reply-To".to_string(), "<1@example.com>".to_string())],
+            body: "See you at noon.\n".to_string(),
+            attachments: Vec::new(),
+            markdown: false,
+        };
+        assert_eq!(Draft::parse(&draft.template()).unwrap(), draft);
+
+        let edited = Draft::parse("to: a@b.c,\n  d@e.f\nSubject: Hi\n\nbody\n\nmore").unwrap();
+        assert_eq!(edited.to, "a@b.c, d@e.f");
+        assert_eq!(edited.body, "body\n\nmore");
+        assert_eq!(
+            edited.recipients().unwrap(),
+            vec!["a@b.c".to_string(), "d@e.f".to_string()]
+        );
+        assert!(edited.check().is_err());
+        assert_eq!(draft.recipients().unwrap().len(), 2);
+        assert!(Draft::parse("not a header\n\nbody").is_err());
+    }
+
+    #[test]
+    fn test_draft_message() {
+        let draft = Draft {
+            from: "Zoë <zoe@example.com>".to_string(),
+            to: "you@example.com".to_string(),
+            subject: "Grüße".to_string(),
+            body: "Hallo\n".to_string(),
+            ..Draft::default()
+        };
+        let message = draft.to_message(Local::now());
+        let parsed = parse_mail(&message).unwrap();
+        assert_eq!(parsed.headers.get_first_value("Subject").unwrap(), "Grüße");
+        assert_eq!(
+            parsed.headers.get_first_value("From").unwrap(),
+            "Zoë <zoe@example.com>"
+        );
+        assert!(parsed
+            .headers
+            .get_first_value("Message-ID")
+            .unwrap()
+            .ends_with("@example.com>"));
+        assert_eq!(parsed.get_body().unwrap(), "Hallo\r\n");
+
+        let markdown = Draft {
+            body: "- *one*\n- two\n".to_string(),
+            markdown: true,
+            ..draft
+        };
+        let message = markdown.to_message(Local::now());
+        let parsed = parse_mail(&message).unwrap();
+        assert_eq!(parsed.ctype.mimetype, "multipart/alternative");
+        assert_eq!(parsed.subparts[0].ctype.mimetype, "text/plain");
+        assert_eq!(
+            parsed.subparts[0].get_body().unwrap(),
+            "- *one*\r\n- two\r\n"
+        );
+        assert_eq!(parsed.subparts[1].ctype.mimetype, "text/html");
+        assert!(parsed.subparts[1]
+            .get_body()
+            .unwrap()
+            .contains("<ul>\r\n<li><em>one</em>\r\n</li>"));
+    }
+
+    const ORIGINAL: &str = "From: Ann <ann@example.com>\r\nTo: me@example.com, bob@example.com\r\n\
+        Cc: Me Too <ME@example.com>, carol@example.com\r\nSubject: Re: RE: Plans\r\n\
+        Date: Mon, 1 Mar 2021 10:00:00 +0000\r\nMessage-ID: <2@example.com>\r\n\
+        References: <0@example.com> <1@example.com>\r\n\
+        List-Post: <mailto:plans@lists.example.com>\r\n\r\nHello\r\n\r\n> earlier\r\n";
+
+    #[test]
+    fn test_reply() {
+        let mail = parse_mail(ORIGINAL.as_bytes()).unwrap();
+        let own = vec!["me@example.com".to_string()];
+        let draft = reply(&mail, ReplyKind::Sender, &own, "On %d, %n wrote:").unwrap();
+        assert_eq!(draft.to, "Ann <ann@example.com>");
+        assert_eq!(draft.from, "me@example.com");
+        assert_eq!(draft.subject, "Re: Plans");
+        assert_eq!(draft.header("In-Reply-To"), Some("<2@example.com>"));
+        assert_eq!(
+            draft.header("References"),
+            Some("<0@example.com> <1@example.com> <2@example.com>")
+        );
+        assert_eq!(
+            draft.body,
+            "\n\nOn Mon, 1 Mar 2021 10:00:00 +0000, Ann wrote:\n> Hello\n>\n>> earlier\n"
+        );
+
+        let all = reply(&mail, ReplyKind::All, &own, "").unwrap();
+        assert_eq!(all.to, "\"Ann\" <ann@example.com>, bob@example.com");
+        assert_eq!(all.cc, "carol@example.com");
+
+        let list = reply(&mail, ReplyKind::List, &own, "").unwrap();
+        assert_eq!(list.to, "plans@lists.example.com");
+    }
+
+    #[test]
+    fn test_forward() {
+        let mail = parse_mail(ORIGINAL.as_bytes()).unwrap();
+        let inline = forward(&mail, ORIGINAL.as_bytes(), false);
+        assert_eq!(inline.subject, "Fwd: Plans");
+        assert!(inline.body.contains("From: Ann <ann@example.com>\n"));
+        assert!(inline.body.ends_with("Hello\n\n> earlier\n"));
+
+        let attached = forward(&mail, ORIGINAL.as_bytes(), true);
+        assert_eq!(attached.attachments[0].filename, "Plans.eml");
+        let message = attached.to_message(Local::now());
+        let parsed = parse_mail(&message).unwrap();
+        assert_eq!(parsed.subparts[1].ctype.mimetype, "message/rfc822");
+    }
+
+    #[test]
+    fn test_bounce() {
+        let resend = Draft {
+            from: "Zoë <zoe@example.com>".to_string(),
+            to: "dan@example.com, Eve <eve@example.com>".to_string(),
+            ..Draft::default()
+        };
+        let message = resend.bounce(ORIGINAL.as_bytes(), Local::now());
+        assert!(message.ends_with(ORIGINAL.as_bytes()));
+        let parsed = parse_mail(&message).unwrap();
+        let header = |name| parsed.headers.get_first_value(name).unwrap();
+        assert_eq!(header("Resent-From"), "Zoë <zoe@example.com>");
+        assert_eq!(
+            header("Resent-To"),
+            "dan@example.com, Eve <eve@example.com>"
+        );
+        assert!(header("Resent-Message-ID").ends_with("@example.com>"));
+        assert!(parsed.headers.get_first_value("Resent-Date").is_some());
+        assert_eq!(header("Message-ID"), "<2@example.com>");
+        assert_eq!(
+            resend.recipients().unwrap(),
+            vec!["dan@example.com".to_string(), "eve@example.com".to_string()]
+        );
+        // 8bit mails which are not UTF-8 are resent as they are
+        let latin1 = b"Subject: x\n\n\xe9t\xe9\n";
+        let message = resend.bounce(latin1, Local::now());
+        assert!(message.ends_with(latin1));
+        assert!(message.starts_with(b"Resent-From: =?UTF-8?B?"));
+        assert!(!message.windows(2).any(|w| w == b"\r\n"));
+    }
+
+    #[test]
+    fn test_draft_from_message() {
+        let mail = parse_mail(ORIGINAL.as_bytes()).unwrap();
+        let mut draft = reply(&mail, ReplyKind::Sender, &[], "%n wrote:").unwrap();
+        draft.from = "Zoë <zoe@example.com>".to_string();
+        draft.attachments.push(Attachment {
+            filename: "notes.bin".to_string(),
+            mimetype: "application/octet-stream".to_string(),
+            data: vec![0, 159, 146, 150, 255],
+        });
+        draft.bcc = "boss@example.com".to_string();
+        draft.markdown = true;
+        let sent = draft.to_message(Local::now());
+        let sent = parse_mail(&sent).unwrap();
+        assert_eq!(sent.headers.get_first_value("Bcc"), None);
+        assert_eq!(sent.subparts[0].ctype.mimetype, "multipart/alternative");
+        assert_eq!(sent.subparts[1].ctype.mimetype, "application/octet-stream");
+        let message = draft.to_postponed(Local::now());
+        let resumed = Draft::from_message(&message).unwrap();
+        let message_id = resumed.header("Message-ID").unwrap().to_string();
+        assert!(message_id.ends_with("@example.com>"));
+        assert_eq!(
+            resumed,
+            Draft {
+                headers: [
+                    vec![("Message-ID".to_string(), message_id)],
+                    draft.headers.clone(),
+                ]
+                .concat(),
+                ..draft
+            }
+        );
+    }
+
+    #[test]
+    fn test_draft_signature() {
+        let mut draft = Draft {
+            body: "Hi\n".to_string(),
+            ..Draft::default()
+        };
+        draft.set_signature("", "Jane\n");
+        assert_eq!(draft.body, "Hi\n\n-- \nJane\n");
+        draft.set_signature("Jane", "J. Doe\nExample Inc.");
+        assert_eq!(draft.body, "Hi\n\n-- \nJ. Doe\nExample Inc.\n");
+        // edited away, so kept as it is
+        draft.body.push_str("PS\n");
+        draft.set_signature("J. Doe\nExample Inc.", "");
+        assert_eq!(draft.body, "Hi\n\n-- \nJ. Doe\nExample Inc.\nPS\n");
+    }
+}
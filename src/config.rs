@@ -22,24 +22,362 @@ use anyhow::{anyhow, Result};
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
+use chrono::format::{Item, StrftimeItems};
+use log::error;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::{self, read_to_string};
 use std::path::PathBuf;
 
 pub const MAIL_DIR: &str = "~/.local/share/mail";
 
+/// Graphics protocol used to show image attachments in the reader
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocol {
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    Halfblocks,
+    None,
+}
+
+/// Column which can be shown in the mail list
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MaillistColumn {
+    Index,
+    Flags,
+    Date,
+    From,
+    To,
+    Subject,
+    Size,
+    Attachment,
+    Thread,
+    List,
+    Preview,
+    Folder,
+    Tags,
+}
+
+impl MaillistColumn {
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::Index => "Idx",
+            Self::Flags => "Flags",
+            Self::Date => "Time",
+            Self::From => "From",
+            Self::To => "To",
+            Self::Subject => "Title",
+            Self::Size => "Size",
+            Self::Attachment => "Att",
+            Self::Thread => "Thr",
+            Self::List => "List",
+            Self::Preview => "Preview",
+            Self::Folder => "Folder",
+            Self::Tags => "Tags",
+        }
+    }
+}
+
+/// A mail list column, with its width in percent of the list
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ColumnConfig {
+    pub column: MaillistColumn,
+    pub width: u16,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MaillistConfig {
+    /// strftime like format, see `chrono::format::strftime`
+    pub date_format: String,
+    /// show "2h ago" for mails of the last week
+    pub relative_dates: bool,
+    /// "local", "utc" or a fixed offset like "+08:00"
+    pub timezone: String,
+    /// group mails into conversations
+    pub threaded: bool,
+    // last, as toml writes tables after values
+    pub columns: Vec<ColumnConfig>,
+}
+
+impl MaillistConfig {
+    /// ### `check_date_format`
+    ///
+    /// Go back to the default date format if the configured one can't be used,
+    /// since formatting a date with it would panic
+    pub fn check_date_format(&mut self) {
+        let invalid = StrftimeItems::new(&self.date_format).any(|item| item == Item::Error);
+        if invalid {
+            error!(
+                "Invalid date_format {:?}, using the default",
+                self.date_format
+            );
+            self.date_format = Self::default().date_format;
+        }
+    }
+}
+
+impl Default for MaillistConfig {
+    fn default() -> Self {
+        let column = |column, width| ColumnConfig { column, width };
+        Self {
+            columns: vec![
+                column(MaillistColumn::Index, 5),
+                column(MaillistColumn::Date, 18),
+                column(MaillistColumn::From, 22),
+                column(MaillistColumn::Subject, 55),
+            ],
+            date_format: "%y-%m-%d %H:%M".to_string(),
+            relative_dates: false,
+            timezone: "local".to_string(),
+            threaded: true,
+        }
+    }
+}
+
+/// What the mail list is sorted by
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Date,
+    Sender,
+    Subject,
+    Size,
+    Recipient,
+    Flagged,
+}
+
+impl SortKey {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Date => Self::Sender,
+            Self::Sender => Self::Subject,
+            Self::Subject => Self::Size,
+            Self::Size => Self::Recipient,
+            Self::Recipient => Self::Flagged,
+            Self::Flagged => Self::Date,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Sender => "sender",
+            Self::Subject => "subject",
+            Self::Size => "size",
+            Self::Recipient => "recipient",
+            Self::Flagged => "flagged",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+    pub unread_first: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Date,
+            descending: true,
+            unread_first: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ComposeConfig {
+    /// sender of new mails, `$EMAIL` when empty
+    pub from: String,
+    /// other addresses of ours, left out of group replies
+    pub addresses: Vec<String>,
+    /// line above quoted text: %n sender name, %a sender address, %d date, %s subject
+    pub attribution: String,
+    /// maildir for postponed mails under the mail dir, Drafts next to the current folder
+    /// when empty
+    pub drafts: String,
+    /// maildir for sent mails under the mail dir, Sent next to the current folder when empty
+    pub sent: String,
+    /// maildir under the mail dir for mails waiting to be sent
+    pub outbox: String,
+    /// write mails in termail's own editor instead of `$EDITOR`
+    pub builtin_editor: bool,
+    /// hunspell `.dic` file the built-in editor checks spelling with, its `.aff` file next to
+    /// it. No checking when empty
+    pub dictionary: String,
+    /// new mails are written in Markdown and sent with an HTML part
+    pub markdown: bool,
+}
+
+impl Default for ComposeConfig {
+    fn default() -> Self {
+        Self {
+            from: String::new(),
+            addresses: Vec::new(),
+            attribution: "On %d, %n wrote:".to_string(),
+            drafts: String::new(),
+            sent: String::new(),
+            outbox: "Outbox".to_string(),
+            builtin_editor: false,
+            dictionary: String::new(),
+            markdown: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// plain connection upgraded with STARTTLS, usually on port 587
+    StartTls,
+    /// no encryption, only for local servers
+    None,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpAuth {
+    Plain,
+    Login,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// 465, 587 or 25 by `security` when not set
+    pub port: Option<u16>,
+    #[serde(default = "default_security")]
+    pub security: SmtpSecurity,
+    /// user to log in as, with the password of `password_command`, no login when not set
+    pub user: Option<String>,
+    #[serde(default = "default_auth")]
+    pub auth: SmtpAuth,
+    /// shell command printing the password, like `pass show mail`
+    pub password_command: Option<String>,
+    /// log in even when the connection is not encrypted, like to a server on this machine
+    #[serde(default)]
+    pub allow_plaintext_auth: bool,
+}
+
+const fn default_security() -> SmtpSecurity {
+    SmtpSecurity::StartTls
+}
+
+const fn default_auth() -> SmtpAuth {
+    SmtpAuth::Plain
+}
+
+const fn default_save_sent() -> bool {
+    true
+}
+
+impl SmtpConfig {
+    pub const fn port(&self) -> u16 {
+        match (self.port, self.security) {
+            (Some(port), _) => port,
+            (None, SmtpSecurity::Tls) => 465,
+            (None, SmtpSecurity::StartTls) => 587,
+            (None, SmtpSecurity::None) => 25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AccountConfig {
+    pub name: String,
+    /// address mails of this account are sent from
+    pub address: String,
+    /// folder of the account under the mail dir
+    #[serde(default)]
+    pub folder: String,
+    /// command reading the mail on stdin, like `msmtp -a work`, used instead of `smtp` when
+    /// set. It gets `-i -- <recipients>` appended, unless it reads them from the headers
+    /// itself with `-t` or `--read-recipients`
+    pub sendmail_command: Option<String>,
+    /// keep a copy of sent mails, off for servers like Gmail which keep one themselves
+    #[serde(default = "default_save_sent")]
+    pub save_sent: bool,
+    /// Sent maildir of the account under the mail dir, `compose.sent` when not set
+    pub sent: Option<String>,
+    // last, as toml writes tables after values
+    pub smtp: Option<SmtpConfig>,
+}
+
+/// A name and address to send from, with its signature
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IdentityConfig {
+    /// name shown to recipients, like `Jane Doe`
+    #[serde(default)]
+    pub name: String,
+    pub address: String,
+    /// put below new mails and replies, after a `-- ` line
+    #[serde(default)]
+    pub signature: String,
+    /// folders under the mail dir where new mails and replies are sent from this identity
+    #[serde(default)]
+    pub folders: Vec<String>,
+    /// account whose transport sends the mails, found by address or folder when not set
+    pub account: Option<String>,
+}
+
+impl IdentityConfig {
+    /// ### `mailbox`
+    ///
+    /// Name and address as written in a From header
+    pub fn mailbox(&self) -> String {
+        crate::compose::mailbox(&self.name, &self.address)
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 #[allow(clippy::module_name_repetitions)]
 pub struct TermailConfig {
     pub mail_dir: String,
     #[serde(skip_serializing)]
     pub mail_dir_from_cli: Option<String>,
+    #[serde(default)]
+    pub image_protocol: ImageProtocol,
+    /// folders merged into All Inboxes, relative to the mail dir; every INBOX when empty
+    #[serde(default)]
+    pub inboxes: Vec<String>,
+    /// vCard file, or folder of them, the address book imports from and exports to
+    #[serde(default)]
+    pub vcards: String,
+    #[serde(default)]
+    pub maillist: MaillistConfig,
+    #[serde(default)]
+    pub compose: ComposeConfig,
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub identities: Vec<IdentityConfig>,
+    /// sort order of each folder, by path
+    #[serde(default)]
+    pub sort_orders: BTreeMap<String, SortOrder>,
 }
 impl Default for TermailConfig {
     fn default() -> Self {
         Self {
             mail_dir: MAIL_DIR.to_string(),
             mail_dir_from_cli: None,
+            image_protocol: ImageProtocol::default(),
+            inboxes: Vec::new(),
+            vcards: String::new(),
+            maillist: MaillistConfig::default(),
+            compose: ComposeConfig::default(),
+            accounts: Vec::new(),
+            identities: Vec::new(),
+            sort_orders: BTreeMap::new(),
         }
     }
 }
@@ -65,7 +403,8 @@ impl TermailConfig {
         }
 
         let string = read_to_string(path.to_string_lossy().as_ref())?;
-        let config: Self = toml::from_str(&string)?;
+        let mut config: Self = toml::from_str(&string)?;
+        config.maillist.check_date_format();
         *self = config;
         Ok(())
     }
@@ -81,3 +420,73 @@ pub fn get_app_config_path() -> Result<PathBuf> {
     }
     Ok(path)
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_config_roundtrip() {
+        let mut config = TermailConfig::default();
+        let order = SortOrder {
+            key: SortKey::Subject,
+            descending: false,
+            unread_first: false,
+        };
+        config.sort_orders.insert("/mail/INBOX".to_string(), order);
+        config.accounts.push(AccountConfig {
+            name: "work".to_string(),
+            address: "me@example.com".to_string(),
+            folder: "work".to_string(),
+            sendmail_command: None,
+            save_sent: false,
+            sent: Some("work/Sent".to_string()),
+            smtp: Some(SmtpConfig {
+                host: "smtp.example.com".to_string(),
+                port: None,
+                security: SmtpSecurity::Tls,
+                user: Some("me".to_string()),
+                auth: SmtpAuth::Login,
+                password_command: Some("pass show mail".to_string()),
+                allow_plaintext_auth: false,
+            }),
+        });
+        config.identities.push(IdentityConfig {
+            name: "Doe, Jane".to_string(),
+            address: "jane@example.com".to_string(),
+            signature: "Jane\nExample Inc.".to_string(),
+            folders: vec!["work/lists".to_string()],
+            account: Some("work".to_string()),
+        });
+        let string = toml::to_string(&config).unwrap();
+        let loaded: TermailConfig = toml::from_str(&string).unwrap();
+        assert_eq!(loaded.accounts, config.accounts);
+        assert_eq!(
+            loaded.accounts[0].smtp.as_ref().map(SmtpConfig::port),
+            Some(465)
+        );
+        assert_eq!(loaded.identities, config.identities);
+        assert_eq!(
+            loaded.identities[0].mailbox(),
+            "\"Doe, Jane\" <jane@example.com>"
+        );
+        assert_eq!(loaded.sort_orders.get("/mail/INBOX"), Some(&order));
+        assert_eq!(loaded.maillist.columns, config.maillist.columns);
+    }
+
+    #[test]
+    fn test_config_date_format() {
+        let mut config = MaillistConfig {
+            date_format: "%d/%m %H:%M".to_string(),
+            ..MaillistConfig::default()
+        };
+        config.check_date_format();
+        assert_eq!(config.date_format, "%d/%m %H:%M");
+        config.date_format = "%Y-%m-%d %Q".to_string();
+        config.check_date_format();
+        assert_eq!(config.date_format, MaillistConfig::default().date_format);
+    }
+}
@@ -0,0 +1,819 @@
+//! ## Ical
+//!
+//! `ical` reads meeting invitations (RFC 5545) and builds iTIP replies for them
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::mime::MessageBuilder;
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc,
+    Weekday,
+};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartStat {
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+impl PartStat {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Accepted => "ACCEPTED",
+            Self::Declined => "DECLINED",
+            Self::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// Subject prefix used by most clients for iTIP replies
+    pub const fn subject_prefix(self) -> &'static str {
+        match self {
+            Self::Accepted => "Accepted",
+            Self::Declined => "Declined",
+            Self::Tentative => "Tentative",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Person {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+impl std::fmt::Display for Person {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name} <{}>", self.email),
+            None => write!(f, "{}", self.email),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attendee {
+    pub person: Person,
+    pub partstat: Option<String>,
+    pub role: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Event {
+    pub uid: String,
+    pub sequence: Option<String>,
+    pub recurrence_id: Option<String>,
+    pub summary: Option<String>,
+    pub location: Option<String>,
+    pub organizer: Option<Person>,
+    pub attendees: Vec<Attendee>,
+    pub start: Option<DateTime<Local>>,
+    pub end: Option<DateTime<Local>>,
+    pub all_day: bool,
+    // DTSTART/DTEND lines for replies, in UTC when they were in a VTIMEZONE
+    dtstart_line: Option<String>,
+    dtend_line: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Calendar {
+    pub method: Option<String>,
+    pub events: Vec<Event>,
+}
+
+struct ContentLine {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+    raw: String,
+}
+
+impl ContentLine {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Observance {
+    start: NaiveDateTime,
+    offset_to: i32,
+    // BYMONTH and BYDAY of the yearly RRULE, if any
+    by_month: Option<u32>,
+    by_day: Option<(i32, Weekday)>,
+}
+
+struct Timezone {
+    tzid: String,
+    observances: Vec<Observance>,
+}
+
+impl Calendar {
+    /// ### `parse`
+    ///
+    /// Parse a `text/calendar` body. Only `VEVENT` and `VTIMEZONE` are read.
+    pub fn parse(text: &str) -> Result<Self> {
+        let lines: Vec<ContentLine> = unfold(text).iter().filter_map(|l| parse_line(l)).collect();
+        if !lines
+            .iter()
+            .any(|l| l.name == "BEGIN" && l.value.eq_ignore_ascii_case("VCALENDAR"))
+        {
+            return Err(anyhow!("not an iCalendar object"));
+        }
+
+        let timezones = parse_timezones(&lines);
+        let mut calendar = Self::default();
+        let mut event: Option<Event> = None;
+        let mut depth_in_event = 0;
+        for line in &lines {
+            match (line.name.as_str(), event.as_mut()) {
+                ("METHOD", None) => calendar.method = Some(line.value.to_uppercase()),
+                ("BEGIN", None) if line.value.eq_ignore_ascii_case("VEVENT") => {
+                    event = Some(Event::default());
+                }
+                // VALARM and friends nested in the event
+                ("BEGIN", Some(_)) => depth_in_event += 1,
+                ("END", Some(_)) if depth_in_event > 0 => depth_in_event -= 1,
+                ("END", Some(_)) => {
+                    if let Some(e) = event.take() {
+                        calendar.events.push(e);
+                    }
+                }
+                (_, Some(_)) if depth_in_event > 0 => {}
+                (name, Some(e)) => e.set_property(name, line, &timezones),
+                _ => {}
+            }
+        }
+        if calendar.events.is_empty() {
+            return Err(anyhow!("no VEVENT in calendar"));
+        }
+        Ok(calendar)
+    }
+}
+
+impl Event {
+    fn set_property(&mut self, name: &str, line: &ContentLine, timezones: &[Timezone]) {
+        match name {
+            "UID" => self.uid.clone_from(&line.value),
+            "SEQUENCE" => self.sequence = Some(line.value.clone()),
+            "RECURRENCE-ID" => self.recurrence_id = Some(utc_line(line, timezones)),
+            "SUMMARY" => self.summary = Some(unescape(&line.value)),
+            "LOCATION" => self.location = Some(unescape(&line.value)),
+            "ORGANIZER" => self.organizer = Some(person(line)),
+            "ATTENDEE" => self.attendees.push(Attendee {
+                person: person(line),
+                partstat: line.param("PARTSTAT").map(str::to_uppercase),
+                role: line.param("ROLE").map(str::to_uppercase),
+            }),
+            "DTSTART" => {
+                self.all_day = line
+                    .param("VALUE")
+                    .map_or(line.value.len() == 8, |v| v.eq_ignore_ascii_case("DATE"));
+                self.start = parse_datetime(line, timezones);
+                self.dtstart_line = Some(utc_line(line, timezones));
+            }
+            "DTEND" => {
+                self.end = parse_datetime(line, timezones);
+                self.dtend_line = Some(utc_line(line, timezones));
+            }
+            _ => {}
+        }
+    }
+
+    /// ### `summary_card`
+    ///
+    /// Lines describing the event, times in local time
+    pub fn summary_card(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Invitation: {}",
+            self.summary.as_deref().unwrap_or("(no title)")
+        )];
+        if let Some(organizer) = &self.organizer {
+            lines.push(format!("Organizer: {organizer}"));
+        }
+        if let Some(start) = self.start {
+            let when = if self.all_day {
+                start.format("%a %Y-%m-%d (all day)").to_string()
+            } else {
+                match self.end {
+                    Some(end) if end.date_naive() == start.date_naive() => format!(
+                        "{} - {}",
+                        start.format("%a %Y-%m-%d %H:%M"),
+                        end.format("%H:%M %Z")
+                    ),
+                    Some(end) => format!(
+                        "{} - {}",
+                        start.format("%a %Y-%m-%d %H:%M"),
+                        end.format("%a %Y-%m-%d %H:%M %Z")
+                    ),
+                    None => start.format("%a %Y-%m-%d %H:%M %Z").to_string(),
+                }
+            };
+            lines.push(format!("When: {when}"));
+        }
+        if let Some(location) = &self.location {
+            lines.push(format!("Where: {location}"));
+        }
+        if !self.attendees.is_empty() {
+            lines.push("Attendees:".to_string());
+            for attendee in &self.attendees {
+                lines.push(format!(
+                    "  {} ({})",
+                    attendee.person,
+                    attendee
+                        .partstat
+                        .as_deref()
+                        .unwrap_or("NEEDS-ACTION")
+                        .to_lowercase()
+                ));
+            }
+        }
+        lines
+    }
+
+    /// ### `find_attendee`
+    ///
+    /// First attendee whose address is one of `addresses`
+    pub fn find_attendee(&self, addresses: &[String]) -> Option<&Attendee> {
+        self.attendees.iter().find(|a| {
+            addresses
+                .iter()
+                .any(|addr| addr.eq_ignore_ascii_case(&a.person.email))
+        })
+    }
+
+    /// ### `reply_ics`
+    ///
+    /// iTIP (RFC 5546) REPLY for this event, answered by `attendee`
+    pub fn reply_ics(&self, attendee: &Person, partstat: PartStat) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "PRODID:-//termail//termail//EN".to_string(),
+            "VERSION:2.0".to_string(),
+            "METHOD:REPLY".to_string(),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", self.uid),
+        ];
+        if let Some(recurrence_id) = &self.recurrence_id {
+            lines.push(recurrence_id.clone());
+        }
+        if let Some(sequence) = &self.sequence {
+            lines.push(format!("SEQUENCE:{sequence}"));
+        }
+        lines.push(format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")));
+        if let Some(dtstart) = &self.dtstart_line {
+            lines.push(dtstart.clone());
+        }
+        if let Some(dtend) = &self.dtend_line {
+            lines.push(dtend.clone());
+        }
+        if let Some(organizer) = &self.organizer {
+            lines.push(person_line("ORGANIZER", organizer, None));
+        }
+        lines.push(person_line("ATTENDEE", attendee, Some(partstat)));
+        if let Some(summary) = &self.summary {
+            lines.push(format!("SUMMARY:{}", escape(summary)));
+        }
+        lines.push("END:VEVENT".to_string());
+        lines.push("END:VCALENDAR".to_string());
+
+        let mut ics = String::new();
+        for line in lines {
+            ics.push_str(&fold(&line));
+            ics.push_str("\r\n");
+        }
+        ics
+    }
+
+    /// ### `reply_message`
+    ///
+    /// Complete RFC 5322 message carrying the REPLY of `attendee` to the organizer, sent
+    /// from the `from` mailbox
+    pub fn reply_message(
+        &self,
+        from: &str,
+        attendee: &Person,
+        partstat: PartStat,
+    ) -> Result<Vec<u8>> {
+        let organizer = self
+            .organizer
+            .as_ref()
+            .ok_or_else(|| anyhow!("invitation has no organizer"))?;
+        let summary = self.summary.as_deref().unwrap_or("(no title)");
+        let now = Local::now();
+        let domain = attendee.email.rsplit('@').next().unwrap_or("localhost");
+        let text = format!(
+            "{} has {} the invitation: {}",
+            attendee,
+            partstat.subject_prefix().to_lowercase(),
+            summary
+        );
+
+        let mut builder = MessageBuilder::default();
+        builder
+            .add_address_header("From", from)
+            .add_address_header("To", &organizer.to_string())
+            .add_header(
+                "Subject",
+                &format!("{}: {}", partstat.subject_prefix(), summary),
+            )
+            .add_header("Date", &now.to_rfc2822())
+            .add_header(
+                "Message-ID",
+                &format!(
+                    "<{}.{}@{}>",
+                    now.timestamp_nanos_opt().unwrap_or_default(),
+                    std::process::id(),
+                    domain
+                ),
+            )
+            .set_text(&text)
+            .add_alternative(
+                "calendar; method=REPLY",
+                &self.reply_ics(attendee, partstat),
+            );
+        Ok(builder.build(&format!(
+            "termail-{}",
+            now.timestamp_nanos_opt().unwrap_or_default()
+        )))
+    }
+}
+
+/// The line with its time in UTC when its TZID is one of `timezones`, so that a reply
+/// needs no VTIMEZONE. Other lines are kept as they are.
+fn utc_line(line: &ContentLine, timezones: &[Timezone]) -> String {
+    let known = line
+        .param("TZID")
+        .is_some_and(|tzid| timezones.iter().any(|tz| tz.tzid == tzid));
+    match parse_datetime(line, timezones) {
+        Some(time) if known && line.value.trim().len() > 8 => format!(
+            "{}:{}",
+            line.name,
+            time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ")
+        ),
+        _ => line.raw.clone(),
+    }
+}
+
+fn person(line: &ContentLine) -> Person {
+    let value = line.value.trim();
+    let email = if value
+        .get(..7)
+        .is_some_and(|p| p.eq_ignore_ascii_case("mailto:"))
+    {
+        &value[7..]
+    } else {
+        value
+    };
+    Person {
+        name: line.param("CN").map(ToString::to_string),
+        email: email.to_string(),
+    }
+}
+
+fn person_line(name: &str, person: &Person, partstat: Option<PartStat>) -> String {
+    let mut line = name.to_string();
+    if let Some(partstat) = partstat {
+        let _ = write!(line, ";PARTSTAT={}", partstat.as_str());
+    }
+    if let Some(cn) = &person.name {
+        let _ = write!(line, ";CN=\"{}\"", cn.replace('"', "'"));
+    }
+    let _ = write!(line, ":mailto:{}", person.email);
+    line
+}
+
+/// Undo line folding: a line starting with a space or tab continues the previous one
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(rest);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+/// Fold a content line at 75 octets without splitting UTF-8 characters
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+fn parse_line(line: &str) -> Option<ContentLine> {
+    let mut in_quotes = false;
+    let mut value_start = None;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => {
+                value_start = Some(idx);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let value_start = value_start?;
+    let head = &line[..value_start];
+    let mut parts = split_params(head).into_iter();
+    let name = parts.next()?.to_uppercase();
+    let params = parts
+        .filter_map(|p| {
+            p.split_once('=')
+                .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+        })
+        .collect();
+    Some(ContentLine {
+        name,
+        params,
+        value: line[value_start + 1..].to_string(),
+        raw: line.to_string(),
+    })
+}
+
+fn split_params(head: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in head.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn parse_timezones(lines: &[ContentLine]) -> Vec<Timezone> {
+    let mut timezones = Vec::new();
+    let mut current: Option<Timezone> = None;
+    let mut observance: Option<Observance> = None;
+    for line in lines {
+        match line.name.as_str() {
+            "BEGIN" if line.value.eq_ignore_ascii_case("VTIMEZONE") => {
+                current = Some(Timezone {
+                    tzid: String::new(),
+                    observances: Vec::new(),
+                });
+            }
+            "BEGIN"
+                if current.is_some()
+                    && (line.value.eq_ignore_ascii_case("STANDARD")
+                        || line.value.eq_ignore_ascii_case("DAYLIGHT")) =>
+            {
+                observance = Some(Observance {
+                    start: NaiveDateTime::default(),
+                    offset_to: 0,
+                    by_month: None,
+                    by_day: None,
+                });
+            }
+            "END" if line.value.eq_ignore_ascii_case("VTIMEZONE") => {
+                timezones.extend(current.take());
+            }
+            "END" if observance.is_some() => {
+                if let (Some(tz), Some(o)) = (current.as_mut(), observance.take()) {
+                    tz.observances.push(o);
+                }
+            }
+            "TZID" => {
+                if let Some(tz) = current.as_mut() {
+                    tz.tzid.clone_from(&line.value);
+                }
+            }
+            "DTSTART" => {
+                if let Some(o) = observance.as_mut() {
+                    o.start = NaiveDateTime::parse_from_str(&line.value, "%Y%m%dT%H%M%S")
+                        .unwrap_or_default();
+                }
+            }
+            "TZOFFSETTO" => {
+                if let Some(o) = observance.as_mut() {
+                    o.offset_to = parse_offset(&line.value).unwrap_or(0);
+                }
+            }
+            "RRULE" => {
+                if let Some(o) = observance.as_mut() {
+                    parse_rrule(&line.value, o);
+                }
+            }
+            _ => {}
+        }
+    }
+    timezones
+}
+
+fn parse_offset(value: &str) -> Option<i32> {
+    let sign = match value.chars().next()? {
+        '-' => -1,
+        '+' => 1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    let hours: i32 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    let seconds: i32 = digits.get(4..6).and_then(|s| s.parse().ok()).unwrap_or(0);
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+fn parse_rrule(value: &str, observance: &mut Observance) {
+    for part in value.split(';') {
+        match part.split_once('=') {
+            Some(("BYMONTH", month)) => observance.by_month = month.parse().ok(),
+            Some(("BYDAY", days)) => {
+                // the first day of the list that makes sense
+                observance.by_day = days.split(',').find_map(parse_by_day);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A BYDAY entry like `-1SU`: the nth weekday, the first when there is no number
+fn parse_by_day(day: &str) -> Option<(i32, Weekday)> {
+    let day = day.trim();
+    let split = day.len().checked_sub(2)?;
+    let weekday = match day.get(split..)? {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let nth = match day.get(..split)? {
+        "" => 1,
+        nth => nth.trim_start_matches('+').parse().ok()?,
+    };
+    Some((nth, weekday))
+}
+
+impl Observance {
+    /// When this observance starts in `year`, in local wall time
+    fn onset(&self, year: i32) -> Option<NaiveDateTime> {
+        let time = self.start.time();
+        let month = self.by_month.unwrap_or_else(|| self.start.month());
+        let date = match self.by_day {
+            Some((nth, weekday)) if nth > 0 => {
+                NaiveDate::from_weekday_of_month_opt(year, month, weekday, u8::try_from(nth).ok()?)
+            }
+            Some((_, weekday)) => {
+                // last weekday of the month
+                let next = if month == 12 {
+                    NaiveDate::from_ymd_opt(year + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(year, month + 1, 1)
+                }?;
+                let mut date = next - Duration::days(1);
+                while date.weekday() != weekday {
+                    date -= Duration::days(1);
+                }
+                Some(date)
+            }
+            None => NaiveDate::from_ymd_opt(year, month, self.start.day()),
+        }?;
+        Some(date.and_time(time))
+    }
+}
+
+impl Timezone {
+    fn offset_at(&self, local: NaiveDateTime) -> Option<i32> {
+        // The observance in effect is the one which started last, looking back one year
+        let year = local.year();
+        self.observances
+            .iter()
+            .filter(|o| o.start.year() <= year)
+            .flat_map(|o| {
+                (year - 1..=year).filter_map(move |y| o.onset(y).map(|onset| (onset, o.offset_to)))
+            })
+            .filter(|(onset, _)| *onset <= local)
+            .max_by_key(|(onset, _)| *onset)
+            .map(|(_, offset)| offset)
+            .or_else(|| self.observances.first().map(|o| o.offset_to))
+    }
+}
+
+fn parse_datetime(line: &ContentLine, timezones: &[Timezone]) -> Option<DateTime<Local>> {
+    let value = line.value.trim();
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest();
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&naive).with_timezone(&Local));
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let offset = line.param("TZID").and_then(|tzid| {
+        timezones
+            .iter()
+            .find(|tz| tz.tzid == tzid)
+            .and_then(|tz| tz.offset_at(naive))
+    });
+    match offset {
+        Some(offset) => FixedOffset::east_opt(offset)?
+            .from_local_datetime(&naive)
+            .single()
+            .map(|dt| dt.with_timezone(&Local)),
+        // floating time, or a TZID we know nothing about
+        None => Local.from_local_datetime(&naive).earliest(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use mailparse::MailHeaderMap;
+    use pretty_assertions::assert_eq;
+
+    const INVITE: &str = "BEGIN:VCALENDAR\r
+METHOD:REQUEST\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:abc-123\r
+SEQUENCE:2\r
+SUMMARY:Sprint planning\\, Q3\r
+DTSTART;TZID=Europe/Berlin:20210705T100000\r
+DTEND;TZID=Europe/Berlin:20210705T110000\r
+ORGANIZER;CN=Alice:mailto:alice@example.com\r
+ATTENDEE;CN=\"Bob B\";PARTSTAT=NEEDS-ACTION:mailto:bob@example.com\r
+LOCATION:Room 1\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+END:VALARM\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn test_ical_parse_event() {
+        let calendar = Calendar::parse(INVITE).unwrap();
+        assert_eq!(calendar.method.as_deref(), Some("REQUEST"));
+        let event = &calendar.events[0];
+        assert_eq!(event.summary.as_deref(), Some("Sprint planning, Q3"));
+        assert_eq!(event.attendees[0].person.name.as_deref(), Some("Bob B"));
+        let start = event.start.unwrap().with_timezone(&Utc);
+        assert_eq!(start.format("%H:%M").to_string(), "08:00");
+    }
+
+    #[test]
+    fn test_ical_odd_values() {
+        let line = parse_line("ATTENDEE:abcdefé@x").unwrap();
+        assert_eq!(person(&line).email, "abcdefé@x");
+        let line = parse_line("ATTENDEE:MAILTO:bob@example.com").unwrap();
+        assert_eq!(person(&line).email, "bob@example.com");
+
+        assert_eq!(parse_by_day("-1SU"), Some((-1, Weekday::Sun)));
+        assert_eq!(parse_by_day("+2MO"), Some((2, Weekday::Mon)));
+        assert_eq!(parse_by_day("TU"), Some((1, Weekday::Tue)));
+        assert_eq!(parse_by_day("ÉSU"), None);
+        assert_eq!(parse_by_day("1É"), None);
+        let mut observance = Observance {
+            start: NaiveDateTime::default(),
+            offset_to: 0,
+            by_month: None,
+            by_day: None,
+        };
+        parse_rrule("FREQ=YEARLY;BYDAY=XX,2WE", &mut observance);
+        assert_eq!(observance.by_day, Some((2, Weekday::Wed)));
+    }
+
+    #[test]
+    fn test_ical_reply() {
+        let calendar = Calendar::parse(INVITE).unwrap();
+        let event = &calendar.events[0];
+        let me = event
+            .find_attendee(&["BOB@example.com".to_string()])
+            .unwrap()
+            .person
+            .clone();
+        let ics = event.reply_ics(&me, PartStat::Accepted);
+        assert!(ics.contains("METHOD:REPLY\r\n"));
+        assert!(ics.contains("SEQUENCE:2\r\n"));
+        assert!(ics.contains("ATTENDEE;PARTSTAT=ACCEPTED;CN=\"Bob B\":mailto:bob@example.com"));
+        assert!(ics.contains("DTSTART:20210705T080000Z\r\n"));
+        assert!(!ics.contains("TZID"));
+    }
+
+    #[test]
+    fn test_ical_reply_message() {
+        let calendar = Calendar::parse(INVITE).unwrap();
+        let me = Person {
+            name: Some("Zoë".to_string()),
+            email: "zoe@example.com".to_string(),
+        };
+        let message = calendar.events[0]
+            .reply_message(&me.to_string(), &me, PartStat::Declined)
+            .unwrap();
+        assert!(message.is_ascii());
+        let parsed = mailparse::parse_mail(&message).unwrap();
+        assert_eq!(
+            parsed.headers.get_first_value("From").as_deref(),
+            Some("Zoë <zoe@example.com>")
+        );
+        assert_eq!(parsed.subparts.len(), 2);
+        let calendar = &parsed.subparts[1];
+        assert_eq!(calendar.ctype.mimetype, "text/calendar");
+        assert_eq!(
+            calendar.ctype.params.get("method").map(String::as_str),
+            Some("REPLY")
+        );
+        assert!(calendar
+            .get_body()
+            .unwrap()
+            .contains("ATTENDEE;PARTSTAT=DECLINED;CN=\"Zoë\":mailto:zoe@example.com"));
+    }
+}
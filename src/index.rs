@@ -0,0 +1,549 @@
+//! ## Index
+//!
+//! `index` is the full text index over the headers and bodies of every mail,
+//! saved as json in the data dir and updated folder by folder
+// document numbers, positions and counts stay far below u32::MAX
+#![allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use anyhow::{anyhow, Result};
+use mailparse::{MailHeaderMap, ParsedMail};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+const FIELDS: [&str; 4] = ["from", "to", "subject", "body"];
+// a match in the subject counts more than one in the body
+const BOOSTS: [f64; 4] = [2.0, 1.5, 3.0, 1.0];
+// only the beginning of long bodies is indexed
+const MAX_BODY: usize = 64 * 1024;
+
+/// Text of the indexed fields of a mail, in the order of `FIELDS`
+pub struct MailFields(pub [String; 4]);
+
+#[derive(Serialize, Deserialize)]
+pub struct Doc {
+    #[serde(rename = "f")]
+    pub folder: String,
+    #[serde(rename = "i")]
+    pub id: String,
+    #[serde(rename = "r", default)]
+    removed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Posting {
+    #[serde(rename = "d")]
+    doc: u32,
+    #[serde(rename = "f")]
+    field: u8,
+    #[serde(rename = "p")]
+    positions: Vec<u32>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Index {
+    docs: Vec<Doc>,
+    postings: HashMap<String, Vec<Posting>>,
+    // folder and id of every indexed mail, rebuilt after loading
+    #[serde(skip)]
+    ids: HashMap<(String, String), u32>,
+    // mails were added or forgotten since the index was loaded or saved
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// A mail found by `Index::search`
+#[derive(Debug, PartialEq)]
+pub struct Hit {
+    pub folder: String,
+    pub id: String,
+    pub score: f64,
+}
+
+/// A term or phrase of a query, limited to one field or not
+#[derive(Debug, PartialEq, Eq)]
+struct Clause {
+    field: Option<u8>,
+    terms: Vec<String>,
+}
+
+impl MailFields {
+    pub fn from_mail(mail: &ParsedMail) -> Self {
+        let headers = mail.get_headers();
+        let header = |name: &str| headers.get_all_values(name).join(" ");
+        let mut body = text_body(mail);
+        if body.len() > MAX_BODY {
+            let mut end = MAX_BODY;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+        }
+        Self([
+            header("From"),
+            format!("{} {}", header("To"), header("Cc")),
+            header("Subject"),
+            body,
+        ])
+    }
+}
+
+/// ### `text_body`
+///
+/// Decoded text of all the text parts of a mail, html without its tags
+pub fn text_body(mail: &ParsedMail) -> String {
+    if !mail.subparts.is_empty() {
+        return mail.subparts.iter().map(text_body).collect();
+    }
+    let mimetype = mail.ctype.mimetype.to_lowercase();
+    let body = if mimetype.starts_with("text/") {
+        mail.get_body().unwrap_or_default()
+    } else {
+        return String::new();
+    };
+    if mimetype == "text/html" {
+        let mut text = String::new();
+        for node in scraper::Html::parse_fragment(&body).tree {
+            if let scraper::node::Node::Text(t) = node {
+                text.push_str(&t.text);
+                text.push(' ');
+            }
+        }
+        return text;
+    }
+    body
+}
+
+const fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}')
+}
+
+/// ### `tokenize`
+///
+/// Lower case words, every CJK character being a word of its own
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        } else if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+pub fn index_path() -> Result<PathBuf> {
+    let mut path = dirs_next::data_dir().ok_or_else(|| anyhow!("failed to find data dir."))?;
+    path.push("termail");
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+    }
+    path.push("index.json");
+    Ok(path)
+}
+
+impl Index {
+    /// ### `load`
+    ///
+    /// Load the saved index, or start an empty one
+    pub fn load() -> Self {
+        let mut index: Self = index_path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        index.rebuild_ids();
+        index
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let path = index_path()?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(tmp, path)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// ### `changed`
+    ///
+    /// Whether there is something new to save
+    pub const fn changed(&self) -> bool {
+        self.changed
+    }
+
+    fn rebuild_ids(&mut self) {
+        self.ids = self
+            .docs
+            .iter()
+            .enumerate()
+            .filter(|(_, doc)| !doc.removed)
+            .map(|(n, doc)| ((doc.folder.clone(), doc.id.clone()), n as u32))
+            .collect();
+    }
+
+    /// ### `missing`
+    ///
+    /// Mails of `folder` which are not indexed yet
+    pub fn missing<'a>(&self, folder: &str, ids: &'a [String]) -> Vec<&'a String> {
+        ids.iter()
+            .filter(|id| !self.ids.contains_key(&(folder.to_string(), (*id).clone())))
+            .collect()
+    }
+
+    pub fn add(&mut self, folder: &str, id: &str, fields: &MailFields) {
+        let key = (folder.to_string(), id.to_string());
+        if self.ids.contains_key(&key) {
+            return;
+        }
+        let doc = self.docs.len() as u32;
+        self.docs.push(Doc {
+            folder: folder.to_string(),
+            id: id.to_string(),
+            removed: false,
+        });
+        self.ids.insert(key, doc);
+        self.changed = true;
+        for (field, text) in fields.0.iter().enumerate() {
+            let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+            for (position, token) in tokenize(text).into_iter().enumerate() {
+                positions.entry(token).or_default().push(position as u32);
+            }
+            for (token, positions) in positions {
+                self.postings.entry(token).or_default().push(Posting {
+                    doc,
+                    field: field as u8,
+                    positions,
+                });
+            }
+        }
+    }
+
+    /// ### `retain`
+    ///
+    /// Forget the mails of `folder` which are not in `ids` anymore
+    pub fn retain(&mut self, folder: &str, ids: &HashSet<String>) {
+        let removed: Vec<(String, String)> = self
+            .ids
+            .keys()
+            .filter(|(f, id)| f == folder && !ids.contains(id))
+            .cloned()
+            .collect();
+        for key in removed {
+            if let Some(doc) = self.ids.remove(&key) {
+                self.docs[doc as usize].removed = true;
+                self.changed = true;
+            }
+        }
+    }
+
+    /// ### `compact`
+    ///
+    /// Drop forgotten mails from the postings once they are half of the index
+    pub fn compact(&mut self) {
+        let removed = self.docs.iter().filter(|d| d.removed).count();
+        if removed == 0 || removed * 2 < self.docs.len() {
+            return;
+        }
+        let mut renumber: Vec<Option<u32>> = Vec::with_capacity(self.docs.len());
+        let mut next = 0;
+        for doc in &self.docs {
+            if doc.removed {
+                renumber.push(None);
+            } else {
+                renumber.push(Some(next));
+                next += 1;
+            }
+        }
+        self.docs.retain(|d| !d.removed);
+        for postings in self.postings.values_mut() {
+            postings.retain_mut(|p| match renumber[p.doc as usize] {
+                Some(doc) => {
+                    p.doc = doc;
+                    true
+                }
+                None => false,
+            });
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+        self.rebuild_ids();
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// ### `search`
+    ///
+    /// Mails having every term and phrase of `query`, best first.
+    /// `"a phrase"`, `subject:word` and `from:"a phrase"` are understood.
+    pub fn search(&self, query: &str, max: usize) -> Result<Vec<Hit>> {
+        let clauses = parse_query(query)?;
+        if clauses.is_empty() {
+            return Ok(Vec::new());
+        }
+        let total = self.len().max(1) as f64;
+        let mut scores: Option<HashMap<u32, f64>> = None;
+        for clause in &clauses {
+            let counts = self.clause_counts(clause);
+            let idf = (total / counts.len().max(1) as f64).ln_1p();
+            let mut clause_scores: HashMap<u32, f64> = HashMap::new();
+            for ((doc, field), count) in counts.iter().flat_map(|(doc, fields)| {
+                fields
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c > 0)
+                    .map(move |(field, c)| ((*doc, field), *c))
+            }) {
+                let tf = f64::from(count);
+                *clause_scores.entry(doc).or_insert(0.0) += BOOSTS[field] * tf / (tf + 1.2) * idf;
+            }
+            // every clause must match
+            scores = Some(match scores {
+                None => clause_scores,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(doc, score)| clause_scores.get(&doc).map(|s| (doc, score + s)))
+                    .collect(),
+            });
+        }
+        let mut hits: Vec<Hit> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(doc, _)| !self.docs[*doc as usize].removed)
+            .map(|(doc, score)| {
+                let doc = &self.docs[doc as usize];
+                Hit {
+                    folder: doc.folder.clone(),
+                    id: doc.id.clone(),
+                    score,
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        hits.truncate(max);
+        Ok(hits)
+    }
+
+    /// Number of times the clause appears in every field of every document
+    fn clause_counts(&self, clause: &Clause) -> HashMap<u32, [u32; 4]> {
+        let mut counts: HashMap<u32, [u32; 4]> = HashMap::new();
+        let Some(first) = self.postings.get(&clause.terms[0]) else {
+            return counts;
+        };
+        let rest: Vec<HashMap<(u32, u8), &Vec<u32>>> = clause.terms[1..]
+            .iter()
+            .map(|term| {
+                self.postings
+                    .get(term)
+                    .map(|postings| {
+                        postings
+                            .iter()
+                            .map(|p| ((p.doc, p.field), &p.positions))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+        for posting in first {
+            if clause.field.is_some_and(|f| f != posting.field) {
+                continue;
+            }
+            let key = (posting.doc, posting.field);
+            let count = posting
+                .positions
+                .iter()
+                .filter(|start| {
+                    rest.iter().enumerate().all(|(offset, term)| {
+                        term.get(&key).is_some_and(|positions| {
+                            positions
+                                .binary_search(&(**start + offset as u32 + 1))
+                                .is_ok()
+                        })
+                    })
+                })
+                .count() as u32;
+            if count > 0 {
+                counts.entry(posting.doc).or_insert([0; 4])[posting.field as usize] += count;
+            }
+        }
+        counts
+    }
+}
+
+fn parse_query(query: &str) -> Result<Vec<Clause>> {
+    let mut clauses = Vec::new();
+    let chars: Vec<char> = query.chars().collect();
+    let mut pos = 0;
+    while pos < chars.len() {
+        if chars[pos].is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        // field name
+        let mut field = None;
+        let word_end = chars[pos..]
+            .iter()
+            .position(|c| c.is_whitespace() || *c == ':' || *c == '"')
+            .map_or(chars.len(), |n| pos + n);
+        if chars.get(word_end) == Some(&':') {
+            let name: String = chars[pos..word_end]
+                .iter()
+                .collect::<String>()
+                .to_lowercase();
+            let n = FIELDS
+                .iter()
+                .position(|f| *f == name)
+                .ok_or_else(|| anyhow!("unknown field {name}, use {}", FIELDS.join(", ")))?;
+            field = Some(n as u8);
+            pos = word_end + 1;
+        }
+        let text: String = if chars.get(pos) == Some(&'"') {
+            let end = chars[pos + 1..]
+                .iter()
+                .position(|c| *c == '"')
+                .map(|n| pos + 1 + n)
+                .ok_or_else(|| anyhow!("unterminated quote at column {}", pos + 1))?;
+            let text = chars[pos + 1..end].iter().collect();
+            pos = end + 1;
+            text
+        } else {
+            let end = chars[pos..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map_or(chars.len(), |n| pos + n);
+            let text = chars[pos..end].iter().collect();
+            pos = end;
+            text
+        };
+        let terms = tokenize(&text);
+        if terms.is_empty() {
+            if field.is_some() {
+                return Err(anyhow!("missing search term at column {}", start + 1));
+            }
+            continue;
+        }
+        // CJK text without quotes still has to be found as written
+        clauses.push(Clause { field, terms });
+    }
+    Ok(clauses)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn fields(from: &str, subject: &str, body: &str) -> MailFields {
+        MailFields([
+            from.to_string(),
+            "bob@example.com".to_string(),
+            subject.to_string(),
+            body.to_string(),
+        ])
+    }
+
+    fn ids(hits: &[Hit]) -> Vec<&str> {
+        hits.iter().map(|h| h.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_index_tokenize() {
+        assert_eq!(
+            tokenize("Hello, World! 会议"),
+            vec!["hello", "world", "会", "议"]
+        );
+    }
+
+    #[test]
+    fn test_index_search() {
+        let mut index = Index::default();
+        assert!(!index.changed());
+        index.add(
+            "inbox",
+            "1",
+            &fields("alice", "Quarterly report", "numbers are up"),
+        );
+        index.add(
+            "inbox",
+            "2",
+            &fields("carol", "lunch", "the report is late, report soon"),
+        );
+        index.add(
+            "archive",
+            "3",
+            &fields("alice", "holiday", "up numbers are"),
+        );
+        index.add("archive", "4", &fields("dave", "明天开会", "会议室"));
+        assert!(index.changed());
+
+        assert_eq!(ids(&index.search("report", 10).unwrap()), vec!["1", "2"]);
+        assert_eq!(ids(&index.search("subject:report", 10).unwrap()), vec!["1"]);
+        assert_eq!(
+            ids(&index.search("\"numbers are up\"", 10).unwrap()),
+            vec!["1"]
+        );
+        assert_eq!(
+            ids(&index.search("from:alice numbers", 10).unwrap()).len(),
+            2
+        );
+        assert_eq!(ids(&index.search("开会", 10).unwrap()), vec!["4"]);
+        assert!(index.search("nothing:here", 10).is_err());
+        assert!(index.search("\"open", 10).is_err());
+
+        assert_eq!(
+            index.missing("inbox", &["1".to_string(), "5".to_string()]),
+            vec!["5"]
+        );
+        index.changed = false;
+        index.retain(
+            "inbox",
+            &vec!["1".to_string(), "2".to_string()].into_iter().collect(),
+        );
+        assert!(!index.changed());
+        index.retain("inbox", &vec!["2".to_string()].into_iter().collect());
+        assert_eq!(ids(&index.search("report", 10).unwrap()), vec!["2"]);
+        index.compact();
+        index.retain("archive", &HashSet::new());
+        index.compact();
+        assert_eq!(index.len(), 1);
+        assert_eq!(ids(&index.search("late", 10).unwrap()), vec!["2"]);
+    }
+}
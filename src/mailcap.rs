@@ -0,0 +1,446 @@
+//! ## Mailcap
+//!
+//! `Mailcap` reads `~/.mailcap` and `/etc/mailcap` (RFC 1524) and finds the viewer for a mime type
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, read_to_string, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MailcapEntry {
+    pub mime_type: String,
+    pub command: String,
+    pub copious_output: bool,
+    pub needs_terminal: bool,
+    pub test: Option<String>,
+    pub name_template: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct Mailcap {
+    entries: Vec<MailcapEntry>,
+}
+
+impl Mailcap {
+    /// ### `load`
+    ///
+    /// Load entries from `~/.mailcap` and `/etc/mailcap`, user entries first
+    pub fn load() -> Self {
+        let mut entries = Vec::new();
+        for path in Self::search_path() {
+            if let Ok(content) = read_to_string(&path) {
+                entries.append(&mut Self::parse(&content));
+            }
+        }
+        Self { entries }
+    }
+
+    fn search_path() -> Vec<PathBuf> {
+        if let Ok(paths) = std::env::var("MAILCAPS") {
+            return paths
+                .split(':')
+                .map(|p| PathBuf::from(shellexpand::tilde(p).as_ref()))
+                .collect();
+        }
+        let mut paths = Vec::new();
+        if let Some(home) = dirs_next::home_dir() {
+            paths.push(home.join(".mailcap"));
+        }
+        paths.push(PathBuf::from("/etc/mailcap"));
+        paths
+    }
+
+    pub fn parse(content: &str) -> Vec<MailcapEntry> {
+        let mut entries = Vec::new();
+        let mut logical_line = String::new();
+        for line in content.lines() {
+            if logical_line.is_empty() && line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(stripped) = line.strip_suffix('\\') {
+                logical_line.push_str(stripped);
+                continue;
+            }
+            logical_line.push_str(line);
+            if let Some(entry) = Self::parse_entry(&logical_line) {
+                entries.push(entry);
+            }
+            logical_line.clear();
+        }
+        if let Some(entry) = Self::parse_entry(&logical_line) {
+            entries.push(entry);
+        }
+        entries
+    }
+
+    fn parse_entry(line: &str) -> Option<MailcapEntry> {
+        let fields = split_fields(line);
+        if fields.len() < 2 || fields[0].is_empty() {
+            return None;
+        }
+        let mut mime_type = fields[0].to_lowercase();
+        // A bare type like "image" means "image/*"
+        if !mime_type.contains('/') {
+            mime_type.push_str("/*");
+        }
+        let mut entry = MailcapEntry {
+            mime_type,
+            command: fields[1].clone(),
+            copious_output: false,
+            needs_terminal: false,
+            test: None,
+            name_template: None,
+        };
+        for field in &fields[2..] {
+            let (key, value) = match field.split_once('=') {
+                Some((k, v)) => (k.trim().to_lowercase(), Some(v.trim().to_string())),
+                None => (field.trim().to_lowercase(), None),
+            };
+            match (key.as_str(), value) {
+                ("copiousoutput", _) => entry.copious_output = true,
+                ("needsterminal", _) => entry.needs_terminal = true,
+                ("test", Some(v)) => entry.test = Some(v),
+                ("nametemplate", Some(v)) => entry.name_template = Some(v),
+                _ => {}
+            }
+        }
+        Some(entry)
+    }
+
+    /// ### `lookup`
+    ///
+    /// Find the first entry for `mimetype` whose `test=` command succeeds.
+    /// `copious_output` selects between inline (pager) entries and interactive ones.
+    pub fn lookup(
+        &self,
+        mimetype: &str,
+        params: &BTreeMap<String, String>,
+        copious_output: bool,
+    ) -> Option<&MailcapEntry> {
+        let mimetype = mimetype.to_lowercase();
+        self.entries.iter().find(|entry| {
+            entry.copious_output == copious_output
+                && entry.matches(&mimetype)
+                && entry.passes_test(&mimetype, params)
+        })
+    }
+}
+
+impl MailcapEntry {
+    fn matches(&self, mimetype: &str) -> bool {
+        if self.mime_type == mimetype {
+            return true;
+        }
+        match (self.mime_type.split_once('/'), mimetype.split_once('/')) {
+            (Some((major, "*")), Some((other_major, _))) => major == other_major,
+            _ => false,
+        }
+    }
+
+    fn passes_test(&self, mimetype: &str, params: &BTreeMap<String, String>) -> bool {
+        self.test.as_ref().is_none_or(|test| {
+            let cmd = expand(test, "", mimetype, params);
+            Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        })
+    }
+
+    /// ### `uses_file`
+    ///
+    /// Whether the command reads the part from a file (`%s`) instead of stdin
+    pub fn uses_file(&self) -> bool {
+        let mut chars = self.command.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == '%' && chars.next() == Some('s') {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// ### `command_line`
+    ///
+    /// Expand `%s`, `%t` and `%{param}` in the view command
+    pub fn command_line(
+        &self,
+        file: &Path,
+        mimetype: &str,
+        params: &BTreeMap<String, String>,
+    ) -> String {
+        let file = shell_quote(&file.to_string_lossy());
+        expand(&self.command, &file, mimetype, params)
+    }
+
+    /// ### `temp_file`
+    ///
+    /// Write the part into a new temporary file only the user can read, named after
+    /// `nametemplate` if present
+    pub fn temp_file(&self, data: &[u8], filename: Option<&str>) -> Result<PathBuf> {
+        // parts of one mail may have the same name, and viewers may still have theirs open
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let base = format!(
+            "termail-{}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed),
+            filename.map_or_else(|| "part".to_string(), sanitize_filename)
+        );
+        let name = match &self.name_template {
+            Some(template) => template.replace("%s", &base),
+            None => base,
+        };
+        let path = std::env::temp_dir().join(name);
+        // never through a link someone else put there
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        if let Err(e) = file.write_all(data) {
+            fs::remove_file(&path).ok();
+            return Err(e.into());
+        }
+        Ok(path)
+    }
+
+    /// ### `render`
+    ///
+    /// Run a `copiousoutput` entry and return what it prints
+    pub fn render(
+        &self,
+        data: &[u8],
+        mimetype: &str,
+        params: &BTreeMap<String, String>,
+        filename: Option<&str>,
+    ) -> Result<String> {
+        let output = if self.uses_file() {
+            let path = self.temp_file(data, filename)?;
+            let cmd = self.command_line(&path, mimetype, params);
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdin(Stdio::null())
+                .output();
+            fs::remove_file(&path).ok();
+            output?
+        } else {
+            let cmd = expand(&self.command, "", mimetype, params);
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            // Write from another thread, so that a viewer printing a lot while it reads
+            // can't block on a full pipe. It may also exit before reading everything.
+            let writer = child.stdin.take().map(|mut stdin| {
+                let data = data.to_vec();
+                thread::spawn(move || stdin.write_all(&data).ok())
+            });
+            let output = child.wait_with_output()?;
+            if let Some(writer) = writer {
+                writer.join().ok();
+            }
+            output
+        };
+        if !output.status.success() && output.stdout.is_empty() {
+            return Err(anyhow!(
+                "{} failed: {}",
+                self.command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    if next != ';' {
+                        current.push('\\');
+                    }
+                    current.push(next);
+                }
+            }
+            ';' => {
+                fields.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        fields.push(current.trim().to_string());
+    }
+    fields
+}
+
+fn expand(template: &str, file: &str, mimetype: &str, params: &BTreeMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push_str(file),
+            Some('t') => result.push_str(&shell_quote(mimetype)),
+            Some('{') => {
+                let mut name = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    name.push(c);
+                }
+                let value = params
+                    .get(&name.to_lowercase())
+                    .map_or("", std::string::String::as_str);
+                result.push_str(&shell_quote(value));
+            }
+            Some(other) => {
+                result.push('%');
+                if other != '%' {
+                    result.push(other);
+                }
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+/// ### `shell_quote`
+///
+/// `s` as one word for `sh`
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::{assert_eq, assert_ne};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_mailcap_parse() {
+        let content = r#"# comment
+text/calendar; khal import %s; nametemplate=%s.ics
+application/pdf; pdftotext %s -; copiousoutput
+text/x-diff; less; needsterminal; \
+    test=test -n \"$DISPLAY\"
+image; feh %s
+"#;
+        let entries = Mailcap::parse(content);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].name_template.as_deref(), Some("%s.ics"));
+        assert!(entries[1].copious_output);
+        assert!(entries[2].needs_terminal);
+        assert_eq!(entries[2].test.as_deref(), Some("test -n \\\"$DISPLAY\\\""));
+        assert_eq!(entries[3].mime_type, "image/*");
+        assert!(entries[3].matches("image/png"));
+        assert!(!entries[2].uses_file());
+    }
+
+    #[test]
+    fn test_mailcap_expand() {
+        let mut params = BTreeMap::new();
+        params.insert("charset".to_string(), "utf-8".to_string());
+        let cmd = expand(
+            "view %s %t %{charset} 100%%",
+            "'/tmp/a'",
+            "text/plain",
+            &params,
+        );
+        assert_eq!(cmd, "view '/tmp/a' 'text/plain' 'utf-8' 100%");
+    }
+
+    #[test]
+    fn test_mailcap_render_large_part() {
+        // larger than a pipe buffer both ways
+        let entries = Mailcap::parse("application/octet-stream; cat; copiousoutput");
+        let data = "0123456789abcdef\n".repeat(64 * 1024);
+        let output = entries[0]
+            .render(
+                data.as_bytes(),
+                "application/octet-stream",
+                &BTreeMap::new(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(output.len(), data.len());
+    }
+
+    #[test]
+    fn test_mailcap_temp_file() {
+        let entry = &Mailcap::parse("application/pdf; view %s; nametemplate=%s.pdf")[0];
+        let first = entry.temp_file(b"one", Some("a b.pdf")).unwrap();
+        let second = entry.temp_file(b"two", Some("a b.pdf")).unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_string_lossy().ends_with("a_b.pdf.pdf"));
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read(&second).unwrap(), b"two");
+        fs::remove_file(first).ok();
+        fs::remove_file(second).ok();
+    }
+}
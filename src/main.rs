@@ -25,13 +25,30 @@
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
+mod addressbook;
 mod app;
+mod compose;
 mod config;
+mod ical;
+mod index;
+mod mailcap;
+mod markdown;
+mod mime;
+mod notmuch;
+mod outbox;
+mod pattern;
+mod smtp;
+mod spell;
+mod threading;
+mod transport;
 mod ui;
 mod utils;
+mod vcard;
 
+use anyhow::{anyhow, Result};
 use app::App;
 use config::TermailConfig;
+use outbox::Outbox;
 use std::path::Path;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -45,11 +62,12 @@ fn main() {
 
     args.remove(0);
     let mut should_exit = false;
+    let mut flush_outbox = false;
     for i in args {
         let i = i.as_str();
         match i {
             "-v" | "--version" => {
-                println!("Termusic version is: {}", VERSION);
+                println!("Termusic version is: {VERSION}");
                 should_exit = true;
             }
 
@@ -59,12 +77,15 @@ fn main() {
 Usage: termusic [DIRECTORY] [OPTIONS]
 -v or --version print version and exit.
 -h or --help print this message and exit.
+flush-outbox send the mails waiting in the outbox and exit.
 directory: start termusic with directory.
 no arguments: start termusic with ~/.config/termusic/config.toml"
                 );
                 should_exit = true;
             }
 
+            "flush-outbox" => flush_outbox = true,
+
             _ => {
                 let p = Path::new(i);
                 let mut p_string = String::new();
@@ -74,7 +95,7 @@ no arguments: start termusic with ~/.config/termusic/config.toml"
                             p_string = p1.as_path().to_string_lossy().to_string();
                         }
                     } else if let Ok(p_base) = std::env::current_dir() {
-                        let p2 = p_base.join(&p);
+                        let p2 = p_base.join(p);
                         if let Ok(p3) = p2.canonicalize() {
                             p_string = p3.as_path().to_string_lossy().to_string();
                         }
@@ -87,6 +108,7 @@ Termusic help:
 Usage: termusic [DIRECTORY] [OPTIONS]
 -v or --version print version and exit.
 -h or --help print this message and exit.
+flush-outbox send the mails waiting in the outbox and exit.
 directory: start termusic with directory.
 no arguments: start termusic with ~/.config/termusic/config.toml"
                     );
@@ -100,8 +122,37 @@ no arguments: start termusic with ~/.config/termusic/config.toml"
         return;
     }
 
+    if flush_outbox {
+        if let Err(e) = run_flush_outbox(&config) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // glib::set_application_name("termusic");
     // glib::set_prgname(Some("termusic"));
     let mut app: App = App::new(config);
     app.run();
 }
+
+/// ### `run_flush_outbox`
+///
+/// Send every mail of the outbox now, whatever their next try
+fn run_flush_outbox(config: &TermailConfig) -> Result<()> {
+    let outbox = Outbox::new(outbox::folder(config));
+    let report = outbox.flush(&config.accounts, chrono::Local::now().timestamp(), true)?;
+    for error in &report.errors {
+        eprintln!("{error}");
+    }
+    println!(
+        "{} sent, {} left in {}",
+        report.sent,
+        report.queued,
+        outbox.path().display()
+    );
+    if report.queued > 0 {
+        return Err(anyhow!("Some mails could not be sent"));
+    }
+    Ok(())
+}
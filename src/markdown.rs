@@ -0,0 +1,845 @@
+//! ## Markdown
+//!
+//! `markdown` renders the Markdown of a draft to HTML for the HTML part of a mail, and to
+//! lines for a preview in the reader
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::fmt::Write;
+
+/// Text inside a block
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Inline {
+    Text(String),
+    Code(String),
+    Strong(Vec<Self>),
+    Emphasis(Vec<Self>),
+    /// text and address
+    Link(Vec<Self>, String),
+    Break,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Block {
+    Heading(usize, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    /// info string and text
+    Code(String, String),
+    Quote(Vec<Self>),
+    /// number of the first item if ordered, items, and whether no blank lines part them
+    List(Option<u64>, Vec<Vec<Self>>, bool),
+    Rule,
+}
+
+/// How a line of the preview is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Text,
+    Heading,
+    Code,
+    Quote,
+    Rule,
+}
+
+/// ### `to_html`
+///
+/// HTML document of the Markdown `text`
+pub fn to_html(text: &str) -> String {
+    let mut body = String::new();
+    html_blocks(&mut body, &parse(text), false);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n</head>\n<body>\n{body}</body>\n</html>\n"
+    )
+}
+
+/// ### `preview`
+///
+/// Lines of the Markdown `text` as it reads once rendered
+pub fn preview(text: &str) -> Vec<(LineKind, String)> {
+    preview_blocks(&parse(text), false)
+}
+
+fn parse(text: &str) -> Vec<Block> {
+    let lines: Vec<String> = text.lines().map(expand_tabs).collect();
+    parse_blocks(&lines)
+}
+
+/// Tabs of the indentation as four spaces
+fn expand_tabs(line: &str) -> String {
+    let rest = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - rest.len()];
+    format!("{}{rest}", indent.replace('\t', "    "))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// The line without its first `columns` spaces, or as many as it has
+fn strip_indent(line: &str, columns: usize) -> &str {
+    &line[indent_of(line).min(columns)..]
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Fence character, its length, indentation and info string of an opening code fence
+fn fence(line: &str) -> Option<(char, usize, usize, String)> {
+    let indent = indent_of(line);
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let c = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = rest.len() - rest.trim_start_matches(c).len();
+    let info = rest[length..].trim();
+    if length < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((c, length, indent, info.to_string()))
+}
+
+/// Level and text of an ATX heading
+fn heading(line: &str) -> Option<(usize, &str)> {
+    if indent_of(line) > 3 {
+        return None;
+    }
+    let rest = line.trim_start();
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    let text = &rest[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    // closing hashes go
+    let text = text.trim();
+    let closed = text.trim_end_matches('#');
+    let text = if closed.is_empty() || closed.ends_with(' ') {
+        closed.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+fn is_rule(line: &str) -> bool {
+    if indent_of(line) > 3 {
+        return false;
+    }
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ['-', '*', '_'].contains(&marks[0]) && marks.iter().all(|c| *c == marks[0])
+}
+
+/// The line inside a block quote
+fn quoted(line: &str) -> Option<&str> {
+    if indent_of(line) > 3 {
+        return None;
+    }
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// A list item marker: its number if ordered, its character, and where the text starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Marker {
+    number: Option<u64>,
+    delimiter: char,
+    offset: usize,
+}
+
+fn list_marker(line: &str) -> Option<Marker> {
+    let indent = indent_of(line);
+    if indent > 3 || is_rule(line) {
+        return None;
+    }
+    let rest = &line[indent..];
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, delimiter) = if digits == 0 {
+        (None, rest.chars().next()?)
+    } else if digits <= 9 {
+        (rest[..digits].parse().ok(), rest[digits..].chars().next()?)
+    } else {
+        return None;
+    };
+    let valid = match number {
+        None => ['-', '+', '*'].contains(&delimiter),
+        Some(_) => ['.', ')'].contains(&delimiter),
+    };
+    if !valid {
+        return None;
+    }
+    let after = &rest[digits + delimiter.len_utf8()..];
+    if !(after.is_empty() || after.starts_with(' ')) {
+        return None;
+    }
+    let spaces = indent_of(after);
+    // text indented by more than four spaces starts with one, the others make a code block
+    let spaces = if spaces == 0 || spaces > 4 || is_blank(after) {
+        1
+    } else {
+        spaces
+    };
+    Some(Marker {
+        number,
+        delimiter,
+        offset: indent + digits + 1 + spaces,
+    })
+}
+
+/// Whether the line starts a block that ends a paragraph
+fn interrupts(line: &str) -> bool {
+    fence(line).is_some()
+        || heading(line).is_some()
+        || is_rule(line)
+        || quoted(line).is_some()
+        || list_marker(line).is_some_and(|m| {
+            m.number.is_none_or(|n| n == 1) && !is_blank(&line[m.offset.min(line.len())..])
+        })
+}
+
+fn parse_blocks(lines: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].as_str();
+        if is_blank(line) {
+            i += 1;
+        } else if let Some((c, length, indent, info)) = fence(line) {
+            let mut text = String::new();
+            i += 1;
+            while i < lines.len() {
+                let closing = lines[i].trim();
+                if closing.len() >= length && closing.chars().all(|x| x == c) {
+                    i += 1;
+                    break;
+                }
+                text.push_str(strip_indent(&lines[i], indent));
+                text.push('\n');
+                i += 1;
+            }
+            blocks.push(Block::Code(info, text));
+        } else if indent_of(line) >= 4 {
+            let mut code: Vec<&str> = Vec::new();
+            while i < lines.len() && (is_blank(&lines[i]) || indent_of(&lines[i]) >= 4) {
+                code.push(strip_indent(&lines[i], 4));
+                i += 1;
+            }
+            while code.last().is_some_and(|l| is_blank(l)) {
+                code.pop();
+            }
+            blocks.push(Block::Code(String::new(), code.join("\n") + "\n"));
+        } else if let Some((level, text)) = heading(line) {
+            blocks.push(Block::Heading(level, parse_inline(text)));
+            i += 1;
+        } else if is_rule(line) {
+            blocks.push(Block::Rule);
+            i += 1;
+        } else if quoted(line).is_some() {
+            let mut inner = Vec::new();
+            while let Some(rest) = lines.get(i).and_then(|l| quoted(l)) {
+                inner.push(rest.to_string());
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse_blocks(&inner)));
+        } else if let Some(marker) = list_marker(line) {
+            i = parse_list(lines, i, marker, &mut blocks);
+        } else {
+            let mut text = vec![line.trim()];
+            i += 1;
+            let mut level = None;
+            while i < lines.len() && !is_blank(&lines[i]) {
+                let next = lines[i].trim();
+                // a paragraph underlined with = or - is a heading
+                if indent_of(&lines[i]) <= 3
+                    && (next.chars().all(|c| c == '=') || next.chars().all(|c| c == '-'))
+                {
+                    level = Some(if next.starts_with('=') { 1 } else { 2 });
+                    i += 1;
+                    break;
+                }
+                if interrupts(&lines[i]) {
+                    break;
+                }
+                text.push(next);
+                i += 1;
+            }
+            // the line break of two trailing spaces is in the untrimmed lines
+            let joined = text
+                .iter()
+                .enumerate()
+                .map(|(n, t)| {
+                    let original = &lines[i - text.len() - usize::from(level.is_some()) + n];
+                    if original.ends_with("  ") && n + 1 < text.len() {
+                        format!("{t}\\")
+                    } else {
+                        (*t).to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let inline = parse_inline(&joined);
+            blocks.push(match level {
+                Some(level) => Block::Heading(level, inline),
+                None => Block::Paragraph(inline),
+            });
+        }
+    }
+    blocks
+}
+
+/// Read the list starting at line `i`, returning the line after it
+fn parse_list(lines: &[String], mut i: usize, first: Marker, blocks: &mut Vec<Block>) -> usize {
+    let mut items = Vec::new();
+    let mut tight = true;
+    let mut marker = first;
+    loop {
+        let line = &lines[i];
+        let mut item: Vec<String> = vec![line.get(marker.offset..).unwrap_or("").to_string()];
+        i += 1;
+        while i < lines.len() {
+            let next = &lines[i];
+            if is_blank(next) {
+                item.push(String::new());
+            } else if indent_of(next) >= marker.offset {
+                item.push(strip_indent(next, marker.offset).to_string());
+            } else if item.last().is_some_and(|l| !is_blank(l))
+                && !interrupts(next)
+                && list_marker(next).is_none()
+            {
+                // a paragraph goes on without indentation
+                item.push(next.trim_start().to_string());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        let trailing_blank = item.last().is_some_and(|l| is_blank(l));
+        while item.last().is_some_and(|l| is_blank(l)) {
+            item.pop();
+        }
+        if item.iter().any(|l| is_blank(l)) {
+            tight = false;
+        }
+        items.push(parse_blocks(&item));
+        match lines.get(i).and_then(|l| list_marker(l)) {
+            Some(next)
+                if next.delimiter == marker.delimiter
+                    && next.number.is_some() == marker.number.is_some() =>
+            {
+                if trailing_blank {
+                    tight = false;
+                }
+                marker = next;
+            }
+            _ => break,
+        }
+    }
+    blocks.push(Block::List(first.number, items, tight));
+    i
+}
+
+fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut inlines = Vec::new();
+    let mut buffer = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && chars.get(i + 1).is_some_and(char::is_ascii_punctuation) {
+            buffer.push(chars[i + 1]);
+            i += 2;
+        } else if let Some((inline, end)) = element(&chars, i) {
+            if !buffer.is_empty() {
+                inlines.push(Inline::Text(std::mem::take(&mut buffer)));
+            }
+            inlines.push(inline);
+            i = end;
+        } else {
+            // a run of delimiters left open is text as a whole
+            let run = if ['`', '*', '_'].contains(&c) {
+                run_length(&chars, i, c)
+            } else {
+                1
+            };
+            buffer.extend(&chars[i..i + run]);
+            i += run;
+        }
+    }
+    if !buffer.is_empty() {
+        inlines.push(Inline::Text(buffer));
+    }
+    inlines
+}
+
+/// The element other than text starting at `i`, and where it ends
+fn element(chars: &[char], i: usize) -> Option<(Inline, usize)> {
+    let c = chars[i];
+    match c {
+        '\\' if chars.get(i + 1) == Some(&'\n') => Some((Inline::Break, i + 2)),
+        '`' => {
+            let run = run_length(chars, i, '`');
+            let end = find_run(chars, i + run, '`', run)?;
+            let code: String = chars[i + run..end].iter().collect();
+            let code = code.replace('\n', " ");
+            let code = if code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') {
+                code[1..code.len() - 1].to_string()
+            } else {
+                code
+            };
+            Some((Inline::Code(code), end + run))
+        }
+        '*' | '_' => {
+            let run = run_length(chars, i, c).min(2);
+            let opens = chars.get(i + run).is_some_and(|n| !n.is_whitespace())
+                && (c == '*' || i == 0 || !chars[i - 1].is_alphanumeric());
+            if !opens {
+                return None;
+            }
+            let end = find_closing(chars, i + run, c, run)?;
+            let inner: String = chars[i + run..end].iter().collect();
+            let inner = parse_inline(&inner);
+            let inline = if run == 2 {
+                Inline::Strong(inner)
+            } else {
+                Inline::Emphasis(inner)
+            };
+            Some((inline, end + run))
+        }
+        '[' => {
+            let (text, url, end) = link(chars, i)?;
+            Some((Inline::Link(parse_inline(&text), url), end))
+        }
+        '<' => {
+            let (url, end) = autolink(chars, i)?;
+            let href = if url.contains(':') {
+                url.clone()
+            } else {
+                format!("mailto:{url}")
+            };
+            Some((Inline::Link(vec![Inline::Text(url)], href), end))
+        }
+        'h' if i == 0 || !chars[i - 1].is_alphanumeric() => {
+            let length = bare_url(chars, i);
+            let url: String = chars[i..i + length].iter().collect();
+            (length > 0).then(|| {
+                (
+                    Inline::Link(vec![Inline::Text(url.clone())], url),
+                    i + length,
+                )
+            })
+        }
+        _ => None,
+    }
+}
+
+fn run_length(chars: &[char], start: usize, c: char) -> usize {
+    chars[start..].iter().take_while(|x| **x == c).count()
+}
+
+/// Start of the next run of exactly `length` characters `c`
+fn find_run(chars: &[char], from: usize, c: char, length: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == c {
+            let run = run_length(chars, i, c);
+            if run == length {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Start of the delimiter closing an emphasis opened with `length` characters `c`
+fn find_closing(chars: &[char], from: usize, c: char, length: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => {
+                // code spans hide their delimiters
+                let run = run_length(chars, i, '`');
+                i = find_run(chars, i + run, '`', run).map_or(i + run, |end| end + run);
+            }
+            x if x == c => {
+                let run = run_length(chars, i, c);
+                let closes = i > from
+                    && !chars[i - 1].is_whitespace()
+                    && (c == '*' || chars.get(i + run).is_none_or(|n| !n.is_alphanumeric()));
+                if closes && (run == length || (length == 1 && run == 3)) {
+                    return Some(i);
+                }
+                if closes && length == 2 && run > 2 {
+                    return Some(i + run - 2);
+                }
+                i += run;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Text, address and end of a `[text](address "title")` link
+fn link(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    for (i, c) in chars.iter().enumerate().skip(start) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let mut depth = 0;
+    let end = chars
+        .iter()
+        .enumerate()
+        .skip(close + 1)
+        .find(|(_, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?
+        .0;
+    let target: String = chars[close + 2..end].iter().collect();
+    let url = target.split_whitespace().next().unwrap_or_default();
+    let url = url
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string();
+    let text = chars[start + 1..close].iter().collect();
+    Some((text, url, end + 1))
+}
+
+/// Address and end of an `<https://…>` or `<someone@example.com>` link
+fn autolink(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let end = start + chars[start..].iter().position(|c| *c == '>')?;
+    let inner: String = chars[start + 1..end].iter().collect();
+    if inner.is_empty() || inner.contains(char::is_whitespace) || inner.contains('<') {
+        return None;
+    }
+    let scheme = inner
+        .split_once(':')
+        .is_some_and(|(s, _)| s.len() > 1 && s.chars().all(|c| c.is_ascii_alphanumeric()));
+    let email = inner.contains('@') && !inner.contains(':');
+    (scheme || email).then_some((inner, end + 1))
+}
+
+/// Length of the web address starting at `start`, without the punctuation ending a sentence
+fn bare_url(chars: &[char], start: usize) -> usize {
+    let rest: String = chars[start..chars.len().min(start + 8)].iter().collect();
+    if !rest.starts_with("http://") && !rest.starts_with("https://") {
+        return 0;
+    }
+    let mut length = chars[start..]
+        .iter()
+        .take_while(|c| !c.is_whitespace() && **c != '<')
+        .count();
+    while length > 0 && ".,;:!?)'\"".contains(chars[start + length - 1]) {
+        length -= 1;
+    }
+    if length <= 8 {
+        0
+    } else {
+        length
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_inline(html: &mut String, inlines: &[Inline]) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape(text)),
+            Inline::Code(code) => {
+                let _ = write!(html, "<code>{}</code>", escape(code));
+            }
+            Inline::Strong(inner) => {
+                html.push_str("<strong>");
+                html_inline(html, inner);
+                html.push_str("</strong>");
+            }
+            Inline::Emphasis(inner) => {
+                html.push_str("<em>");
+                html_inline(html, inner);
+                html.push_str("</em>");
+            }
+            Inline::Link(inner, url) => {
+                let _ = write!(html, "<a href=\"{}\">", escape(url));
+                html_inline(html, inner);
+                html.push_str("</a>");
+            }
+            Inline::Break => html.push_str("<br>\n"),
+        }
+    }
+}
+
+/// Blocks as HTML, the paragraphs of a tight list without `<p>`
+fn html_blocks(html: &mut String, blocks: &[Block], tight: bool) {
+    for block in blocks {
+        match block {
+            Block::Heading(level, inline) => {
+                let _ = write!(html, "<h{level}>");
+                html_inline(html, inline);
+                let _ = writeln!(html, "</h{level}>");
+            }
+            Block::Paragraph(inline) if tight => {
+                html_inline(html, inline);
+                html.push('\n');
+            }
+            Block::Paragraph(inline) => {
+                html.push_str("<p>");
+                html_inline(html, inline);
+                html.push_str("</p>\n");
+            }
+            Block::Code(info, text) => {
+                html.push_str("<pre><code");
+                if let Some(language) = info.split_whitespace().next() {
+                    let _ = write!(html, " class=\"language-{}\"", escape(language));
+                }
+                let _ = writeln!(html, ">{}</code></pre>", escape(text));
+            }
+            Block::Quote(inner) => {
+                html.push_str("<blockquote>\n");
+                html_blocks(html, inner, false);
+                html.push_str("</blockquote>\n");
+            }
+            Block::List(number, items, tight) => {
+                let tag = match number {
+                    Some(1) => "<ol>".to_string(),
+                    Some(n) => format!("<ol start=\"{n}\">"),
+                    None => "<ul>".to_string(),
+                };
+                let _ = writeln!(html, "{tag}");
+                for item in items {
+                    html.push_str("<li>");
+                    html_blocks(html, item, *tight);
+                    html.push_str("</li>\n");
+                }
+                let _ = writeln!(html, "{}", if number.is_some() { "</ol>" } else { "</ul>" });
+            }
+            Block::Rule => html.push_str("<hr>\n"),
+        }
+    }
+}
+
+/// Text of inline elements, with link addresses after their text
+fn text_of(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(t) => text.push_str(&t.replace('\n', " ")),
+            Inline::Code(code) => text.push_str(code),
+            Inline::Strong(inner) | Inline::Emphasis(inner) => text.push_str(&text_of(inner)),
+            Inline::Link(inner, url) => {
+                let shown = text_of(inner);
+                if shown == *url || format!("mailto:{shown}") == *url {
+                    text.push_str(&shown);
+                } else {
+                    let _ = write!(text, "{shown} <{url}>");
+                }
+            }
+            Inline::Break => text.push('\n'),
+        }
+    }
+    text
+}
+
+fn preview_blocks(blocks: &[Block], tight: bool) -> Vec<(LineKind, String)> {
+    let mut lines = Vec::new();
+    for (n, block) in blocks.iter().enumerate() {
+        if n > 0 && !tight {
+            lines.push((LineKind::Text, String::new()));
+        }
+        match block {
+            Block::Heading(_, inline) => lines.push((LineKind::Heading, text_of(inline))),
+            Block::Paragraph(inline) => lines.extend(
+                text_of(inline)
+                    .split('\n')
+                    .map(|l| (LineKind::Text, l.to_string())),
+            ),
+            Block::Code(_, text) => {
+                lines.extend(text.lines().map(|l| (LineKind::Code, format!("    {l}"))));
+            }
+            Block::Quote(inner) => lines.extend(
+                preview_blocks(inner, false)
+                    .into_iter()
+                    .map(|(_, l)| (LineKind::Quote, format!("\u{2502} {l}"))),
+            ),
+            Block::List(number, items, tight) => {
+                for (i, item) in items.iter().enumerate() {
+                    let marker = number.map_or_else(
+                        || "\u{2022} ".to_string(),
+                        |first| format!("{}. ", first + i as u64),
+                    );
+                    let indent = " ".repeat(marker.chars().count());
+                    for (j, (kind, line)) in preview_blocks(item, *tight).into_iter().enumerate() {
+                        let prefix = if j == 0 { &marker } else { &indent };
+                        lines.push((kind, format!("{prefix}{line}")));
+                    }
+                    if !tight && i + 1 < items.len() {
+                        lines.push((LineKind::Text, String::new()));
+                    }
+                }
+            }
+            Block::Rule => lines.push((LineKind::Rule, "\u{2500}".repeat(40))),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TEXT: &str = "# Release *notes*
+
+Hi team,  
+the **new** build is at <https://example.com/build> and
+[the docs](https://example.com/docs \"Docs\") are up. Ask me@example.com or
+<me@example.com>, see https://example.com/faq.
+
+1. Run `make`
+2. Check:
+   - logs
+   - metrics
+
+> Looks good
+> to me
+
+```rust
+fn main() { println!(\"<hi>\"); }
+```
+
+snake_case_name and 2 * 3 * 4
+";
+
+    #[test]
+    fn test_markdown_html() {
+        let html = to_html(TEXT);
+        let body = html
+            .split("<body>\n")
+            .nth(1)
+            .and_then(|b| b.split("</body>").next())
+            .unwrap();
+        assert_eq!(
+            body,
+            "<h1>Release <em>notes</em></h1>
+<p>Hi team,<br>
+the <strong>new</strong> build is at <a href=\"https://example.com/build\">https://example.com/build</a> and
+<a href=\"https://example.com/docs\">the docs</a> are up. Ask me@example.com or
+<a href=\"mailto:me@example.com\">me@example.com</a>, see <a href=\"https://example.com/faq\">https://example.com/faq</a>.</p>
+<ol>
+<li>Run <code>make</code>
+</li>
+<li>Check:
+<ul>
+<li>logs
+</li>
+<li>metrics
+</li>
+</ul>
+</li>
+</ol>
+<blockquote>
+<p>Looks good
+to me</p>
+</blockquote>
+<pre><code class=\"language-rust\">fn main() { println!(&quot;&lt;hi&gt;&quot;); }
+</code></pre>
+<p>snake_case_name and 2 * 3 * 4</p>
+"
+        );
+        assert!(html.contains("<meta charset=\"utf-8\">"));
+    }
+
+    #[test]
+    fn test_markdown_blocks() {
+        let html = to_html(
+            "Title\n=====\n\n- a\n\n- b\n\nText\n\n    indented code\n\n3) three\n4) four\n\n***\n",
+        );
+        assert!(html.contains("<h1>Title</h1>\n"));
+        assert!(html.contains("<ul>\n<li><p>a</p>\n</li>\n<li><p>b</p>\n</li>\n</ul>\n"));
+        assert!(html.contains("<pre><code>indented code\n</code></pre>\n"));
+        assert!(html.contains("<ol start=\"3\">\n<li>three\n</li>\n<li>four\n</li>\n</ol>\n<hr>\n"));
+
+        // lines starting with characters of more than one byte
+        for (text, expected) in [
+            ("Über uns", "<p>Über uns</p>\n"),
+            ("你好", "<p>你好</p>\n"),
+            ("1é", "<p>1é</p>\n"),
+            ("- é", "<li>é\n</li>"),
+        ] {
+            assert!(to_html(text).contains(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_markdown_preview() {
+        let lines = preview(TEXT);
+        let text: Vec<&str> = lines.iter().map(|(_, l)| l.as_str()).collect();
+        assert_eq!(lines[0], (LineKind::Heading, "Release notes".to_string()));
+        assert_eq!(text[2], "Hi team,");
+        assert!(text[3].contains(
+            "build is at https://example.com/build and the docs <https://example.com/docs>"
+        ));
+        assert_eq!(
+            &text[5..9],
+            &[
+                "1. Run make",
+                "2. Check:",
+                "   \u{2022} logs",
+                "   \u{2022} metrics"
+            ]
+        );
+        assert_eq!(
+            lines[10],
+            (LineKind::Quote, "\u{2502} Looks good to me".to_string())
+        );
+        assert_eq!(lines[12].0, LineKind::Code);
+    }
+}
@@ -0,0 +1,544 @@
+//! ## Mime
+//!
+//! `mime` builds outgoing mails: encoded headers, the text part and attachments
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use anyhow::{anyhow, Result};
+use mailparse::{addrparse, MailAddr, SingleInfo};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Longest encoded word allowed by RFC 2047
+const MAX_WORD: usize = 75;
+/// Longest line of quoted-printable and base64 text
+const MAX_LINE: usize = 76;
+/// Headers are folded to lines this long where they can be (RFC 5322 2.1.1)
+const FOLD_LINE: usize = 78;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub mimetype: String,
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    /// ### `from_file`
+    ///
+    /// Read a file to attach, guessing its content type
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        let filename = path.file_name().map_or_else(
+            || "attachment".to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        Ok(Self {
+            mimetype: content_type(&filename, &data).to_string(),
+            filename,
+            data,
+        })
+    }
+}
+
+/// ### `content_type`
+///
+/// Content type of a file from its extension, else from its first bytes
+pub fn content_type(filename: &str, data: &[u8]) -> &'static str {
+    let extension = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let by_extension = match extension.as_str() {
+        "txt" | "text" | "log" | "md" | "rs" | "toml" | "conf" => Some("text/plain"),
+        "html" | "htm" => Some("text/html"),
+        "csv" => Some("text/csv"),
+        "ics" => Some("text/calendar"),
+        "vcf" => Some("text/vcard"),
+        "eml" => Some("message/rfc822"),
+        "pdf" => Some("application/pdf"),
+        "zip" => Some("application/zip"),
+        "gz" | "tgz" => Some("application/gzip"),
+        "json" => Some("application/json"),
+        "doc" => Some("application/msword"),
+        "docx" => Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+        "xlsx" => Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        "odt" => Some("application/vnd.oasis.opendocument.text"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "mp3" => Some("audio/mpeg"),
+        "ogg" => Some("audio/ogg"),
+        "mp4" => Some("video/mp4"),
+        _ => None,
+    };
+    by_extension.unwrap_or_else(|| {
+        let magic: [(&[u8], &str); 6] = [
+            (b"%PDF-", "application/pdf"),
+            (b"\x89PNG", "image/png"),
+            (b"\xff\xd8\xff", "image/jpeg"),
+            (b"GIF8", "image/gif"),
+            (b"PK\x03\x04", "application/zip"),
+            (b"\x1f\x8b", "application/gzip"),
+        ];
+        magic
+            .iter()
+            .find(|(prefix, _)| data.starts_with(prefix))
+            .map_or_else(
+                || {
+                    if !data.contains(&0) && std::str::from_utf8(data).is_ok() {
+                        "text/plain"
+                    } else {
+                        "application/octet-stream"
+                    }
+                },
+                |(_, mimetype)| mimetype,
+            )
+    })
+}
+
+/// Builds a mail from its headers, text and attachments
+#[derive(Default)]
+pub struct MessageBuilder {
+    headers: Vec<(String, String)>,
+    text: String,
+    /// subtypes and texts sent as the alternatives to `text`, like the rendered HTML
+    alternatives: Vec<(String, String)>,
+    attachments: Vec<Attachment>,
+}
+
+impl MessageBuilder {
+    /// ### `add_header`
+    ///
+    /// Add an unstructured header, like Subject
+    pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.push((name.to_string(), encode_words(value)));
+        self
+    }
+
+    /// ### `add_address_header`
+    ///
+    /// Add a header holding addresses, like From or To
+    pub fn add_address_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers
+            .push((name.to_string(), encode_addresses(value)));
+        self
+    }
+
+    pub fn set_text(&mut self, text: &str) -> &mut Self {
+        self.text = text.to_string();
+        self
+    }
+
+    pub fn set_html(&mut self, html: &str) -> &mut Self {
+        self.add_alternative("html", html)
+    }
+
+    /// ### `add_alternative`
+    ///
+    /// Add a text part of `subtype`, which may carry parameters, as an alternative to the text
+    pub fn add_alternative(&mut self, subtype: &str, text: &str) -> &mut Self {
+        self.alternatives
+            .push((subtype.to_string(), text.to_string()));
+        self
+    }
+
+    pub fn add_attachment(&mut self, attachment: Attachment) -> &mut Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// ### `build`
+    ///
+    /// The mail, multipart/mixed with `boundary` when it has attachments, and its text
+    /// multipart/alternative when it has alternatives. Only attached mails may have 8bit
+    /// content, as they can't be encoded again.
+    pub fn build(&self, boundary: &str) -> Vec<u8> {
+        let mut message = String::new();
+        for (name, value) in &self.headers {
+            message.push_str(&fold_header(name, value));
+        }
+        message.push_str("MIME-Version: 1.0\r\n");
+        if self.attachments.is_empty() {
+            self.write_body(&mut message, boundary);
+            return message.into_bytes();
+        }
+        let _ = write!(
+            message,
+            "Content-Type: multipart/mixed; boundary=\"{boundary}\"\r\n\r\n\
+             This is a multi-part message in MIME format.\r\n--{boundary}\r\n"
+        );
+        // no boundary may start with another one (RFC 2046 5.1.1)
+        self.write_body(&mut message, &format!("alt-{boundary}"));
+        let mut message = message.into_bytes();
+        for attachment in &self.attachments {
+            message.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Type: {}\r\n{}",
+                    attachment.mimetype,
+                    fold_header(
+                        "Content-Disposition",
+                        &format!("attachment; {}", filename_param(&attachment.filename))
+                    )
+                )
+                .as_bytes(),
+            );
+            if attachment.mimetype == "message/rfc822" {
+                // a mail must not be encoded again (RFC 2046 5.2.1), its bytes go as they are
+                let encoding = if attachment.data.is_ascii() {
+                    "7bit"
+                } else {
+                    "8bit"
+                };
+                message.extend_from_slice(
+                    format!("Content-Transfer-Encoding: {encoding}\r\n\r\n").as_bytes(),
+                );
+                let data = attachment
+                    .data
+                    .strip_suffix(b"\n")
+                    .unwrap_or(&attachment.data);
+                for line in data.split(|&b| b == b'\n') {
+                    message.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+                    message.extend_from_slice(b"\r\n");
+                }
+            } else {
+                message.extend_from_slice(b"Content-Transfer-Encoding: base64\r\n\r\n");
+                let encoded = base64::encode(&attachment.data);
+                for chunk in encoded.as_bytes().chunks(MAX_LINE) {
+                    message.extend_from_slice(chunk);
+                    message.extend_from_slice(b"\r\n");
+                }
+            }
+        }
+        message.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+        message
+    }
+
+    /// The text, along with its alternatives in a multipart/alternative with `boundary`
+    fn write_body(&self, message: &mut String, boundary: &str) {
+        if self.alternatives.is_empty() {
+            write_text(message, &self.text, "plain");
+            return;
+        }
+        let _ = write!(
+            message,
+            "Content-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n--{boundary}\r\n"
+        );
+        write_text(message, &self.text, "plain");
+        for (subtype, text) in &self.alternatives {
+            let _ = write!(message, "--{boundary}\r\n");
+            write_text(message, text, subtype);
+        }
+        let _ = write!(message, "--{boundary}--\r\n");
+    }
+}
+
+/// Text part of `subtype`, as it is when it is short ASCII lines, else quoted-printable.
+/// 8bit would need a transport known to take it (RFC 6152).
+fn write_text(message: &mut String, text: &str, subtype: &str) {
+    let _ = write!(message, "Content-Type: text/{subtype}; charset=utf-8\r\n");
+    // SMTP lines may not be longer than 998 bytes (RFC 5321 4.5.3.1.6)
+    let long_lines = text.lines().any(|l| l.len() > 998);
+    let control = text
+        .chars()
+        .any(|c| c.is_control() && c != '\n' && c != '\r' && c != '\t');
+    if long_lines || control || !text.is_ascii() {
+        message.push_str("Content-Transfer-Encoding: quoted-printable\r\n\r\n");
+        message.push_str(&quoted_printable(text));
+    } else {
+        message.push_str("Content-Transfer-Encoding: 7bit\r\n\r\n");
+        for line in text.lines() {
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+    }
+}
+
+/// ### `fold_header`
+///
+/// `name: value` with its line end, folded at spaces so that lines stay short. Lines
+/// without a space to fold at are left longer.
+pub fn fold_header(name: &str, value: &str) -> String {
+    let unfolded = value.replace("\r\n", "");
+    let mut header = format!("{name}:");
+    let mut length = header.len();
+    // runs of spaces are kept, a fold goes before one of them
+    for word in unfolded.split(' ') {
+        // parsers keep a fold right after the name as part of the value
+        if length + 1 + word.len() > FOLD_LINE && length > name.len() + 1 {
+            header.push_str("\r\n");
+            length = 0;
+        }
+        header.push(' ');
+        header.push_str(word);
+        length += 1 + word.len();
+    }
+    header.push_str("\r\n");
+    header
+}
+/// ### `quoted_printable`
+///
+/// Quoted-printable text (RFC 2045 6.7), with CRLF line ends
+pub fn quoted_printable(text: &str) -> String {
+    let mut encoded = String::new();
+    for line in text.lines() {
+        let mut length = 0;
+        let bytes = line.as_bytes();
+        for (i, &byte) in bytes.iter().enumerate() {
+            let last = i + 1 == bytes.len();
+            let literal = (byte == b' ' || byte == b'\t') && !last
+                || (33..=126).contains(&byte) && byte != b'=';
+            let token = if literal {
+                char::from(byte).to_string()
+            } else {
+                format!("={byte:02X}")
+            };
+            // a soft break ends with "=", which needs a column too
+            if length + token.len() > MAX_LINE - 1 {
+                encoded.push_str("=\r\n");
+                length = 0;
+            }
+            length += token.len();
+            encoded.push_str(&token);
+        }
+        encoded.push_str("\r\n");
+    }
+    encoded
+}
+
+/// ### `encode_words`
+///
+/// RFC 2047 encoded words for header text which is not plain ASCII
+pub fn encode_words(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    // "=?UTF-8?B?" and "?=" around base64, which grows by 4/3
+    let max_bytes = (MAX_WORD - 12) / 4 * 3;
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > max_bytes {
+            words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+    }
+    words.join(" ")
+}
+
+/// ### `encode_addresses`
+///
+/// Address list with its display names encoded, leaving the addresses readable
+pub fn encode_addresses(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    addrparse(value).map_or_else(
+        |_| encode_words(value),
+        |list| {
+            list.iter()
+                .map(|addr| match addr {
+                    MailAddr::Single(info) => encode_single(info),
+                    MailAddr::Group(group) => format!(
+                        "{}: {};",
+                        encode_phrase(&group.group_name),
+                        group
+                            .addrs
+                            .iter()
+                            .map(encode_single)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        },
+    )
+}
+
+fn encode_single(info: &SingleInfo) -> String {
+    info.display_name.as_ref().map_or_else(
+        || info.addr.clone(),
+        |name| format!("{} <{}>", encode_phrase(name), info.addr),
+    )
+}
+
+/// Display or group name: encoded words, or quoted when it has special characters
+fn encode_phrase(name: &str) -> String {
+    let atom = |c: char| c.is_ascii_alphanumeric() || " !#$%&'*+-/=?^_`{|}~".contains(c);
+    if !name.is_ascii() {
+        encode_words(name)
+    } else if name.chars().all(atom) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// ### `filename_param`
+///
+/// `filename` parameter, in RFC 2231 form unless the name is plain ASCII
+pub fn filename_param(filename: &str) -> String {
+    let plain = filename
+        .chars()
+        .all(|c| c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\');
+    if plain {
+        return format!("filename=\"{filename}\"");
+    }
+    let mut encoded = String::from("filename*=UTF-8''");
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mailparse::{parse_mail, MailHeaderMap};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mime_encoding() {
+        assert_eq!(encode_words("plain"), "plain");
+        let long = "Größenänderung ".repeat(6);
+        let encoded = encode_words(&long);
+        assert!(encoded.split(' ').all(|w| w.len() <= MAX_WORD));
+        let header = fold_header("Subject", &encoded) + "\r\n";
+        assert!(header.lines().skip(1).all(|l| l.len() <= FOLD_LINE));
+        let parsed = parse_mail(header.as_bytes()).unwrap();
+        assert_eq!(parsed.headers.get_first_value("Subject").unwrap(), long);
+
+        let to: Vec<String> = (0..20).map(|i| format!("user{i}@example.com")).collect();
+        let header = fold_header("To", &to.join(", "));
+        assert!(header.lines().all(|l| l.len() <= FOLD_LINE));
+        assert_eq!(
+            header.replace("\r\n", "").trim_end(),
+            format!("To: {}", to.join(", "))
+        );
+        assert_eq!(fold_header("Subject", "a  b"), "Subject: a  b\r\n");
+
+        assert_eq!(
+            encode_addresses("Friends: Zoë <zoe@example.com>, \"Doe, Jane\" <jane@example.com>;"),
+            "Friends: =?UTF-8?B?Wm/Dqw==?= <zoe@example.com>, \"Doe, Jane\" <jane@example.com>;"
+        );
+
+        assert_eq!(filename_param("a b.txt"), "filename=\"a b.txt\"");
+        assert_eq!(
+            filename_param("résumé.pdf"),
+            "filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+        );
+
+        assert_eq!(quoted_printable("a=b c\nend "), "a=3Db c\r\nend=20\r\n");
+        let wrapped = quoted_printable(&"x".repeat(100));
+        assert!(wrapped.lines().all(|l| l.len() <= MAX_LINE));
+
+        assert_eq!(content_type("notes.PDF", b""), "application/pdf");
+        assert_eq!(content_type("blob", b"\x89PNG\r\n"), "image/png");
+        assert_eq!(content_type("README", b"hello"), "text/plain");
+        assert_eq!(content_type("blob", &[0, 1, 2]), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_mime_build() {
+        let mut builder = MessageBuilder::default();
+        builder
+            .add_address_header("From", "Zoë <zoe@example.com>")
+            .add_header("Subject", "Grüße")
+            .set_text(&format!("Hallo\n{}\n", "long ".repeat(250)))
+            .add_attachment(Attachment {
+                filename: "résumé.pdf".to_string(),
+                mimetype: "application/pdf".to_string(),
+                data: b"%PDF-1.4 \xff".to_vec(),
+            });
+        let message = builder.build("b1");
+        let parsed = parse_mail(&message).unwrap();
+        assert_eq!(parsed.ctype.mimetype, "multipart/mixed");
+        assert_eq!(
+            parsed.headers.get_first_value("From").unwrap(),
+            "Zoë <zoe@example.com>"
+        );
+        let text = &parsed.subparts[0];
+        assert_eq!(
+            text.headers
+                .get_first_value("Content-Transfer-Encoding")
+                .unwrap(),
+            "quoted-printable"
+        );
+        assert!(text.get_body().unwrap().starts_with("Hallo\r\nlong long"));
+        let attachment = &parsed.subparts[1];
+        assert_eq!(
+            attachment.get_body_raw().unwrap(),
+            b"%PDF-1.4 \xff".to_vec()
+        );
+        assert_eq!(
+            attachment.get_content_disposition().params.get("filename"),
+            Some(&"résumé.pdf".to_string())
+        );
+
+        // only an attached mail is 8bit, and its bytes are kept
+        let mut builder = MessageBuilder::default();
+        builder
+            .add_header("Subject", "Fwd")
+            .set_text("Grüße\n")
+            .add_attachment(Attachment {
+                filename: "old.eml".to_string(),
+                mimetype: "message/rfc822".to_string(),
+                data: b"Subject: old\n\nCaf\xe9\n".to_vec(),
+            });
+        let message = builder.build("b2");
+        let parsed = parse_mail(&message).unwrap();
+        assert_eq!(
+            parsed.subparts[0]
+                .headers
+                .get_first_value("Content-Transfer-Encoding")
+                .unwrap(),
+            "quoted-printable"
+        );
+        assert_eq!(parsed.subparts[0].get_body().unwrap(), "Grüße\r\n");
+        let attached = &parsed.subparts[1];
+        assert_eq!(
+            attached
+                .headers
+                .get_first_value("Content-Transfer-Encoding")
+                .unwrap(),
+            "8bit"
+        );
+        assert_eq!(
+            attached.get_body_raw().unwrap(),
+            b"Subject: old\r\n\r\nCaf\xe9\r\n".to_vec()
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! ## Notmuch
+//!
+//! `notmuch` uses the notmuch database found in `.notmuch` under the mail root,
+//! through the `notmuch` command, for saved queries and tags
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Prefix of the tree node ids of saved queries
+pub const NODE_PREFIX: &str = "notmuch:";
+
+#[derive(Clone)]
+pub struct Notmuch {
+    root: PathBuf,
+}
+
+/// A message found by a query
+#[derive(Debug, PartialEq, Eq)]
+pub struct Message {
+    pub id: String,
+    pub filename: PathBuf,
+    pub tags: Vec<String>,
+}
+
+impl Notmuch {
+    /// ### `detect`
+    ///
+    /// A notmuch database is used when `.notmuch` exists under the mail root
+    pub fn detect(root: &Path) -> Option<Self> {
+        root.join(".notmuch").is_dir().then(|| Self {
+            root: root.to_path_buf(),
+        })
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("notmuch")
+            .args(args)
+            .env("NOTMUCH_DATABASE", &self.root)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| anyhow!("Failed to run notmuch: {e}"))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "notmuch {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// ### `saved_queries`
+    ///
+    /// Queries saved with `notmuch config set query.<name>`, or a few usual ones
+    pub fn saved_queries(&self) -> Vec<(String, String)> {
+        let queries = self
+            .run(&["config", "list"])
+            .map(|output| parse_saved_queries(&output))
+            .unwrap_or_default();
+        if queries.is_empty() {
+            return vec![
+                ("inbox".to_string(), "tag:inbox".to_string()),
+                ("unread".to_string(), "tag:unread".to_string()),
+                ("flagged".to_string(), "tag:flagged".to_string()),
+            ];
+        }
+        queries
+    }
+
+    /// ### `search`
+    ///
+    /// Messages matching `query`, newest first
+    pub fn search(&self, query: &str) -> Result<Vec<Message>> {
+        let output = self.run(&[
+            "show",
+            "--format=json",
+            "--body=false",
+            "--entire-thread=false",
+            "--sort=newest-first",
+            query,
+        ])?;
+        let json: Value = serde_json::from_str(&output)?;
+        let mut messages = Vec::new();
+        collect_messages(&json, &mut messages);
+        Ok(messages)
+    }
+
+    /// ### `tag`
+    ///
+    /// Apply changes like `+todo -inbox` to the message with `message_id`
+    pub fn tag(&self, message_id: &str, changes: &[String]) -> Result<()> {
+        if let Some(bad) = changes
+            .iter()
+            .find(|c| !(c.starts_with('+') || c.starts_with('-')) || c.len() < 2)
+        {
+            return Err(anyhow!("Tag changes look like +tag or -tag, not {bad}"));
+        }
+        let query = id_query(message_id);
+        let mut args: Vec<&str> = vec!["tag"];
+        args.extend(changes.iter().map(String::as_str));
+        args.push("--");
+        args.push(&query);
+        self.run(&args)?;
+        Ok(())
+    }
+}
+
+fn id_query(message_id: &str) -> String {
+    format!("id:\"{}\"", message_id.replace('"', "\"\""))
+}
+
+fn parse_saved_queries(config: &str) -> Vec<(String, String)> {
+    config
+        .lines()
+        .filter_map(|line| {
+            let (key, query) = line.split_once('=')?;
+            let name = key.strip_prefix("query.")?;
+            Some((name.to_string(), query.to_string()))
+        })
+        .collect()
+}
+
+/// Messages are objects with an id, tags and the filenames, nested in threads
+fn collect_messages(json: &Value, messages: &mut Vec<Message>) {
+    match json {
+        Value::Array(values) => {
+            for value in values {
+                collect_messages(value, messages);
+            }
+        }
+        Value::Object(object) => {
+            let filename = match object.get("filename") {
+                Some(Value::String(f)) => Some(f.as_str()),
+                // several files since notmuch 0.28
+                Some(Value::Array(files)) => files.first().and_then(Value::as_str),
+                _ => None,
+            };
+            if let (Some(Value::String(id)), Some(filename)) = (object.get("id"), filename) {
+                let tags = object
+                    .get("tags")
+                    .and_then(Value::as_array)
+                    .map(|tags| {
+                        tags.iter()
+                            .filter_map(Value::as_str)
+                            .map(ToString::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                messages.push(Message {
+                    id: id.clone(),
+                    filename: PathBuf::from(filename),
+                    tags,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_notmuch_parse() {
+        let config = "database.path=/home/me/mail\nquery.inbox=tag:inbox and not tag:spam\nquery.work=folder:work\n";
+        assert_eq!(
+            parse_saved_queries(config),
+            vec![
+                (
+                    "inbox".to_string(),
+                    "tag:inbox and not tag:spam".to_string()
+                ),
+                ("work".to_string(), "folder:work".to_string())
+            ]
+        );
+
+        let show = r#"[[[{"id": "a@x", "filename": ["/m/INBOX/cur/1:2,S"], "tags": ["inbox"]},
+            [[{"id": "b@x", "filename": "/m/INBOX/new/2", "tags": ["inbox", "unread"]}, []]]]]]"#;
+        let json: Value = serde_json::from_str(show).unwrap();
+        let mut messages = Vec::new();
+        collect_messages(&json, &mut messages);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].filename, PathBuf::from("/m/INBOX/cur/1:2,S"));
+        assert_eq!(messages[1].tags, vec!["inbox", "unread"]);
+        assert_eq!(id_query("a\"b@x"), "id:\"a\"\"b@x\"");
+    }
+}
@@ -0,0 +1,501 @@
+//! ## Outbox
+//!
+//! `outbox` keeps mails which could not be sent yet in a maildir, and retries them with backoff
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::config::{AccountConfig, TermailConfig};
+use crate::transport;
+use anyhow::{anyhow, Result};
+use maildir::Maildir;
+use std::fmt::Write;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// envelope of a queued mail, in headers before the mail itself
+const HEADER_ACCOUNT: &str = "X-Termail-Account";
+const HEADER_FROM: &str = "X-Termail-From";
+const HEADER_RECIPIENTS: &str = "X-Termail-Recipients";
+const HEADER_SENT: &str = "X-Termail-Sent";
+const HEADER_ATTEMPTS: &str = "X-Termail-Attempts";
+const HEADER_RETRY: &str = "X-Termail-Retry";
+const HEADER_ERROR: &str = "X-Termail-Error";
+const HEADER_ORIGIN: &str = "X-Termail-Origin";
+const HEADER_DRAFT: &str = "X-Termail-Draft";
+const HEADER_PREFIX: &str = "X-Termail-";
+
+// a lock older than this was left by a flush which did not finish
+const LOCK_EXPIRE: Duration = Duration::from_mins(10);
+
+/// A mail waiting in the outbox, with what is needed to send it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Queued {
+    pub path: PathBuf,
+    /// name of the account sending it
+    pub account: String,
+    pub from: String,
+    pub recipients: Vec<String>,
+    /// maildir to keep a copy in once it is sent
+    pub sent: Option<PathBuf>,
+    pub attempts: u32,
+    /// unix time of the next try
+    pub retry: i64,
+    /// why the last try failed
+    pub error: Option<String>,
+    /// folder, id and flag of the mail it answers or forwards, flagged once it is sent
+    pub origin: Option<(PathBuf, String, String)>,
+    /// folder and id of the draft it was written in, removed once it is sent
+    pub draft: Option<(PathBuf, String)>,
+    pub message: Vec<u8>,
+}
+
+impl Queued {
+    pub fn new(
+        account: &str,
+        from: &str,
+        recipients: &[String],
+        sent: Option<PathBuf>,
+        message: &[u8],
+    ) -> Self {
+        Self {
+            path: PathBuf::new(),
+            account: account.to_string(),
+            from: from.to_string(),
+            recipients: recipients.to_vec(),
+            sent,
+            attempts: 0,
+            retry: 0,
+            error: None,
+            origin: None,
+            draft: None,
+            message: message.to_vec(),
+        }
+    }
+
+    /// ### `parse`
+    ///
+    /// Read a queued mail back from its file
+    pub fn parse(path: &Path, data: &[u8]) -> Result<Self> {
+        let mut queued = Self::new("", "", &[], None, b"");
+        queued.path = path.to_path_buf();
+        let mut rest = data;
+        while rest.starts_with(HEADER_PREFIX.as_bytes()) {
+            let end = rest
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .unwrap_or(rest.len());
+            let line = std::str::from_utf8(&rest[..end])
+                .map_err(|_| anyhow!("Bad outbox header in {}", path.display()))?;
+            rest = rest.get(end + 2..).unwrap_or_default();
+            let (name, value) = line
+                .split_once(": ")
+                .ok_or_else(|| anyhow!("Bad outbox header: {line}"))?;
+            match name {
+                HEADER_ACCOUNT => queued.account = value.to_string(),
+                HEADER_FROM => queued.from = value.to_string(),
+                HEADER_RECIPIENTS => {
+                    queued.recipients = value.split(", ").map(ToString::to_string).collect();
+                }
+                HEADER_SENT => queued.sent = Some(PathBuf::from(value)),
+                HEADER_ATTEMPTS => queued.attempts = value.parse()?,
+                HEADER_RETRY => queued.retry = value.parse()?,
+                HEADER_ERROR => queued.error = Some(value.to_string()),
+                HEADER_ORIGIN => {
+                    let mut fields = value.splitn(3, ' ');
+                    if let (Some(flag), Some(id), Some(folder)) =
+                        (fields.next(), fields.next(), fields.next())
+                    {
+                        queued.origin =
+                            Some((PathBuf::from(folder), id.to_string(), flag.to_string()));
+                    }
+                }
+                HEADER_DRAFT => {
+                    if let Some((id, folder)) = value.split_once(' ') {
+                        queued.draft = Some((PathBuf::from(folder), id.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if queued.account.is_empty() || queued.recipients.is_empty() {
+            return Err(anyhow!("{} is not a queued mail", path.display()));
+        }
+        queued.message = rest.to_vec();
+        Ok(queued)
+    }
+
+    /// ### `to_file`
+    ///
+    /// The mail with its envelope in front, as it is kept in the outbox
+    pub fn to_file(&self) -> Vec<u8> {
+        let mut text = format!(
+            "{HEADER_ACCOUNT}: {}\r\n{HEADER_FROM}: {}\r\n{HEADER_RECIPIENTS}: {}\r\n",
+            self.account,
+            self.from,
+            self.recipients.join(", ")
+        );
+        if let Some(sent) = &self.sent {
+            let _ = write!(text, "{HEADER_SENT}: {}\r\n", sent.display());
+        }
+        let _ = write!(
+            text,
+            "{HEADER_ATTEMPTS}: {}\r\n{HEADER_RETRY}: {}\r\n",
+            self.attempts, self.retry
+        );
+        if let Some(error) = &self.error {
+            let _ = write!(text, "{HEADER_ERROR}: {error}\r\n");
+        }
+        if let Some((folder, id, flag)) = &self.origin {
+            let _ = write!(
+                text,
+                "{HEADER_ORIGIN}: {flag} {id} {}\r\n",
+                folder.display()
+            );
+        }
+        if let Some((folder, id)) = &self.draft {
+            let _ = write!(text, "{HEADER_DRAFT}: {id} {}\r\n", folder.display());
+        }
+        let mut data = text.into_bytes();
+        data.extend_from_slice(&self.message);
+        data
+    }
+
+    /// ### `failed`
+    ///
+    /// Note a failed try, and put the next one off
+    pub fn failed(&mut self, error: &str, now: i64) {
+        self.attempts += 1;
+        self.retry = now + backoff(self.attempts);
+        self.error = Some(error.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    /// ### `finish`
+    ///
+    /// Once the mail is sent, flag the mail it answers or forwards and remove the draft it
+    /// was written in
+    pub fn finish(&self) -> Result<()> {
+        if let Some((folder, id)) = &self.draft {
+            Maildir::from(folder.clone())
+                .delete(id)
+                .map_err(|e| anyhow!("Failed to remove the old draft: {e}"))?;
+        }
+        if let Some((folder, id, flag)) = &self.origin {
+            let maildir = Maildir::from(folder.clone());
+            // only mails in cur carry flags
+            if maildir
+                .list_new()
+                .filter_map(Result::ok)
+                .any(|e| e.id() == id)
+            {
+                maildir.move_new_to_cur(id)?;
+            }
+            maildir.add_flags(id, flag)?;
+        }
+        Ok(())
+    }
+}
+
+/// ### `backoff`
+///
+/// Seconds to wait after `attempts` failed tries: 1, 2, 4 and so on minutes, at most an hour
+pub fn backoff(attempts: u32) -> i64 {
+    let doubled = 1_i64 << attempts.saturating_sub(1).min(6);
+    (60 * doubled).min(3600)
+}
+
+/// What a flush of the outbox did, and what is left
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub sent: usize,
+    /// failures of this flush, with the recipients of the mail
+    pub errors: Vec<String>,
+    pub queued: usize,
+    /// unix time of the next try, if any mail is left
+    pub retry: Option<i64>,
+}
+
+/// ### `folder`
+///
+/// Outbox maildir of `config`, under the mail dir
+pub fn folder(config: &TermailConfig) -> PathBuf {
+    let mail_dir = config
+        .mail_dir_from_cli
+        .as_ref()
+        .unwrap_or(&config.mail_dir);
+    Path::new(shellexpand::tilde(mail_dir).as_ref())
+        .join(shellexpand::tilde(&config.compose.outbox).as_ref())
+}
+
+pub struct Outbox {
+    maildir: Maildir,
+}
+
+impl Outbox {
+    pub fn new(folder: PathBuf) -> Self {
+        Self {
+            maildir: Maildir::from(folder),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.maildir.path()
+    }
+
+    /// ### `enqueue`
+    ///
+    /// Keep `queued` in the outbox until it is sent
+    pub fn enqueue(&self, queued: &Queued) -> Result<()> {
+        self.maildir.create_dirs()?;
+        self.maildir
+            .store_new(&queued.to_file())
+            .map_err(|e| anyhow!("Failed to queue in {}: {e}", self.path().display()))?;
+        Ok(())
+    }
+
+    /// ### `list`
+    ///
+    /// Mails in the outbox, oldest try first
+    pub fn list(&self) -> Vec<Queued> {
+        if !self.path().join("new").is_dir() {
+            return Vec::new();
+        }
+        let mut queued: Vec<Queued> = self
+            .maildir
+            .list_new()
+            .chain(self.maildir.list_cur())
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let data = fs::read(entry.path()).ok()?;
+                Queued::parse(entry.path(), &data).ok()
+            })
+            .collect();
+        queued.sort_by_key(|q| q.retry);
+        queued
+    }
+
+    /// ### `flush`
+    ///
+    /// Send the mails whose next try is due, or all of them with `all`
+    pub fn flush(&self, accounts: &[AccountConfig], now: i64, all: bool) -> Result<Report> {
+        let mut report = Report::default();
+        if !self.path().join("new").is_dir() {
+            return Ok(report);
+        }
+        let _lock = Lock::take(&self.path().join(".lock"))?;
+        for mut queued in self.list() {
+            if !all && queued.retry > now {
+                report.queued += 1;
+                report.retry = Some(report.retry.map_or(queued.retry, |r| r.min(queued.retry)));
+                continue;
+            }
+            match send(accounts, &queued) {
+                Ok(()) => {
+                    fs::remove_file(&queued.path)?;
+                    report.sent += 1;
+                    if let Some(sent) = &queued.sent {
+                        // the mail is gone already, so this is no reason to send it again
+                        if let Err(e) = transport::save_sent(sent, &queued.message) {
+                            report.errors.push(format!("Mail sent, but not saved: {e}"));
+                        }
+                    }
+                    if let Err(e) = queued.finish() {
+                        report.errors.push(format!("Mail sent, but {e}"));
+                    }
+                }
+                Err(e) => {
+                    queued.failed(&e.to_string(), now);
+                    report.errors.push(format!(
+                        "{}: {}",
+                        queued.recipients.join(", "),
+                        queued.error.as_deref().unwrap_or_default()
+                    ));
+                    self.update(&queued)?;
+                    report.queued += 1;
+                    report.retry = Some(report.retry.map_or(queued.retry, |r| r.min(queued.retry)));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Write the new state of a queued mail over its file
+    fn update(&self, queued: &Queued) -> Result<()> {
+        let name = queued
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow!("Bad outbox file {}", queued.path.display()))?;
+        let tmp = self.path().join("tmp").join(name);
+        fs::write(&tmp, queued.to_file())?;
+        fs::rename(&tmp, &queued.path)?;
+        Ok(())
+    }
+}
+
+fn send(accounts: &[AccountConfig], queued: &Queued) -> Result<()> {
+    let account = accounts
+        .iter()
+        .find(|a| a.name == queued.account)
+        .ok_or_else(|| anyhow!("No account is named {}", queued.account))?;
+    transport::send(account, &queued.from, &queued.recipients, &queued.message)
+}
+
+/// Lock file keeping two flushes from sending the same mail, removed when dropped
+struct Lock(PathBuf);
+
+impl Lock {
+    fn take(path: &Path) -> Result<Self> {
+        let stale = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .is_ok_and(|age| age > LOCK_EXPIRE)
+            });
+        if stale {
+            fs::remove_file(path).ok();
+        }
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(_) => Ok(Self(path.to_path_buf())),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                Err(anyhow!("The outbox is being sent already"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn account(name: &str, command: &str) -> AccountConfig {
+        AccountConfig {
+            name: name.to_string(),
+            address: format!("{name}@example.org"),
+            folder: String::new(),
+            sendmail_command: Some(command.to_string()),
+            save_sent: false,
+            sent: None,
+            smtp: None,
+        }
+    }
+
+    #[test]
+    fn test_outbox_queued() {
+        let mut queued = Queued::new(
+            "work",
+            "me@example.org",
+            &["a@example.org".to_string(), "b@example.org".to_string()],
+            Some(PathBuf::from("/mail/Sent")),
+            b"Subject: hi\r\n\r\n\xe9t\xe9\r\n",
+        );
+        queued.origin = Some((
+            PathBuf::from("/mail/My Inbox"),
+            "1.a".to_string(),
+            "R".to_string(),
+        ));
+        queued.draft = Some((PathBuf::from("/mail/Drafts"), "2.b".to_string()));
+        queued.failed("connection\r\n refused", 1000);
+        let parsed = Queued::parse(Path::new(""), &queued.to_file()).unwrap();
+        assert_eq!(parsed, queued);
+        assert_eq!(parsed.error.as_deref(), Some("connection refused"));
+        assert_eq!(parsed.retry, 1060);
+        assert_eq!(parsed.message, b"Subject: hi\r\n\r\n\xe9t\xe9\r\n");
+
+        assert_eq!(backoff(1), 60);
+        assert_eq!(backoff(3), 240);
+        assert_eq!(backoff(40), 3600);
+        assert!(Queued::parse(Path::new(""), b"Subject: hi\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_outbox_flush() {
+        let root = std::env::temp_dir().join(format!("termail-outbox-{}", std::process::id()));
+        let outbox = Outbox::new(root.join("Outbox"));
+        let out = root.join("out.eml");
+        let accounts = vec![
+            account("home", &format!("sh -c \"cat > '{}'\"", out.display())),
+            account("work", "sh -c 'exit 75'"),
+        ];
+        assert_eq!(
+            outbox.flush(&accounts, 0, false).unwrap(),
+            Report::default()
+        );
+
+        // the mail answered, and the draft of the answer
+        let inbox = Maildir::from(root.join("Inbox"));
+        let drafts = Maildir::from(root.join("Drafts"));
+        inbox.create_dirs().unwrap();
+        drafts.create_dirs().unwrap();
+        let original = inbox.store_new(b"Subject: 0\r\n\r\n").unwrap();
+        let draft = drafts
+            .store_cur_with_flags(b"Subject: 1\r\n\r\n", "D")
+            .unwrap();
+
+        let to = ["a@example.org".to_string()];
+        let mut answer = Queued::new("home", "h@example.org", &to, None, b"Subject: 1\r\n\r\n");
+        answer.origin = Some((
+            inbox.path().to_path_buf(),
+            original.clone(),
+            "R".to_string(),
+        ));
+        answer.draft = Some((drafts.path().to_path_buf(), draft.clone()));
+        outbox.enqueue(&answer).unwrap();
+        let mut unsent = answer;
+        unsent.account = "work".to_string();
+        unsent.message = b"Subject: 2\r\n\r\n".to_vec();
+        outbox.enqueue(&unsent).unwrap();
+        let report = outbox.flush(&accounts, 100, false).unwrap();
+        assert_eq!(report.sent, 1);
+        assert_eq!(report.queued, 1);
+        assert_eq!(report.retry, Some(160));
+        assert_eq!(
+            report.errors,
+            vec!["a@example.org: sh -c 'exit 75' exited with exit status: 75:"]
+        );
+        assert_eq!(fs::read_to_string(&out).unwrap(), "Subject: 1\r\n\r\n");
+        assert!(inbox.find(&original).unwrap().is_replied());
+        assert!(drafts.find(&draft).is_none());
+
+        // not due yet
+        let report = outbox.flush(&accounts, 120, false).unwrap();
+        assert_eq!((report.sent, report.queued), (0, 1));
+        let left = outbox.list();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].attempts, 1);
+
+        let _lock = Lock::take(&outbox.path().join(".lock")).unwrap();
+        assert!(outbox.flush(&accounts, 200, true).is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,444 @@
+//! ## Pattern
+//!
+//! `pattern` parses mutt style limit patterns like `~f alice ~s report | ~F` and matches mails
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use chrono::{Datelike, Local, NaiveDate, TimeDelta, TimeZone};
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// What a pattern can look at in a mail
+pub trait Matchable {
+    fn sender(&self) -> &str;
+    fn subject(&self) -> &str;
+    fn recipients(&self) -> &str;
+    fn body(&self) -> String;
+    fn date(&self) -> i64;
+    fn is_new(&self) -> bool;
+    fn is_flagged(&self) -> bool;
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    And(Vec<Self>),
+    Or(Vec<Self>),
+    Not(Box<Self>),
+    Sender(Regex),
+    Subject(Regex),
+    Recipient(Regex),
+    Body(Regex),
+    /// unix timestamps, start included and end excluded
+    Date(i64, i64),
+    New,
+    Flagged,
+}
+
+/// A pattern which could not be parsed, `position` counts characters from 1
+#[derive(Debug, PartialEq, Eq)]
+pub struct PatternError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Pattern {
+    /// ### `parse`
+    ///
+    /// Parse `input`, relative dates are counted from now
+    pub fn parse(input: &str) -> Result<Self, PatternError> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        };
+        let pattern = parser.expr()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(pattern),
+            Some(')') => Err(parser.error("unmatched )")),
+            Some(_) => Err(parser.error("expected a pattern")),
+        }
+    }
+
+    /// ### `needs_body`
+    ///
+    /// Whether matching reads the body of the mails, which is slow
+    pub fn needs_body(&self) -> bool {
+        match self {
+            Self::And(patterns) | Self::Or(patterns) => patterns.iter().any(Self::needs_body),
+            Self::Not(pattern) => pattern.needs_body(),
+            Self::Body(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn matches(&self, mail: &dyn Matchable) -> bool {
+        match self {
+            Self::And(patterns) => patterns.iter().all(|p| p.matches(mail)),
+            Self::Or(patterns) => patterns.iter().any(|p| p.matches(mail)),
+            Self::Not(pattern) => !pattern.matches(mail),
+            Self::Sender(re) => re.is_match(mail.sender()),
+            Self::Subject(re) => re.is_match(mail.subject()),
+            Self::Recipient(re) => re.is_match(mail.recipients()),
+            Self::Body(re) => re.is_match(&mail.body()),
+            Self::Date(start, end) => (*start..*end).contains(&mail.date()),
+            Self::New => mail.is_new(),
+            Self::Flagged => mail.is_flagged(),
+        }
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> PatternError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &str) -> PatternError {
+        PatternError {
+            position: pos.min(self.chars.len()) + 1,
+            message: message.to_string(),
+        }
+    }
+
+    /// and ( '|' and )*
+    fn expr(&mut self) -> Result<Pattern, PatternError> {
+        let mut alternatives = vec![self.and()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('|') {
+                break;
+            }
+            self.pos += 1;
+            alternatives.push(self.and()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Pattern::Or(alternatives)
+        })
+    }
+
+    /// unary+
+    fn and(&mut self) -> Result<Pattern, PatternError> {
+        let mut patterns = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some('|' | ')') => break,
+                Some(_) => patterns.push(self.unary()?),
+            }
+        }
+        match patterns.len() {
+            0 => Err(self.error("expected a pattern")),
+            1 => Ok(patterns.remove(0)),
+            _ => Ok(Pattern::And(patterns)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Pattern, PatternError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                Ok(Pattern::Not(Box::new(self.unary()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let pattern = self.expr()?;
+                self.skip_whitespace();
+                if self.peek() == Some(')') {
+                    self.pos += 1;
+                    Ok(pattern)
+                } else {
+                    Err(self.error_at(start, "missing )"))
+                }
+            }
+            Some('~') => {
+                self.pos += 1;
+                let Some(kind) = self.peek() else {
+                    return Err(self.error("missing pattern letter after ~"));
+                };
+                self.pos += 1;
+                match kind {
+                    'N' => Ok(Pattern::New),
+                    'F' => Ok(Pattern::Flagged),
+                    'f' => Ok(Pattern::Sender(self.regex()?)),
+                    's' => Ok(Pattern::Subject(self.regex()?)),
+                    't' => Ok(Pattern::Recipient(self.regex()?)),
+                    'b' => Ok(Pattern::Body(self.regex()?)),
+                    'd' => {
+                        let (arg_start, arg) = self.argument()?;
+                        let (start, end) = date_range(&arg, Local::now().timestamp())
+                            .map_err(|message| self.error_at(arg_start, &message))?;
+                        Ok(Pattern::Date(start, end))
+                    }
+                    other => Err(self.error_at(start, &format!("unknown pattern ~{other}"))),
+                }
+            }
+            Some(_) => Err(self.error("expected ~, ! or (")),
+            None => Err(self.error("expected a pattern")),
+        }
+    }
+
+    /// A word, or a string in single or double quotes
+    fn argument(&mut self) -> Result<(usize, String), PatternError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut arg = String::new();
+        match self.peek() {
+            None | Some(')' | '|') => return Err(self.error("missing argument")),
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                loop {
+                    match self.peek() {
+                        None => return Err(self.error_at(start, "unterminated quote")),
+                        Some(c) if c == quote => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some('\\') if self.chars.get(self.pos + 1) == Some(&quote) => {
+                            arg.push(quote);
+                            self.pos += 2;
+                        }
+                        Some(c) => {
+                            arg.push(c);
+                            self.pos += 1;
+                        }
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == ')' {
+                        break;
+                    }
+                    arg.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok((start, arg))
+    }
+
+    /// Case insensitive unless the pattern has an upper case letter
+    fn regex(&mut self) -> Result<Regex, PatternError> {
+        let (start, arg) = self.argument()?;
+        RegexBuilder::new(&arg)
+            .case_insensitive(!arg.chars().any(char::is_uppercase))
+            .build()
+            .map_err(|e| {
+                let reason = e.to_string();
+                let reason = reason.lines().last().unwrap_or_default().trim();
+                self.error_at(start, &format!("invalid regex: {reason}"))
+            })
+    }
+}
+
+/// ### `date_range`
+///
+/// Mutt date ranges: `DD/MM/YY-DD/MM/YY`, `DD/MM/YY-`, `-DD/MM/YY`, `DD/MM/YY`,
+/// `<3d` (less than 3 days ago) and `>2w` (more than 2 weeks ago), with units `d`, `w`, `m` and `y`
+fn date_range(arg: &str, now: i64) -> Result<(i64, i64), String> {
+    let ago = |offset: &str| {
+        now.checked_sub(relative_seconds(offset)?)
+            .ok_or_else(|| format!("date offset {offset} is too large"))
+    };
+    if let Some(offset) = arg.strip_prefix('<') {
+        return Ok((ago(offset)?, i64::MAX));
+    }
+    if let Some(offset) = arg.strip_prefix('>') {
+        return Ok((i64::MIN, ago(offset)?));
+    }
+    let current_year = Local
+        .timestamp_opt(now, 0)
+        .single()
+        .map_or(1970, |d| d.year());
+    if let Some((from, to)) = arg.split_once('-') {
+        let start = if from.is_empty() {
+            i64::MIN
+        } else {
+            day_start(parse_date(from, current_year)?)
+        };
+        let end = if to.is_empty() {
+            i64::MAX
+        } else {
+            let to = parse_date(to, current_year)?;
+            day_start(to.succ_opt().ok_or("invalid date")?)
+        };
+        Ok((start, end))
+    } else {
+        let date = parse_date(arg, current_year)?;
+        Ok((
+            day_start(date),
+            day_start(date.succ_opt().ok_or("invalid date")?),
+        ))
+    }
+}
+
+fn relative_seconds(offset: &str) -> Result<i64, String> {
+    let unit = offset.chars().last().ok_or("missing date offset")?;
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => return Err(format!("unknown date unit {unit}, use d, w, m or y")),
+    };
+    let count: i64 = offset[..offset.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| format!("invalid date offset {offset}"))?;
+    count
+        .checked_mul(days)
+        .and_then(TimeDelta::try_days)
+        .map(|delta| delta.num_seconds())
+        .ok_or_else(|| format!("date offset {offset} is too large"))
+}
+
+/// `DD/MM/YYYY`, `DD/MM/YY` or `DD/MM`
+fn parse_date(date: &str, current_year: i32) -> Result<NaiveDate, String> {
+    let invalid = || format!("invalid date {date}, use DD/MM/YY");
+    let parts: Vec<&str> = date.split('/').collect();
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+    let (day, month) = match parts.as_slice() {
+        [day, month] | [day, month, _] => (number(day)?, number(month)?),
+        _ => return Err(invalid()),
+    };
+    let year = match parts.get(2) {
+        None => current_year,
+        Some(y) if y.len() == 2 => 2000 + y.parse::<i32>().map_err(|_| invalid())?,
+        Some(y) => y.parse::<i32>().map_err(|_| invalid())?,
+    };
+    NaiveDate::from_ymd_opt(year, month, day).ok_or_else(invalid)
+}
+
+fn day_start(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|d| Local.from_local_datetime(&d).earliest())
+        .map_or(0, |d| d.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    struct Mail {
+        sender: &'static str,
+        subject: &'static str,
+        date: i64,
+        new: bool,
+    }
+
+    impl Matchable for Mail {
+        fn sender(&self) -> &str {
+            self.sender
+        }
+        fn subject(&self) -> &str {
+            self.subject
+        }
+        fn recipients(&self) -> &'static str {
+            "bob@example.com"
+        }
+        fn body(&self) -> String {
+            "see you tomorrow".to_string()
+        }
+        fn date(&self) -> i64 {
+            self.date
+        }
+        fn is_new(&self) -> bool {
+            self.new
+        }
+        fn is_flagged(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_pattern_match() {
+        let mail = Mail {
+            sender: "Alice <alice@example.com>",
+            subject: "Monthly Report",
+            date: day_start(NaiveDate::from_ymd_opt(2021, 6, 15).unwrap()) + 3600,
+            new: true,
+        };
+        let matches = |p: &str| Pattern::parse(p).unwrap().matches(&mail);
+        assert!(matches("~f alice ~s report"));
+        assert!(!matches("~s REPORT"));
+        assert!(matches("~f carol | ~N"));
+        assert!(!matches("!(~N | ~F)"));
+        assert!(matches("~b 'you tomorrow' ~t bob"));
+        assert!(matches("~d 15/06/21"));
+        assert!(matches("~d 01/06/2021-30/06/2021"));
+        assert!(!matches("~d -14/06/21"));
+        assert!(matches("~d 14/06/21-"));
+        assert!(matches("~d >1w"));
+        assert!(!matches("~d <1w"));
+        assert!(matches("~d <100000000d"));
+        assert!(!Pattern::parse("~s report").unwrap().needs_body());
+        assert!(Pattern::parse("~s report | !~b x").unwrap().needs_body());
+    }
+
+    #[test]
+    fn test_pattern_error() {
+        let error = |p: &str| Pattern::parse(p).unwrap_err().position;
+        assert_eq!(error("~s"), 3);
+        assert_eq!(error("~N ~x"), 4);
+        assert_eq!(error("~N (~F"), 4);
+        assert_eq!(error("~N )"), 4);
+        assert_eq!(error("~f ("), 4);
+        assert_eq!(error("~d 31/02/21"), 4);
+        assert_eq!(error("~d <200000000000000d"), 4);
+        assert_eq!(error("~d >9223372036854775807y"), 4);
+        assert_eq!(error("~N |"), 5);
+        assert_eq!(
+            Pattern::parse("~s 'open").unwrap_err().to_string(),
+            "column 4: unterminated quote"
+        );
+    }
+}
@@ -0,0 +1,481 @@
+//! ## Smtp
+//!
+//! `smtp` submits mails to the SMTP server of an account (RFC 5321, 3207 and 4954)
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::config::{SmtpAuth, SmtpConfig, SmtpSecurity};
+use anyhow::{anyhow, Context, Result};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_mins(1);
+
+/// ### `send`
+///
+/// Send `message` from `from` to `recipients` through the server of `config`
+pub fn send(config: &SmtpConfig, from: &str, recipients: &[String], message: &[u8]) -> Result<()> {
+    let password = match (&config.user, &config.password_command) {
+        (Some(_), Some(command)) => Some(password(command)?),
+        _ => None,
+    };
+    let tcp = TcpStream::connect((config.host.as_str(), config.port()))
+        .with_context(|| format!("Failed to connect to {}:{}", config.host, config.port()))?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let stream = if config.security == SmtpSecurity::Tls {
+        Stream::Tls(Box::new(tls(&config.host, tcp)?))
+    } else {
+        Stream::Plain(tcp)
+    };
+    // greet with the domain we send from
+    let domain = from
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain);
+    let mut client = Client::new(stream);
+    client.expect("connect", 220)?;
+    let mut extensions = client.ehlo(domain)?;
+    if config.security == SmtpSecurity::StartTls {
+        if !extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case("STARTTLS"))
+        {
+            return Err(anyhow!("{} does not offer STARTTLS", config.host));
+        }
+        client.command("STARTTLS", 220)?;
+        client = client.start_tls(&config.host)?;
+        extensions = client.ehlo(domain)?;
+    }
+    let eight_bit = !message.is_ascii();
+    if eight_bit
+        && !extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case("8BITMIME"))
+    {
+        // only attached mails are 8bit, and they can't be encoded again
+        return Err(anyhow!("{} does not accept 8bit mail", config.host));
+    }
+    if let (Some(user), Some(password)) = (&config.user, &password) {
+        if config.security == SmtpSecurity::None && !config.allow_plaintext_auth {
+            return Err(anyhow!(
+                "Refusing to send the password to {} unencrypted, set allow_plaintext_auth \
+                 to allow it",
+                config.host
+            ));
+        }
+        let mechanism = match config.auth {
+            SmtpAuth::Plain => "PLAIN",
+            SmtpAuth::Login => "LOGIN",
+        };
+        if !offers_auth(&extensions, mechanism) {
+            return Err(anyhow!("{} does not offer AUTH {mechanism}", config.host));
+        }
+        client.login(config.auth, user, password)?;
+    }
+    if let Err(e) = client.transaction(from, recipients, message, eight_bit) {
+        // leave the server cleanly, the transaction is abandoned
+        client.command("RSET", 250).ok();
+        client.command("QUIT", 221).ok();
+        return Err(e);
+    }
+    // the mail is accepted, a failing QUIT doesn't matter
+    client.command("QUIT", 221).ok();
+    Ok(())
+}
+
+/// Whether the EHLO `extensions` offer the AUTH `mechanism`, as "AUTH PLAIN LOGIN" or
+/// the older "AUTH=PLAIN LOGIN"
+fn offers_auth(extensions: &[String], mechanism: &str) -> bool {
+    extensions.iter().any(|extension| {
+        let mut words = extension.split(|c: char| c.is_whitespace() || c == '=');
+        words
+            .next()
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("AUTH"))
+            && words.any(|m| m.eq_ignore_ascii_case(mechanism))
+    })
+}
+
+/// ### `password`
+///
+/// First line printed by the password command
+pub fn password(command: &str) -> Result<String> {
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{command} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+fn tls(host: &str, tcp: TcpStream) -> Result<StreamOwned<ClientConnection, TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())?;
+    let connection = ClientConnection::new(Arc::new(config), name)?;
+    Ok(StreamOwned::new(connection, tcp))
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(s) => s.read(buf),
+            Self::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(s) => s.write(buf),
+            Self::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(s) => s.flush(),
+            Self::Tls(s) => s.flush(),
+        }
+    }
+}
+
+/// A server reply: code and text lines
+struct Reply {
+    code: u16,
+    lines: Vec<String>,
+}
+
+struct Client<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> Client<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn read_reply(&mut self) -> Result<Reply> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(anyhow!("The SMTP server closed the connection"));
+            }
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|c| c.parse::<u16>().ok())
+                .ok_or_else(|| anyhow!("Not an SMTP reply: {line}"))?;
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+            // "250-" continues, "250 " ends the reply
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(Reply { code, lines });
+            }
+        }
+    }
+
+    /// Read a reply and fail with the server's words unless it has code `expected`
+    fn expect(&mut self, after: &str, expected: u16) -> Result<Reply> {
+        let reply = self.read_reply()?;
+        if reply.code == expected {
+            Ok(reply)
+        } else {
+            Err(anyhow!(
+                "SMTP {} after {after}: {}",
+                reply.code,
+                reply.lines.join(" ")
+            ))
+        }
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str, expected: u16) -> Result<Reply> {
+        self.send_line(command)?;
+        // keep secrets out of error messages
+        let name = command.split_whitespace().take(2).collect::<Vec<_>>();
+        let name = if name.first() == Some(&"AUTH") {
+            name.join(" ")
+        } else {
+            command.to_string()
+        };
+        self.expect(&name, expected)
+    }
+
+    /// Greet the server and return the extensions it offers
+    fn ehlo(&mut self, domain: &str) -> Result<Vec<String>> {
+        let reply = self.command(&format!("EHLO {domain}"), 250)?;
+        Ok(reply.lines.into_iter().skip(1).collect())
+    }
+
+    fn login(&mut self, auth: SmtpAuth, user: &str, password: &str) -> Result<()> {
+        match auth {
+            SmtpAuth::Plain => {
+                let token = base64::encode(format!("\0{user}\0{password}"));
+                self.command(&format!("AUTH PLAIN {token}"), 235)?;
+            }
+            SmtpAuth::Login => {
+                self.command("AUTH LOGIN", 334)?;
+                self.send_line(&base64::encode(user))?;
+                self.expect("AUTH LOGIN user", 334)?;
+                self.send_line(&base64::encode(password))?;
+                self.expect("AUTH LOGIN password", 235)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn transaction(
+        &mut self,
+        from: &str,
+        recipients: &[String],
+        message: &[u8],
+        eight_bit: bool,
+    ) -> Result<()> {
+        let body = if eight_bit { " BODY=8BITMIME" } else { "" };
+        self.command(&format!("MAIL FROM:<{from}>{body}"), 250)?;
+        for recipient in recipients {
+            self.send_line(&format!("RCPT TO:<{recipient}>"))?;
+            let reply = self.read_reply()?;
+            // 251: user not local, will forward
+            if reply.code != 250 && reply.code != 251 {
+                return Err(anyhow!(
+                    "SMTP {} for {recipient}: {}",
+                    reply.code,
+                    reply.lines.join(" ")
+                ));
+            }
+        }
+        self.command("DATA", 354)?;
+        let stream = self.stream.get_mut();
+        let message = message.strip_suffix(b"\n").unwrap_or(message);
+        for line in message.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            // a lone dot would end the mail early
+            if line.starts_with(b".") {
+                stream.write_all(b".")?;
+            }
+            stream.write_all(line)?;
+            stream.write_all(b"\r\n")?;
+        }
+        self.command(".", 250)?;
+        Ok(())
+    }
+}
+
+impl Client<Stream> {
+    fn start_tls(self, host: &str) -> Result<Self> {
+        match self.stream.into_inner() {
+            Stream::Plain(tcp) => Ok(Self::new(Stream::Tls(Box::new(tls(host, tcp)?)))),
+            Stream::Tls(_) => Err(anyhow!("The connection is encrypted already")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A stand-in SMTP server, answering each command with the next scripted reply.
+    /// Returns its port and a handle giving the lines it received.
+    fn server(replies: Vec<&'static str>) -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = Vec::new();
+            let mut in_data = false;
+            for reply in replies {
+                if !received.is_empty() || in_data {
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap() == 0 {
+                            return received;
+                        }
+                        let line = line.trim_end().to_string();
+                        received.push(line.clone());
+                        if !in_data || line == "." {
+                            break;
+                        }
+                    }
+                }
+                in_data = reply.starts_with("354");
+                writer.write_all(reply.as_bytes()).unwrap();
+                writer.write_all(b"\r\n").unwrap();
+                if received.is_empty() && !in_data {
+                    // the greeting is sent before any command
+                    received.push(String::new());
+                }
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            user: Some("me".to_string()),
+            auth: SmtpAuth::Plain,
+            password_command: Some("echo secret".to_string()),
+            // the stand-in server has no TLS
+            allow_plaintext_auth: true,
+        }
+    }
+
+    #[test]
+    fn test_smtp_send() {
+        let (port, handle) = server(vec![
+            "220 stand-in ready",
+            "250-stand-in\r\n250-AUTH PLAIN LOGIN\r\n250 8BITMIME",
+            "235 ok",
+            "250 sender ok",
+            "250 recipient ok",
+            "251 will forward",
+            "354 go ahead",
+            "250 queued",
+            "221 bye",
+        ]);
+        let recipients = vec![
+            "you@example.com".to_string(),
+            "them@example.org".to_string(),
+        ];
+        send(
+            &config(port),
+            "me@example.com",
+            &recipients,
+            b"Subject: hi\r\n\r\n.hidden dot\r\nbye\r\n",
+        )
+        .unwrap();
+        let received = handle.join().unwrap();
+        assert_eq!(
+            received[1..].to_vec(),
+            vec![
+                "EHLO example.com".to_string(),
+                format!("AUTH PLAIN {}", base64::encode("\0me\0secret")),
+                "MAIL FROM:<me@example.com>".to_string(),
+                "RCPT TO:<you@example.com>".to_string(),
+                "RCPT TO:<them@example.org>".to_string(),
+                "DATA".to_string(),
+                "Subject: hi".to_string(),
+                String::new(),
+                "..hidden dot".to_string(),
+                "bye".to_string(),
+                ".".to_string(),
+                "QUIT".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_smtp_error() {
+        let (port, handle) = server(vec![
+            "220 stand-in ready",
+            "250-stand-in\r\n250 AUTH=PLAIN",
+            "235 ok",
+            "250 sender ok",
+            "550 5.1.1 no such user",
+            "250 reset",
+            "221 bye",
+        ]);
+        let error = send(
+            &config(port),
+            "me@example.com",
+            &["nobody@example.com".to_string()],
+            b"Subject: hi\r\n\r\nbody\r\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SMTP 550 for nobody@example.com: 5.1.1 no such user"
+        );
+        let received = handle.join().unwrap();
+        assert_eq!(received[received.len() - 2..], ["RSET", "QUIT"]);
+
+        let (port, handle) = server(vec!["220 stand-in ready", "250-stand-in\r\n250 AUTH LOGIN"]);
+        let error = send(&config(port), "me@example.com", &[], b"").unwrap_err();
+        assert_eq!(error.to_string(), "127.0.0.1 does not offer AUTH PLAIN");
+        handle.join().unwrap();
+
+        let (port, handle) = server(vec!["220 stand-in ready", "250-stand-in\r\n250 AUTH PLAIN"]);
+        let mut plaintext = config(port);
+        plaintext.allow_plaintext_auth = false;
+        let error = send(&plaintext, "me@example.com", &[], b"").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Refusing to send the password"));
+        handle.join().unwrap();
+
+        let (port, handle) = server(vec!["220 stand-in ready", "250 stand-in"]);
+        let mut starttls = config(port);
+        starttls.security = SmtpSecurity::StartTls;
+        let error = send(&starttls, "me@example.com", &[], b"").unwrap_err();
+        assert_eq!(error.to_string(), "127.0.0.1 does not offer STARTTLS");
+        handle.join().unwrap();
+
+        let (port, handle) = server(vec!["220 stand-in ready", "250 stand-in"]);
+        let error = send(&config(port), "me@example.com", &[], b"caf\xe9").unwrap_err();
+        assert_eq!(error.to_string(), "127.0.0.1 does not accept 8bit mail");
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,475 @@
+//! ## Spell
+//!
+//! `spell` checks words against a hunspell dictionary, a `.dic` word list with the affix
+//! rules of the `.aff` file next to it
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::iter::once;
+use std::ops::Range;
+use std::path::Path;
+
+/// How flags are written in the dictionary
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FlagType {
+    /// one character each
+    Char,
+    /// two characters each
+    Long,
+    /// numbers separated by commas
+    Num,
+}
+
+/// A character of an affix condition
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Condition {
+    Any,
+    Char(char),
+    /// characters in brackets, or not in them when negated
+    Set(bool, Vec<char>),
+}
+
+impl Condition {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Char(expected) => *expected == c,
+            Self::Set(negated, chars) => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+/// A prefix or suffix rule: `strip` is taken off the word and `add` put in its place
+#[derive(Clone, Debug)]
+struct Affix {
+    flag: String,
+    /// may be combined with an affix of the other kind
+    cross: bool,
+    strip: String,
+    add: String,
+    condition: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    /// words with their flags
+    words: HashMap<String, Vec<String>>,
+    /// affixes by the text they add
+    prefixes: HashMap<String, Vec<Affix>>,
+    suffixes: HashMap<String, Vec<Affix>>,
+}
+
+impl Dictionary {
+    /// ### `load`
+    ///
+    /// Dictionary of the `.dic` file at `path`, with the affixes of the `.aff` file beside it
+    pub fn load(path: &Path) -> Result<Self> {
+        let dic = fs::read(path)
+            .map_err(|e| anyhow!("Failed to read dictionary {}: {e}", path.display()))?;
+        let aff = fs::read(path.with_extension("aff")).unwrap_or_default();
+        // dictionaries in ISO 8859-1 say so in the affix file
+        let latin1 = String::from_utf8_lossy(&aff).lines().any(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next() == Some("SET")
+                && fields
+                    .next()
+                    .is_some_and(|set| set.eq_ignore_ascii_case("ISO8859-1"))
+        });
+        let decode = |bytes: &[u8]| {
+            if latin1 {
+                bytes.iter().map(|b| char::from(*b)).collect()
+            } else {
+                String::from_utf8_lossy(bytes).to_string()
+            }
+        };
+        Ok(Self::parse(&decode(&aff), &decode(&dic)))
+    }
+
+    /// ### `parse`
+    ///
+    /// Dictionary of the content of an affix file and a word list
+    pub fn parse(aff: &str, dic: &str) -> Self {
+        let mut dictionary = Self::default();
+        let mut prefixes: Vec<Affix> = Vec::new();
+        let mut suffixes: Vec<Affix> = Vec::new();
+        let mut flag_type = FlagType::Char;
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let affixes = if fields.first() == Some(&"PFX") {
+                &mut prefixes
+            } else {
+                &mut suffixes
+            };
+            match fields.as_slice() {
+                ["FLAG", kind, ..] => {
+                    flag_type = match *kind {
+                        "long" => FlagType::Long,
+                        "num" => FlagType::Num,
+                        _ => FlagType::Char,
+                    };
+                }
+                // the header of a rule group: kind, flag, cross product, count
+                ["PFX" | "SFX", flag, cross, count] if count.parse::<usize>().is_ok() => {
+                    let affix = Affix {
+                        flag: (*flag).to_string(),
+                        cross: *cross == "Y",
+                        strip: String::new(),
+                        add: String::new(),
+                        condition: Vec::new(),
+                    };
+                    // remembered as a rule without text, filled in by the rule lines
+                    affixes.push(affix);
+                }
+                ["PFX" | "SFX", flag, strip, add, rest @ ..] => {
+                    let Some(header) = affixes.iter().rev().find(|a| a.flag == *flag) else {
+                        continue;
+                    };
+                    let affix = Affix {
+                        flag: (*flag).to_string(),
+                        cross: header.cross,
+                        strip: if *strip == "0" {
+                            String::new()
+                        } else {
+                            (*strip).to_string()
+                        },
+                        // continuation flags after the slash are not followed
+                        add: match add.split('/').next() {
+                            Some("0") | None => String::new(),
+                            Some(add) => add.to_string(),
+                        },
+                        condition: parse_condition(rest.first().copied().unwrap_or(".")),
+                    };
+                    affixes.push(affix);
+                }
+                _ => {}
+            }
+        }
+        dictionary.prefixes = index_affixes(prefixes);
+        dictionary.suffixes = index_affixes(suffixes);
+
+        // the first line is the number of words
+        for line in dic.lines().skip(1) {
+            // morphological fields follow a tab or space
+            let entry = line.split(['\t', ' ']).next().unwrap_or_default();
+            if entry.is_empty() {
+                continue;
+            }
+            let (word, flags) = match entry.find('/') {
+                // a slash may be escaped in the word
+                Some(slash) if slash > 0 && !entry[..slash].ends_with('\\') => {
+                    (&entry[..slash], split_flags(&entry[slash + 1..], flag_type))
+                }
+                _ => (entry, Vec::new()),
+            };
+            dictionary
+                .words
+                .entry(word.replace("\\/", "/"))
+                .or_default()
+                .extend(flags);
+        }
+        dictionary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// ### `check`
+    ///
+    /// Whether `word` is spelled right, in lower case too when it is capitalized or all caps
+    pub fn check(&self, word: &str) -> bool {
+        if self.check_exact(word) {
+            return true;
+        }
+        let mut chars = word.chars();
+        let Some(first) = chars.next() else {
+            return true;
+        };
+        let rest: String = chars.collect();
+        if !first.is_uppercase() {
+            return false;
+        }
+        let cased = rest.chars().all(char::is_lowercase) || rest.chars().all(char::is_uppercase);
+        if cased && self.check_exact(&word.to_lowercase()) {
+            return true;
+        }
+        // all caps, as a capitalized word
+        let mut capitalized: String = first.to_string();
+        capitalized.push_str(&rest.to_lowercase());
+        word.chars().all(|c| !c.is_lowercase()) && self.check_exact(&capitalized)
+    }
+
+    fn check_exact(&self, word: &str) -> bool {
+        self.words.contains_key(word)
+            || self.suffixes_of(word).any(|suffix| {
+                self.has_root(strip_suffix(word, suffix).as_deref(), &suffix.flag, None)
+            })
+            || self.prefixes_of(word).any(|prefix| {
+                let stem = strip_prefix(word, prefix);
+                self.has_root(stem.as_deref(), &prefix.flag, None)
+                    || (prefix.cross
+                        && stem.as_deref().is_some_and(|stem| {
+                            self.suffixes_of(stem).filter(|s| s.cross).any(|suffix| {
+                                self.has_root(
+                                    strip_suffix(stem, suffix).as_deref(),
+                                    &suffix.flag,
+                                    Some(&prefix.flag),
+                                )
+                            })
+                        }))
+            })
+    }
+
+    /// Prefixes adding a text `word` starts with
+    fn prefixes_of<'a>(&'a self, word: &'a str) -> impl Iterator<Item = &'a Affix> + 'a {
+        boundaries(word)
+            .filter_map(move |i| self.prefixes.get(&word[..i]))
+            .flatten()
+    }
+
+    /// Suffixes adding a text `word` ends with
+    fn suffixes_of<'a>(&'a self, word: &'a str) -> impl Iterator<Item = &'a Affix> + 'a {
+        boundaries(word)
+            .filter_map(move |i| self.suffixes.get(&word[i..]))
+            .flatten()
+    }
+
+    /// Whether `root` is a word with `flag`, and `other` too if given
+    fn has_root(&self, root: Option<&str>, flag: &str, other: Option<&str>) -> bool {
+        root.and_then(|root| self.words.get(root))
+            .is_some_and(|flags| {
+                flags.iter().any(|f| f == flag)
+                    && other.is_none_or(|other| flags.iter().any(|f| f == other))
+            })
+    }
+
+    /// ### `misspelled`
+    ///
+    /// Character ranges of the words of `line` not found in the dictionary. Quoted lines,
+    /// words with digits and addresses are left alone
+    pub fn misspelled(&self, line: &str) -> Vec<Range<usize>> {
+        if self.is_empty() || line.trim_start().starts_with('>') {
+            return Vec::new();
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut ranges = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].is_whitespace() {
+                i += 1;
+                continue;
+            }
+            // a run of text up to the next space, which may hold several words
+            let end = chars[i..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map_or(chars.len(), |n| i + n);
+            let token: String = chars[i..end].iter().collect();
+            if !token.contains(['@', '/', '_']) && !token.chars().any(|c| c.is_ascii_digit()) {
+                for word in words(&chars[i..end]) {
+                    let text: String = chars[i + word.start..i + word.end]
+                        .iter()
+                        .map(|c| if *c == '\u{2019}' { '\'' } else { *c })
+                        .collect();
+                    if !self.check(&text) {
+                        ranges.push(i + word.start..i + word.end);
+                    }
+                }
+            }
+            i = end;
+        }
+        ranges
+    }
+}
+
+/// Ranges of the words in `chars`: letters with apostrophes between them
+fn words(chars: &[char]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, c) in chars.iter().enumerate() {
+        let apostrophe = matches!(c, '\'' | '\u{2019}')
+            && start.is_some()
+            && chars.get(i + 1).is_some_and(|next| next.is_alphabetic());
+        if c.is_alphabetic() || apostrophe {
+            start.get_or_insert(i);
+        } else if let Some(s) = start.take() {
+            ranges.push(s..i);
+        }
+    }
+    if let Some(s) = start {
+        ranges.push(s..chars.len());
+    }
+    ranges
+}
+
+/// Byte offsets between the characters of `word`, its ends included
+fn boundaries(word: &str) -> impl Iterator<Item = usize> + '_ {
+    word.char_indices().map(|(i, _)| i).chain(once(word.len()))
+}
+
+/// Affix rules by the text they add, without the group headers
+fn index_affixes(affixes: Vec<Affix>) -> HashMap<String, Vec<Affix>> {
+    let mut index: HashMap<String, Vec<Affix>> = HashMap::new();
+    for affix in affixes.into_iter().filter(|a| !a.condition.is_empty()) {
+        index.entry(affix.add.clone()).or_default().push(affix);
+    }
+    index
+}
+
+fn split_flags(flags: &str, flag_type: FlagType) -> Vec<String> {
+    match flag_type {
+        FlagType::Char => flags.chars().map(String::from).collect(),
+        FlagType::Long => flags
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|pair| pair.iter().collect())
+            .collect(),
+        FlagType::Num => flags.split(',').map(|f| f.trim().to_string()).collect(),
+    }
+}
+
+/// The characters of a condition like `[^aeiou]y`
+fn parse_condition(text: &str) -> Vec<Condition> {
+    let mut condition = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        condition.push(match c {
+            '.' => Condition::Any,
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|c| *c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                Condition::Set(negated, set)
+            }
+            c => Condition::Char(c),
+        });
+    }
+    condition
+}
+
+/// Root of `word` if it ends with the suffix, and the root meets its condition
+fn strip_suffix(word: &str, suffix: &Affix) -> Option<String> {
+    let stem = word.strip_suffix(suffix.add.as_str())?;
+    if stem.is_empty() {
+        return None;
+    }
+    let root = format!("{stem}{}", suffix.strip);
+    let chars: Vec<char> = root.chars().collect();
+    let tail = chars.len().checked_sub(suffix.condition.len())?;
+    let meets = suffix
+        .condition
+        .iter()
+        .zip(&chars[tail..])
+        .all(|(condition, c)| condition.matches(*c));
+    meets.then_some(root)
+}
+
+/// Root of `word` if it starts with the prefix, and the root meets its condition
+fn strip_prefix(word: &str, prefix: &Affix) -> Option<String> {
+    let stem = word.strip_prefix(prefix.add.as_str())?;
+    if stem.is_empty() {
+        return None;
+    }
+    let root = format!("{}{stem}", prefix.strip);
+    let meets = root.chars().count() >= prefix.condition.len()
+        && prefix
+            .condition
+            .iter()
+            .zip(root.chars())
+            .all(|(condition, c)| condition.matches(c));
+    meets.then_some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const AFF: &str = "SET UTF-8
+TRY esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'
+
+PFX A Y 1
+PFX A   0     re         .
+
+SFX D Y 4
+SFX D   0     d          e
+SFX D   y     ied        [^aeiou]y
+SFX D   0     ed         [^ey]
+SFX D   0     ed         [aeiou]y
+
+SFX S Y 2
+SFX S   y     ies        [^aeiou]y
+SFX S   0     s          [aeiou]y
+";
+
+    const DIC: &str = "5
+create/ADS
+try/ADS
+play/ADS
+don't
+Paris
+";
+
+    #[test]
+    fn test_spell_check() {
+        let dictionary = Dictionary::parse(AFF, DIC);
+        for word in &[
+            "create",
+            "created",
+            "recreated",
+            "tried",
+            "retries",
+            "played",
+            "plays",
+            "Tried",
+            "TRIED",
+            "don't",
+            "Paris",
+            "PARIS",
+        ] {
+            assert!(dictionary.check(word), "{}", word);
+        }
+        for word in &["createed", "tryed", "plaied", "paris", "reParis", "dont"] {
+            assert!(!dictionary.check(word), "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_spell_misspelled() {
+        let dictionary = Dictionary::parse(AFF, DIC);
+        let line = "Créated, tried (don\u{2019}t) plaied 42nd me@example.com";
+        assert_eq!(dictionary.misspelled(line), vec![0..7, 23..29]);
+        assert_eq!(
+            dictionary.misspelled("> plaied"),
+            Vec::<Range<usize>>::new()
+        );
+        assert!(Dictionary::default().misspelled("plaied").is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+//! ## Threading
+//!
+//! `threading` groups mails into conversations with the JWZ algorithm
+//! (<https://www.jwz.org/doc/threading.html>) and draws them as trees
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+/// What the threading needs to know about a mail
+pub struct ThreadMessage<'a> {
+    pub message_id: Option<&'a str>,
+    /// References, with In-Reply-To appended when it is not the last one
+    pub references: Vec<&'a str>,
+    pub subject: &'a str,
+    pub date: i64,
+}
+
+/// A mail with its replies, `index` being the position of the mail in the input
+#[derive(Debug, PartialEq, Eq)]
+pub struct ThreadNode {
+    pub index: usize,
+    pub children: Vec<Self>,
+}
+
+/// A line of the threaded mail list
+#[derive(Debug, PartialEq, Eq)]
+pub struct ThreadRow {
+    pub index: usize,
+    /// tree connectors drawn before the subject
+    pub prefix: String,
+    /// first mail of the thread
+    pub root: usize,
+    /// every mail of the thread when this row is a collapsed thread
+    pub collapsed: Option<Vec<usize>>,
+}
+
+#[derive(Default)]
+struct Container {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Intermediate tree, `message` is `None` for mails which are referenced but not here
+struct Node {
+    message: Option<usize>,
+    children: Vec<Self>,
+}
+
+/// ### `normalize_subject`
+///
+/// Strip `Re:`, `Fwd:` and similar prefixes, returns whether there was one
+pub fn normalize_subject(subject: &str) -> (&str, bool) {
+    let mut rest = subject.trim();
+    let mut reply = false;
+    loop {
+        let lower = rest.to_lowercase();
+        let prefix_len = ["re", "fwd", "fw", "aw", "sv", "wg"]
+            .iter()
+            .find(|p| lower.starts_with(*p))
+            .map(|p| p.len());
+        let Some(mut len) = prefix_len else {
+            break;
+        };
+        // "Re[2]:" or "Re(2):"
+        if let Some(close) = match rest[len..].chars().next() {
+            Some('[') => Some(']'),
+            Some('(') => Some(')'),
+            _ => None,
+        } {
+            match rest[len..].find(close) {
+                Some(pos) if rest[len + 1..len + pos].chars().all(|c| c.is_ascii_digit()) => {
+                    len += pos + 1;
+                }
+                _ => break,
+            }
+        }
+        match rest[len..].strip_prefix(':') {
+            Some(stripped) => {
+                rest = stripped.trim_start();
+                reply = true;
+            }
+            None => break,
+        }
+    }
+    (rest, reply)
+}
+
+/// ### `thread`
+///
+/// Group `messages` into threads. Threads are sorted by their latest mail, newest first,
+/// and replies by date.
+pub fn thread(messages: &[ThreadMessage]) -> Vec<ThreadNode> {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut id_table: HashMap<&str, usize> = HashMap::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        // 1A: find or create the container of this message
+        let this = match message.message_id.and_then(|id| id_table.get(id).copied()) {
+            Some(c) if containers[c].message.is_none() => c,
+            _ => {
+                containers.push(Container::default());
+                let c = containers.len() - 1;
+                if let Some(id) = message.message_id {
+                    id_table.entry(id).or_insert(c);
+                }
+                c
+            }
+        };
+        containers[this].message = Some(index);
+
+        // 1B: link the references together, parent first
+        let mut previous: Option<usize> = None;
+        for reference in &message.references {
+            let c = *id_table.entry(reference).or_insert_with(|| {
+                containers.push(Container::default());
+                containers.len() - 1
+            });
+            if let Some(parent) = previous {
+                if containers[c].parent.is_none()
+                    && c != parent
+                    && !is_ancestor(&containers, c, parent)
+                {
+                    link(&mut containers, parent, c);
+                }
+            }
+            previous = Some(c);
+        }
+
+        // 1C: the last reference is the parent of this message
+        if let Some(old) = containers[this].parent {
+            containers[old].children.retain(|c| *c != this);
+            containers[this].parent = None;
+        }
+        if let Some(parent) = previous {
+            if parent != this && !is_ancestor(&containers, this, parent) {
+                link(&mut containers, parent, this);
+            }
+        }
+    }
+
+    // 2, 3, 4: find the roots and drop empty containers
+    let mut roots: Vec<Node> = Vec::new();
+    for (c, container) in containers.iter().enumerate() {
+        if container.parent.is_none() {
+            roots.extend(prune(&containers, c, true));
+        }
+    }
+
+    // 5: gather replies whose references are lost by subject
+    let roots = group_by_subject(roots, messages);
+
+    // the first mail of a thread without root takes the others as replies
+    let mut threads: Vec<ThreadNode> = roots
+        .into_iter()
+        .filter_map(|node| into_thread_node(node, messages))
+        .collect();
+    for node in &mut threads {
+        sort_children(node, messages);
+    }
+    threads.sort_by_key(|node| std::cmp::Reverse(latest(node, messages)));
+    threads
+}
+
+fn link(containers: &mut [Container], parent: usize, child: usize) {
+    containers[child].parent = Some(parent);
+    containers[parent].children.push(child);
+}
+
+/// Whether `ancestor` is `node` or one of its parents
+fn is_ancestor(containers: &[Container], ancestor: usize, node: usize) -> bool {
+    let mut current = Some(node);
+    while let Some(c) = current {
+        if c == ancestor {
+            return true;
+        }
+        current = containers[c].parent;
+    }
+    false
+}
+
+fn prune(containers: &[Container], c: usize, root: bool) -> Vec<Node> {
+    let children: Vec<Node> = containers[c]
+        .children
+        .iter()
+        .flat_map(|child| prune(containers, *child, false))
+        .collect();
+    match containers[c].message {
+        Some(message) => vec![Node {
+            message: Some(message),
+            children,
+        }],
+        // promote the children, but keep an empty root with several children together
+        None if root && children.len() > 1 => vec![Node {
+            message: None,
+            children,
+        }],
+        None => children,
+    }
+}
+
+fn group_by_subject(roots: Vec<Node>, messages: &[ThreadMessage]) -> Vec<Node> {
+    let subject = |node: &Node| {
+        let message = node
+            .message
+            .or_else(|| node.children.first().and_then(|c| c.message))?;
+        let (subject, reply) = normalize_subject(messages[message].subject);
+        (!subject.is_empty()).then(|| (subject.to_string(), reply && node.message.is_some()))
+    };
+
+    // threads started without Re:, which replies can join
+    let mut table: HashMap<String, usize> = HashMap::new();
+    for (i, node) in roots.iter().enumerate() {
+        if let Some((subject, false)) = subject(node) {
+            table.entry(subject).or_insert(i);
+        }
+    }
+
+    let mut moves: Vec<(usize, usize)> = Vec::new();
+    for (i, node) in roots.iter().enumerate() {
+        if let Some((subject, true)) = subject(node) {
+            if let Some(&target) = table.get(&subject) {
+                if target != i {
+                    moves.push((i, target));
+                }
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<Node>> = roots.into_iter().map(Some).collect();
+    for (from, to) in moves {
+        if let Some(node) = slots[from].take() {
+            if let Some(target) = slots[to].as_mut() {
+                target.children.push(node);
+            }
+        }
+    }
+    slots.into_iter().flatten().collect()
+}
+
+fn into_thread_node(node: Node, messages: &[ThreadMessage]) -> Option<ThreadNode> {
+    let mut children: Vec<ThreadNode> = node
+        .children
+        .into_iter()
+        .filter_map(|child| into_thread_node(child, messages))
+        .collect();
+    if let Some(index) = node.message {
+        return Some(ThreadNode { index, children });
+    }
+    if children.is_empty() {
+        return None;
+    }
+    children.sort_by_key(|c| messages[c.index].date);
+    let mut first = children.remove(0);
+    first.children.append(&mut children);
+    Some(first)
+}
+
+fn sort_children(node: &mut ThreadNode, messages: &[ThreadMessage]) {
+    node.children.sort_by_key(|c| messages[c.index].date);
+    for child in &mut node.children {
+        sort_children(child, messages);
+    }
+}
+
+fn latest(node: &ThreadNode, messages: &[ThreadMessage]) -> i64 {
+    node.children
+        .iter()
+        .map(|c| latest(c, messages))
+        .fold(messages[node.index].date, i64::max)
+}
+
+impl ThreadNode {
+    /// ### `indexes`
+    ///
+    /// This mail and all its replies
+    pub fn indexes(&self) -> Vec<usize> {
+        let mut indexes = vec![self.index];
+        for child in &self.children {
+            indexes.extend(child.indexes());
+        }
+        indexes
+    }
+}
+
+/// ### `filter`
+///
+/// Keep the mails for which `keep` is true, replies of a dropped mail move up to its place
+pub fn filter(threads: &[ThreadNode], keep: &dyn Fn(usize) -> bool) -> Vec<ThreadNode> {
+    threads
+        .iter()
+        .flat_map(|thread| {
+            let mut nodes = filter_node(thread, keep);
+            // the first remaining mail takes the others of the thread as replies
+            if nodes.len() > 1 {
+                let mut first = nodes.remove(0);
+                first.children.append(&mut nodes);
+                nodes.push(first);
+            }
+            nodes
+        })
+        .collect()
+}
+
+fn filter_node(node: &ThreadNode, keep: &dyn Fn(usize) -> bool) -> Vec<ThreadNode> {
+    let children: Vec<ThreadNode> = node
+        .children
+        .iter()
+        .flat_map(|child| filter_node(child, keep))
+        .collect();
+    if keep(node.index) {
+        vec![ThreadNode {
+            index: node.index,
+            children,
+        }]
+    } else {
+        children
+    }
+}
+
+/// ### `flatten`
+///
+/// Lines of the mail list for `threads`, with tree connectors.
+/// Threads whose first mail is `collapsed` are shown as a single line.
+pub fn flatten(threads: &[ThreadNode], collapsed: &dyn Fn(usize) -> bool) -> Vec<ThreadRow> {
+    let mut rows = Vec::new();
+    for thread in threads {
+        if collapsed(thread.index) && !thread.children.is_empty() {
+            rows.push(ThreadRow {
+                index: thread.index,
+                prefix: String::new(),
+                root: thread.index,
+                collapsed: Some(thread.indexes()),
+            });
+        } else {
+            flatten_node(thread, thread.index, "", None, &mut rows);
+        }
+    }
+    rows
+}
+
+fn flatten_node(
+    node: &ThreadNode,
+    root: usize,
+    indent: &str,
+    last: Option<bool>,
+    rows: &mut Vec<ThreadRow>,
+) {
+    let (prefix, child_indent) = match last {
+        None => (String::new(), String::new()),
+        Some(true) => (format!("{indent}└─"), format!("{indent}  ")),
+        Some(false) => (format!("{indent}├─"), format!("{indent}│ ")),
+    };
+    rows.push(ThreadRow {
+        index: node.index,
+        prefix,
+        root,
+        collapsed: None,
+    });
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        flatten_node(child, root, &child_indent, Some(last), rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn message<'a>(
+        id: &'a str,
+        references: &[&'a str],
+        subject: &'a str,
+        date: i64,
+    ) -> ThreadMessage<'a> {
+        ThreadMessage {
+            message_id: Some(id),
+            references: references.to_vec(),
+            subject,
+            date,
+        }
+    }
+
+    #[test]
+    fn test_threading_normalize_subject() {
+        assert_eq!(normalize_subject("Re: RE: Fwd: hello"), ("hello", true));
+        assert_eq!(normalize_subject("Re[2]: hello"), ("hello", true));
+        assert_eq!(normalize_subject("Reply needed"), ("Reply needed", false));
+        assert_eq!(normalize_subject("hello"), ("hello", false));
+    }
+
+    #[test]
+    fn test_threading_thread() {
+        let messages = vec![
+            message("a", &[], "hello", 1),
+            message("c", &["a", "b"], "Re: hello", 4),
+            message("b", &["a"], "Re: hello", 2),
+            message("d", &["a"], "Re: hello", 3),
+            message("e", &[], "other", 5),
+            // parent is missing, found by subject
+            message("f", &["zz"], "Re: other", 6),
+            // both replies to a missing mail
+            message("g", &["x"], "Re: third", 0),
+            message("h", &["x"], "Re: third", 1),
+        ];
+        let threads = thread(&messages);
+        let roots: Vec<usize> = threads.iter().map(|t| t.index).collect();
+        assert_eq!(roots, vec![4, 0, 6]);
+        assert_eq!(threads[0].indexes(), vec![4, 5]);
+        assert_eq!(threads[1].indexes(), vec![0, 2, 1, 3]);
+        assert_eq!(threads[2].indexes(), vec![6, 7]);
+
+        let rows = flatten(&threads, &|index| index == 4);
+        let prefixes: Vec<(usize, &str)> =
+            rows.iter().map(|r| (r.index, r.prefix.as_str())).collect();
+        assert_eq!(
+            prefixes,
+            vec![
+                (4, ""),
+                (0, ""),
+                (2, "├─"),
+                (1, "│ └─"),
+                (3, "└─"),
+                (6, ""),
+                (7, "└─")
+            ]
+        );
+        assert_eq!(rows[0].collapsed, Some(vec![4, 5]));
+        assert_eq!(rows[3].root, 0);
+
+        // without the first mail, its replies form the thread
+        let filtered = filter(&threads, &|index| index != 0 && index != 5);
+        assert_eq!(filtered[1].indexes(), vec![2, 1, 3]);
+        assert_eq!(filtered[1].children[1].index, 3);
+    }
+}
@@ -0,0 +1,260 @@
+//! ## Transport
+//!
+//! `transport` hands finished mails to the SMTP server or the sendmail command of an account
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::config::AccountConfig;
+use crate::smtp;
+use anyhow::{anyhow, Result};
+use maildir::Maildir;
+use mailparse::{addrparse, parse_headers, MailAddr, MailHeaderMap};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// ### `send`
+///
+/// Send `message` with the transport of `account`
+pub fn send(
+    account: &AccountConfig,
+    from: &str,
+    recipients: &[String],
+    message: &[u8],
+) -> Result<()> {
+    if let Some(command) = &account.sendmail_command {
+        return sendmail(command, recipients, message);
+    }
+    let server = account
+        .smtp
+        .as_ref()
+        .ok_or_else(|| anyhow!("Account {} has no way to send mail", account.name))?;
+    if let (Some(user), None) = (&server.user, &server.password_command) {
+        return Err(anyhow!(
+            "Account {} logs in as {user} but has no password_command",
+            account.name
+        ));
+    }
+    smtp::send(server, from, recipients, message)
+}
+
+/// ### `sendmail`
+///
+/// Pipe `message` to a sendmail compatible command, run as `<command> -i -- <recipients>` so
+/// that blind copies, which are not in the headers, are sent too. A command reading the
+/// recipients from the headers itself, with `-t` or `--read-recipients`, only gets the
+/// message, with a Bcc header for the blind copies, which it removes before sending.
+pub fn sendmail(command: &str, recipients: &[String], message: &[u8]) -> Result<()> {
+    let read_recipients = command
+        .split_whitespace()
+        .any(|arg| matches!(arg, "-t" | "-ti" | "-it" | "--read-recipients"));
+    let (script, args, message) = if read_recipients {
+        (command.to_string(), &[][..], with_bcc(recipients, message))
+    } else {
+        // the recipients reach the command as arguments, never through the shell
+        (
+            format!("{command} -i -- \"$@\""),
+            recipients,
+            message.to_vec(),
+        )
+    };
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .arg("sh")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // written from a thread, so that a command writing much to stderr before reading
+    // can't block on a full pipe. A command failing early closes its input, its exit
+    // status tells why.
+    let writer = child
+        .stdin
+        .take()
+        .map(|mut stdin| thread::spawn(move || stdin.write_all(&message).ok()));
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        writer.join().ok();
+    }
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(anyhow!(
+        "{command} exited with {}: {}",
+        output.status,
+        stderr.trim()
+    ))
+}
+
+/// The message with a Bcc header, put first, for the `recipients` which are not in its
+/// To or Cc headers
+fn with_bcc(recipients: &[String], message: &[u8]) -> Vec<u8> {
+    let mut shown: Vec<String> = Vec::new();
+    if let Ok((headers, _)) = parse_headers(message) {
+        for header in &["To", "Cc"] {
+            for value in headers.get_all_values(header) {
+                for addr in addrparse(&value).iter().flat_map(|list| list.iter()) {
+                    match addr {
+                        MailAddr::Single(info) => shown.push(info.addr.to_lowercase()),
+                        MailAddr::Group(group) => {
+                            shown.extend(group.addrs.iter().map(|i| i.addr.to_lowercase()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let blind: Vec<&str> = recipients
+        .iter()
+        .filter(|r| !shown.contains(&r.to_lowercase()))
+        .map(String::as_str)
+        .collect();
+    if blind.is_empty() {
+        return message.to_vec();
+    }
+    let mut result = format!("Bcc: {}\r\n", blind.join(", ")).into_bytes();
+    result.extend_from_slice(message);
+    result
+}
+
+/// ### `save_sent`
+///
+/// Keep a copy of a sent mail, as it was sent, in the Sent maildir `folder`
+pub fn save_sent(folder: &Path, message: &[u8]) -> Result<()> {
+    let sent = Maildir::from(folder.to_path_buf());
+    sent.create_dirs()?;
+    sent.store_cur_with_flags(message, "S")
+        .map_err(|e| anyhow!("Failed to save to {}: {e}", folder.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compose::Draft;
+    use crate::config::{SmtpAuth, SmtpConfig, SmtpSecurity};
+    use chrono::Local;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_sendmail() {
+        let dir = std::env::temp_dir().join(format!("termail-sendmail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // records its arguments and the mail
+        let command = format!(
+            "cd '{}' && sh -c 'printf \"%s\\n\" \"$@\" > args; cat > mail' sendmail",
+            dir.display()
+        );
+        let draft = Draft {
+            from: "me@example.com".to_string(),
+            to: "you@example.com".to_string(),
+            bcc: "Boss <boss@example.com>".to_string(),
+            subject: "hi".to_string(),
+            body: "body\n".to_string(),
+            ..Draft::default()
+        };
+        let message = draft.to_message(Local::now());
+        sendmail(&command, &draft.recipients().unwrap(), &message).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("args")).unwrap(),
+            "-i\n--\nyou@example.com\nboss@example.com\n"
+        );
+        let sent = fs::read_to_string(dir.join("mail")).unwrap();
+        assert_eq!(sent.as_bytes(), message);
+        assert!(!sent.contains("boss@example.com"));
+        fs::remove_dir_all(&dir).ok();
+
+        // reads the recipients from the headers, and gets the blind copies there
+        let dir = std::env::temp_dir().join(format!("termail-sendmail-t-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let command = format!(
+            "cd '{}' && sh -c 'printf \"%s\\n\" \"$@\" > args; cat > mail' sendmail --read-recipients",
+            dir.display()
+        );
+        sendmail(&command, &draft.recipients().unwrap(), &message).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("args")).unwrap(),
+            "--read-recipients\n"
+        );
+        let mut expected = b"Bcc: boss@example.com\r\n".to_vec();
+        expected.extend_from_slice(&message);
+        assert_eq!(fs::read(dir.join("mail")).unwrap(), expected);
+        fs::remove_dir_all(&dir).ok();
+
+        let command = "sh -c \"echo 'no account' >&2; exit 78\"";
+        let error = sendmail(command, &[], b"").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{command} exited with exit status: 78: no account")
+        );
+    }
+
+    #[test]
+    fn test_send_without_password() {
+        let account = AccountConfig {
+            name: "work".to_string(),
+            address: "me@example.com".to_string(),
+            folder: String::new(),
+            sendmail_command: None,
+            save_sent: false,
+            sent: None,
+            smtp: Some(SmtpConfig {
+                host: "127.0.0.1".to_string(),
+                port: Some(1),
+                security: SmtpSecurity::None,
+                user: Some("me".to_string()),
+                auth: SmtpAuth::Plain,
+                password_command: None,
+                allow_plaintext_auth: true,
+            }),
+        };
+        let error = send(&account, "me@example.com", &[], b"").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Account work logs in as me but has no password_command"
+        );
+    }
+
+    #[test]
+    fn test_save_sent() {
+        let dir = std::env::temp_dir().join(format!("termail-sent-{}", std::process::id()));
+        let folder = dir.join("Sent");
+        let message = b"Subject: caf\xe9\r\n\r\nbody\r\n";
+        save_sent(&folder, message).unwrap();
+        assert_eq!(fs::read_dir(folder.join("new")).unwrap().count(), 0);
+        let saved: Vec<_> = fs::read_dir(folder.join("cur"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].to_string_lossy().ends_with(":2,S"));
+        assert_eq!(fs::read(&saved[0]).unwrap(), message);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
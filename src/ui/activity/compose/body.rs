@@ -0,0 +1,250 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::buffer::TextBuffer;
+use crate::spell::Dictionary;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::sync::Arc;
+use tuirealm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tuirealm::props::Props;
+use tuirealm::tui::layout::Rect;
+use tuirealm::tui::style::{Color, Modifier, Style};
+use tuirealm::tui::text::{Span, Spans};
+use tuirealm::tui::widgets::{Block, Paragraph};
+use tuirealm::{Component, Frame, Msg, Payload, Value};
+use unicode_width::UnicodeWidthChar;
+
+/// ## `BodyEditor`
+///
+/// Multi-line editor of the mail text, underlining the words the dictionary does not know
+pub struct BodyEditor {
+    props: Props,
+    buffer: TextBuffer,
+    dictionary: Arc<Dictionary>,
+    focus: bool,
+    // first line on screen, and lines that fit, as last rendered
+    top: Cell<usize>,
+    height: Cell<usize>,
+    // misspelled words of the lines last rendered, by their text
+    misspelled: RefCell<HashMap<String, Vec<Range<usize>>>>,
+}
+
+impl BodyEditor {
+    pub fn new(props: Props, text: &str, dictionary: Arc<Dictionary>) -> Self {
+        Self {
+            props,
+            buffer: TextBuffer::new(text),
+            dictionary,
+            focus: false,
+            top: Cell::new(0),
+            height: Cell::new(10),
+            misspelled: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Styled characters of a line from the column `left`, `misspelled` words underlined in red
+    fn line_spans(&self, row: usize, left: usize, misspelled: &[Range<usize>]) -> Spans<'static> {
+        let line = &self.buffer.lines()[row];
+        let (cursor_row, cursor_col) = self.buffer.cursor();
+        // the word being typed is not finished yet
+        let misspelled: Vec<&Range<usize>> = misspelled
+            .iter()
+            .filter(|range| !(row == cursor_row && range.end == cursor_col))
+            .collect();
+        let normal = Style::default().fg(self.props.foreground);
+        let wrong = Style::default()
+            .fg(Color::LightRed)
+            .add_modifier(Modifier::UNDERLINED);
+        let mut spans = Vec::new();
+        let mut run = String::new();
+        let mut run_wrong = false;
+        for (i, c) in line.iter().enumerate().skip(left) {
+            let is_wrong = misspelled.iter().any(|range| range.contains(&i));
+            if is_wrong != run_wrong && !run.is_empty() {
+                let style = if run_wrong { wrong } else { normal };
+                spans.push(Span::styled(std::mem::take(&mut run), style));
+            }
+            run_wrong = is_wrong;
+            run.push(*c);
+        }
+        if !run.is_empty() {
+            spans.push(Span::styled(run, if run_wrong { wrong } else { normal }));
+        }
+        Spans::from(spans)
+    }
+}
+
+impl Component for BodyEditor {
+    fn render(&self, render: &mut Frame, area: Rect) {
+        if !self.props.visible {
+            return;
+        }
+        let mut block = Block::default()
+            .borders(self.props.borders.borders)
+            .border_type(self.props.borders.variant)
+            .border_style(if self.focus {
+                self.props.borders.style()
+            } else {
+                Style::default()
+            });
+        if let Some(title) = &self.props.title {
+            block = block
+                .title(title.text().to_string())
+                .title_alignment(title.alignment());
+        }
+        let inner = block.inner(area);
+        let height = usize::from(inner.height).max(1);
+        let width = usize::from(inner.width).max(1);
+        self.height.set(height);
+
+        // scroll so that the cursor stays on screen
+        let (row, col) = self.buffer.cursor();
+        let mut top = self.top.get();
+        if row < top {
+            top = row;
+        } else if row >= top + height {
+            top = row + 1 - height;
+        }
+        self.top.set(top);
+        // lines scroll sideways together, as far as the cursor needs
+        let cursor_line = &self.buffer.lines()[row];
+        let mut left = 0;
+        let mut x: usize = cursor_line[..col]
+            .iter()
+            .map(|c| c.width().unwrap_or(0))
+            .sum();
+        while x >= width {
+            x -= cursor_line[left].width().unwrap_or(0);
+            left += 1;
+        }
+
+        // only the lines that changed are checked again
+        let mut checked = self.misspelled.take();
+        let mut misspelled = HashMap::new();
+        let lines: Vec<Spans> = (top..self.buffer.lines().len().min(top + height))
+            .map(|row| {
+                let text: String = self.buffer.lines()[row].iter().collect();
+                let ranges = misspelled.entry(text).or_insert_with_key(|text| {
+                    checked
+                        .remove(text)
+                        .unwrap_or_else(|| self.dictionary.misspelled(text))
+                });
+                self.line_spans(row, left, ranges)
+            })
+            .collect();
+        self.misspelled.replace(misspelled);
+        render.render_widget(
+            Paragraph::new(lines).block(block).style(
+                Style::default()
+                    .fg(self.props.foreground)
+                    .bg(self.props.background),
+            ),
+            area,
+        );
+        if self.focus {
+            #[allow(clippy::cast_possible_truncation)]
+            render.set_cursor(inner.x + x as u16, inner.y + (row - top) as u16);
+        }
+    }
+
+    fn update(&mut self, props: Props) -> Msg {
+        self.props = props;
+        Msg::None
+    }
+
+    fn get_props(&self) -> Props {
+        self.props.clone()
+    }
+
+    fn on(&mut self, ev: Event) -> Msg {
+        let Event::Key(key) = ev else {
+            return Msg::None;
+        };
+        let page = isize::try_from(self.height.get()).unwrap_or(10);
+        let buffer = &mut self.buffer;
+        match key {
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+            } => buffer.insert(c),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::CONTROL,
+            } => match c {
+                'a' => buffer.home(),
+                'e' => buffer.end(),
+                'k' => buffer.kill_line(),
+                'w' => buffer.delete_word(),
+                'z' => {
+                    buffer.undo();
+                }
+                'y' => {
+                    buffer.redo();
+                }
+                _ => return Msg::OnKey(key),
+            },
+            KeyEvent {
+                code,
+                modifiers: KeyModifiers::CONTROL,
+            } => match code {
+                KeyCode::Left => buffer.word_left(),
+                KeyCode::Right => buffer.word_right(),
+                KeyCode::Home => buffer.top(),
+                KeyCode::End => buffer.bottom(),
+                KeyCode::Backspace => buffer.delete_word(),
+                _ => return Msg::OnKey(key),
+            },
+            KeyEvent { code, .. } => match code {
+                KeyCode::Enter => buffer.newline(),
+                KeyCode::Backspace => buffer.backspace(),
+                KeyCode::Delete => buffer.delete(),
+                KeyCode::Left => buffer.left(),
+                KeyCode::Right => buffer.right(),
+                KeyCode::Up => buffer.vertical(-1),
+                KeyCode::Down => buffer.vertical(1),
+                KeyCode::PageUp => buffer.vertical(-page),
+                KeyCode::PageDown => buffer.vertical(page),
+                KeyCode::Home => buffer.home(),
+                KeyCode::End => buffer.end(),
+                _ => return Msg::OnKey(key),
+            },
+        }
+        Msg::None
+    }
+
+    /// The text
+    fn get_state(&self) -> Payload {
+        Payload::One(Value::Str(self.buffer.text()))
+    }
+
+    fn blur(&mut self) {
+        self.focus = false;
+    }
+
+    fn active(&mut self) {
+        self.focus = true;
+    }
+}
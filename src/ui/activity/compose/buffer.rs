@@ -0,0 +1,397 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+// most undo steps kept
+const MAX_UNDO: usize = 200;
+
+/// Kind of the last change, so that typing a word is undone at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Insert,
+    Delete,
+}
+
+/// Text with the cursor where it was before a change
+#[derive(Clone, Debug)]
+struct Snapshot {
+    lines: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+}
+
+/// Lines of text being edited, with a cursor and undo history
+#[derive(Clone, Debug)]
+pub struct TextBuffer {
+    lines: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    // column to go back to when moving up and down through shorter lines
+    goal: Option<usize>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_change: Option<Change>,
+}
+
+impl TextBuffer {
+    pub fn new(text: &str) -> Self {
+        let mut lines: Vec<Vec<char>> = text.split('\n').map(|l| l.chars().collect()).collect();
+        // the newline ending the text makes no line of its own
+        if lines.len() > 1 && lines.last().is_some_and(Vec::is_empty) {
+            lines.pop();
+        }
+        Self {
+            lines,
+            row: 0,
+            col: 0,
+            goal: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_change: None,
+        }
+    }
+
+    /// ### `text`
+    ///
+    /// The lines, each ending with a newline
+    pub fn text(&self) -> String {
+        let mut text: String = self
+            .lines
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn lines(&self) -> &[Vec<char>] {
+        &self.lines
+    }
+
+    /// Row and column of the cursor, in characters
+    pub const fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Remember the text before a change, merging it into the last undo step when it is more of
+    /// the same typing
+    fn record(&mut self, change: Option<Change>) {
+        if change.is_none() || change != self.last_change {
+            self.undo.push(Snapshot {
+                lines: self.lines.clone(),
+                row: self.row,
+                col: self.col,
+            });
+            if self.undo.len() > MAX_UNDO {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_change = change;
+        self.goal = None;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        // a word typed after a space is a step of its own
+        let change = if c.is_whitespace() {
+            None
+        } else {
+            Some(Change::Insert)
+        };
+        self.record(change);
+        self.lines[self.row].insert(self.col, c);
+        self.col += 1;
+    }
+
+    pub fn newline(&mut self) {
+        self.record(None);
+        let rest = self.lines[self.row].split_off(self.col);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.record(Some(Change::Delete));
+            self.col -= 1;
+            self.lines[self.row].remove(self.col);
+        } else if self.row > 0 {
+            self.record(None);
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.lines[self.row].len();
+            self.lines[self.row].extend(line);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.col < self.lines[self.row].len() {
+            self.record(Some(Change::Delete));
+            self.lines[self.row].remove(self.col);
+        } else if self.row + 1 < self.lines.len() {
+            self.record(None);
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(next);
+        }
+    }
+
+    /// ### `kill_line`
+    ///
+    /// Delete to the end of the line, or join the next line when at the end
+    pub fn kill_line(&mut self) {
+        if self.col < self.lines[self.row].len() {
+            self.record(None);
+            self.lines[self.row].truncate(self.col);
+        } else {
+            self.delete();
+        }
+    }
+
+    /// ### `delete_word`
+    ///
+    /// Delete the word before the cursor, with the spaces after it
+    pub fn delete_word(&mut self) {
+        let start = self.word_start();
+        if start == self.col {
+            self.backspace();
+            return;
+        }
+        self.record(None);
+        self.lines[self.row].drain(start..self.col);
+        self.col = start;
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.restore(true)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.restore(false)
+    }
+
+    fn restore(&mut self, undo: bool) -> bool {
+        let (from, to) = if undo {
+            (&mut self.undo, &mut self.redo)
+        } else {
+            (&mut self.redo, &mut self.undo)
+        };
+        let Some(snapshot) = from.pop() else {
+            return false;
+        };
+        to.push(Snapshot {
+            lines: std::mem::replace(&mut self.lines, snapshot.lines),
+            row: self.row,
+            col: self.col,
+        });
+        self.row = snapshot.row;
+        self.col = snapshot.col;
+        self.last_change = None;
+        self.goal = None;
+        true
+    }
+
+    /// Stop merging typing into the last undo step, as the cursor moved away
+    #[allow(clippy::missing_const_for_fn)]
+    fn moved(&mut self) {
+        self.last_change = None;
+    }
+
+    pub fn left(&mut self) {
+        self.moved();
+        self.goal = None;
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.lines[self.row].len();
+        }
+    }
+
+    pub fn right(&mut self) {
+        self.moved();
+        self.goal = None;
+        if self.col < self.lines[self.row].len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    /// Go `rows` lines up, or down when negative, keeping the column where it can
+    pub fn vertical(&mut self, rows: isize) {
+        self.moved();
+        let goal = *self.goal.get_or_insert(self.col);
+        self.row = self
+            .row
+            .saturating_add_signed(rows)
+            .min(self.lines.len() - 1);
+        self.col = goal.min(self.lines[self.row].len());
+    }
+
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn home(&mut self) {
+        self.moved();
+        self.goal = None;
+        self.col = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.moved();
+        self.goal = None;
+        self.col = self.lines[self.row].len();
+    }
+
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn top(&mut self) {
+        self.moved();
+        self.goal = None;
+        self.row = 0;
+        self.col = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.moved();
+        self.goal = None;
+        self.row = self.lines.len() - 1;
+        self.col = self.lines[self.row].len();
+    }
+
+    pub fn word_left(&mut self) {
+        if self.col == 0 {
+            self.left();
+            return;
+        }
+        self.moved();
+        self.goal = None;
+        self.col = self.word_start();
+    }
+
+    pub fn word_right(&mut self) {
+        let line = &self.lines[self.row];
+        if self.col == line.len() {
+            self.right();
+            return;
+        }
+        let mut col = self.col;
+        while col < line.len() && !line[col].is_alphanumeric() {
+            col += 1;
+        }
+        while col < line.len() && line[col].is_alphanumeric() {
+            col += 1;
+        }
+        self.moved();
+        self.goal = None;
+        self.col = col;
+    }
+
+    /// Column where the word before the cursor starts
+    fn word_start(&self) -> usize {
+        let line = &self.lines[self.row];
+        let mut col = self.col;
+        while col > 0 && !line[col - 1].is_alphanumeric() {
+            col -= 1;
+        }
+        while col > 0 && line[col - 1].is_alphanumeric() {
+            col -= 1;
+        }
+        col
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn typed(buffer: &mut TextBuffer, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                buffer.newline();
+            } else {
+                buffer.insert(c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_buffer_editing() {
+        let mut buffer = TextBuffer::new("Hi\n\n-- \nJane\n");
+        assert_eq!(buffer.lines().len(), 4);
+        buffer.end();
+        typed(&mut buffer, " Bob,\nthanks");
+        assert_eq!(buffer.text(), "Hi Bob,\nthanks\n\n-- \nJane\n");
+        assert_eq!(buffer.cursor(), (1, 6));
+        buffer.delete_word();
+        assert_eq!(buffer.cursor(), (1, 0));
+        buffer.backspace();
+        assert_eq!(buffer.cursor(), (0, 7));
+        buffer.word_left();
+        buffer.kill_line();
+        assert_eq!(buffer.text(), "Hi \n\n-- \nJane\n");
+
+        // from the end of a long line to a short one and on, keeping the column
+        let mut buffer = TextBuffer::new("long line\nab\nlonger line");
+        buffer.end();
+        buffer.vertical(1);
+        assert_eq!(buffer.cursor(), (1, 2));
+        buffer.vertical(5);
+        assert_eq!(buffer.cursor(), (2, 9));
+        buffer.vertical(-10);
+        assert_eq!(buffer.cursor(), (0, 9));
+        buffer.delete();
+        assert_eq!(buffer.text(), "long lineab\nlonger line\n");
+        assert_eq!(TextBuffer::new("").text(), "");
+    }
+
+    #[test]
+    fn test_buffer_undo() {
+        let mut buffer = TextBuffer::new("");
+        typed(&mut buffer, "Dear Ann");
+        for _ in 0..3 {
+            buffer.backspace();
+        }
+        typed(&mut buffer, "Bob");
+        assert_eq!(buffer.text(), "Dear Bob\n");
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "Dear \n");
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "Dear Ann\n");
+        assert!(buffer.undo());
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "Dear\n");
+        assert!(buffer.redo());
+        assert_eq!(buffer.text(), "Dear \n");
+        assert_eq!(buffer.cursor(), (0, 5));
+        buffer.undo();
+        buffer.undo();
+        assert!(!buffer.undo());
+        assert_eq!(buffer.text(), "");
+        // a change drops what could be redone
+        buffer.insert('x');
+        assert!(!buffer.redo());
+    }
+}
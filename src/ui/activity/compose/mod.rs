@@ -0,0 +1,206 @@
+//! ## `ComposeActivity`
+//!
+//! `compose_activity` is the module which implements the Compose activity, the built-in editor
+//! mails are written in when `$EDITOR` is not wanted
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+mod body;
+mod buffer;
+mod update;
+mod view;
+use super::{Activity, Context, ExitReason};
+use crate::addressbook::{AddressBook, Completion};
+use crate::compose::Draft;
+use crate::config::TermailConfig;
+use crate::spell::Dictionary;
+use crossterm::terminal::enable_raw_mode;
+use log::error;
+use std::path::Path;
+use std::sync::Arc;
+use tuirealm::{Payload, Value, View};
+
+// -- components
+const COMPONENT_INPUT_TO: &str = "INPUT_TO";
+const COMPONENT_INPUT_CC: &str = "INPUT_CC";
+const COMPONENT_INPUT_BCC: &str = "INPUT_BCC";
+const COMPONENT_INPUT_SUBJECT: &str = "INPUT_SUBJECT";
+const COMPONENT_EDITOR_BODY: &str = "EDITOR_BODY";
+const COMPONENT_TABLE_ATTACHMENTS: &str = "TABLE_ATTACHMENTS";
+const COMPONENT_INPUT_ATTACH: &str = "INPUT_ATTACH";
+const COMPONENT_LABEL_HELP: &str = "LABEL_HELP";
+const COMPONENT_TEXT_ERROR: &str = "TEXT_ERROR";
+
+// fields in the order <TAB> goes through them
+const FIELDS: [&str; 6] = [
+    COMPONENT_INPUT_TO,
+    COMPONENT_INPUT_CC,
+    COMPONENT_INPUT_BCC,
+    COMPONENT_INPUT_SUBJECT,
+    COMPONENT_EDITOR_BODY,
+    COMPONENT_TABLE_ATTACHMENTS,
+];
+
+/// ## `ComposeActivity`
+///
+/// Compose activity states holder
+pub struct ComposeActivity {
+    exit_reason: Option<ExitReason>,
+    context: Option<Context>,
+    view: View,
+    redraw: bool,
+    draft: Draft,
+    dictionary: Arc<Dictionary>,
+    // name of the dictionary in the help line, or why there is none
+    dictionary_status: String,
+    address_book: AddressBook,
+    // tab completion going on in a recipient field, and the field
+    completion: Option<(&'static str, Completion)>,
+    // field with the focus, to go back to after a popup
+    focus: &'static str,
+}
+
+impl ComposeActivity {
+    /// ### `new`
+    ///
+    /// Activity editing `draft`, checking the spelling with the dictionary of `config`
+    pub fn new(draft: Draft, config: &TermailConfig) -> Self {
+        let path = shellexpand::tilde(&config.compose.dictionary).to_string();
+        let (dictionary, dictionary_status) = if path.is_empty() {
+            (Dictionary::default(), "no spell checking".to_string())
+        } else {
+            match Dictionary::load(Path::new(&path)) {
+                Ok(dictionary) => {
+                    let name = Path::new(&path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    (dictionary, format!("spelling: {name}"))
+                }
+                Err(e) => {
+                    error!("{e}");
+                    (Dictionary::default(), format!("no spell checking: {e}"))
+                }
+            }
+        };
+        Self {
+            exit_reason: None,
+            context: None,
+            view: View::init(),
+            redraw: true,
+            draft,
+            dictionary: Arc::new(dictionary),
+            dictionary_status,
+            address_book: AddressBook::load().unwrap_or_else(|e| {
+                error!("{e}");
+                AddressBook::default()
+            }),
+            completion: None,
+            focus: COMPONENT_EDITOR_BODY,
+        }
+    }
+
+    /// ### `draft`
+    ///
+    /// The mail as written so far
+    pub fn draft(&self) -> Draft {
+        let value = |component: &str| match self.view.get_state(component) {
+            Some(Payload::One(Value::Str(value))) => value,
+            _ => String::new(),
+        };
+        let recipients = |component: &str| {
+            value(component)
+                .trim()
+                .trim_end_matches(',')
+                .trim()
+                .to_string()
+        };
+        Draft {
+            to: recipients(COMPONENT_INPUT_TO),
+            cc: recipients(COMPONENT_INPUT_CC),
+            bcc: recipients(COMPONENT_INPUT_BCC),
+            subject: value(COMPONENT_INPUT_SUBJECT).trim().to_string(),
+            body: value(COMPONENT_EDITOR_BODY),
+            ..self.draft.clone()
+        }
+    }
+}
+
+impl Activity for ComposeActivity {
+    /// ### `on_create`
+    ///
+    /// `on_create` is the function which must be called to initialize the activity.
+    /// `on_create` must initialize all the data structures used by the activity
+    /// Context is taken from activity manager and will be released only when activity is destroyed
+    fn on_create(&mut self, context: Context) {
+        self.context = Some(context);
+        if let Some(context) = self.context.as_mut() {
+            context.clear_screen();
+        }
+        if let Err(err) = enable_raw_mode() {
+            error!("Failed to enter raw mode: {err}");
+        }
+        self.init_setup();
+    }
+
+    /// ### `on_draw`
+    ///
+    /// `on_draw` is the function which draws the graphical interface.
+    /// This function must be called at each tick to refresh the interface
+    fn on_draw(&mut self) {
+        if self.context.is_none() {
+            return;
+        }
+        if let Ok(Some(event)) = crate::ui::inputhandler::InputHandler::read_event() {
+            self.redraw = true;
+            let msg = self.view.on(event);
+            self.update(msg.as_ref());
+        }
+        if self.redraw {
+            self.view();
+            self.redraw = false;
+        }
+    }
+
+    /// ### `will_umount`
+    ///
+    /// `will_umount` is the method which must be able to report to the activity manager, whether
+    /// the activity should be terminated or not.
+    /// If not, the call will return `None`, otherwise return`Some(ExitReason)`
+    fn will_umount(&self) -> Option<&ExitReason> {
+        self.exit_reason.as_ref()
+    }
+
+    /// ### `on_destroy`
+    ///
+    /// `on_destroy` is the function which cleans up runtime variables and data before terminating
+    /// the activity. This function must be called once before terminating the activity.
+    /// This function finally releases the context
+    fn on_destroy(&mut self) -> Option<Context> {
+        // raw mode stays on for the main activity, which takes the context back
+        let mut ctx = self.context.take()?;
+        ctx.clear_screen();
+        Some(ctx)
+    }
+}
@@ -0,0 +1,240 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{
+    ComposeActivity, ExitReason, COMPONENT_INPUT_ATTACH, COMPONENT_INPUT_BCC, COMPONENT_INPUT_CC,
+    COMPONENT_INPUT_SUBJECT, COMPONENT_INPUT_TO, COMPONENT_TABLE_ATTACHMENTS, COMPONENT_TEXT_ERROR,
+    FIELDS,
+};
+use crate::addressbook::Completion;
+use crate::mime::Attachment;
+use crate::ui::keymap::{
+    MSG_KEY_CHAR_A, MSG_KEY_CHAR_D, MSG_KEY_CTRL_S, MSG_KEY_DEL, MSG_KEY_DOWN, MSG_KEY_ENTER,
+    MSG_KEY_ESC, MSG_KEY_SHIFT_TAB, MSG_KEY_TAB, MSG_KEY_UP,
+};
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use std::path::Path;
+use tui_realm_stdlib::InputPropsBuilder;
+use tuirealm::tui::layout::Alignment;
+use tuirealm::{Msg, Payload, PropsBuilder, Value};
+
+impl ComposeActivity {
+    /// ### update
+    ///
+    /// Update compose activity model based on msg
+    /// The function exits when returns None
+    pub(super) fn update(&mut self, msg: Option<&(String, Msg)>) -> Option<(String, Msg)> {
+        let ref_msg: Option<(&str, &Msg)> = msg.map(|(s, msg)| (s.as_str(), msg));
+        ref_msg.and_then(|msg| match msg {
+            // -- error
+            (COMPONENT_TEXT_ERROR, key) if (key == &MSG_KEY_ESC) || (key == &MSG_KEY_ENTER) => {
+                self.umount_error();
+                None
+            }
+            // -- attach
+            (COMPONENT_INPUT_ATTACH, Msg::OnSubmit(Payload::One(Value::Str(path)))) => {
+                match self.attach(path) {
+                    Ok(()) => self.umount_attach(),
+                    Err(e) => self.mount_error(&e.to_string()),
+                }
+                None
+            }
+            (COMPONENT_INPUT_ATTACH, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_attach();
+                None
+            }
+            (COMPONENT_TEXT_ERROR | COMPONENT_INPUT_ATTACH, _) => None,
+
+            (_, key) if (key == &MSG_KEY_CTRL_S) || (key == &MSG_KEY_ESC) => {
+                self.exit_reason = Some(ExitReason::Quit);
+                None
+            }
+            (COMPONENT_INPUT_TO | COMPONENT_INPUT_CC | COMPONENT_INPUT_BCC, key)
+                if (key == &MSG_KEY_TAB) =>
+            {
+                // the field with the focus
+                if !self.complete_recipient(self.focus) {
+                    self.next_field(1);
+                }
+                None
+            }
+            (_, key) if (key == &MSG_KEY_TAB) => {
+                self.next_field(1);
+                None
+            }
+            (_, key) if (key == &MSG_KEY_SHIFT_TAB) => {
+                self.next_field(-1);
+                None
+            }
+            (
+                COMPONENT_INPUT_TO
+                | COMPONENT_INPUT_CC
+                | COMPONENT_INPUT_BCC
+                | COMPONENT_INPUT_SUBJECT,
+                Msg::OnSubmit(_),
+            ) => {
+                self.next_field(1);
+                None
+            }
+            (
+                COMPONENT_INPUT_TO
+                | COMPONENT_INPUT_CC
+                | COMPONENT_INPUT_BCC
+                | COMPONENT_INPUT_SUBJECT,
+                key,
+            ) if (key == &MSG_KEY_DOWN) || (key == &MSG_KEY_UP) => {
+                self.next_field(if key == &MSG_KEY_UP { -1 } else { 1 });
+                None
+            }
+
+            // -- attachments
+            (COMPONENT_TABLE_ATTACHMENTS, key) if (key == &MSG_KEY_CHAR_A) => {
+                self.mount_attach();
+                None
+            }
+            (COMPONENT_TABLE_ATTACHMENTS, key)
+                if (key == &MSG_KEY_CHAR_D) || (key == &MSG_KEY_DEL) =>
+            {
+                if let Err(e) = self.remove_attachment() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+            _ => None,
+        })
+    }
+
+    /// ### `next_field`
+    ///
+    /// Give the focus to the field `step` fields after the current one, around the end
+    fn next_field(&mut self, step: isize) {
+        self.end_completion();
+        let current = FIELDS
+            .iter()
+            .position(|f| *f == self.focus)
+            .unwrap_or_default();
+        let count = FIELDS.len().cast_signed();
+        let next = (current.cast_signed() + step).rem_euclid(count);
+        self.focus = FIELDS[next.cast_unsigned()];
+        self.view.active(self.focus);
+    }
+
+    /// ### `complete_recipient`
+    ///
+    /// Complete the address typed last in `field` from the address book, or go to the next
+    /// candidate when it was just completed. False when there is nothing more to complete
+    fn complete_recipient(&mut self, field: &'static str) -> bool {
+        let Some(Payload::One(Value::Str(value))) = self.view.get_state(field) else {
+            return false;
+        };
+        let previous = self
+            .completion
+            .take()
+            .filter(|(f, _)| *f == field)
+            .map(|(_, completion)| completion);
+        // the only candidate was taken already
+        if previous
+            .as_ref()
+            .is_some_and(|c| c.value() == value && c.position().1 == 1)
+        {
+            self.end_completion();
+            return false;
+        }
+        let Some(completion) = Completion::next(
+            previous,
+            &value,
+            &self.address_book,
+            Local::now().timestamp(),
+        ) else {
+            self.end_completion();
+            return false;
+        };
+        let (index, count) = completion.position();
+        if let Some(props) = self.view.get_props(field) {
+            let props = InputPropsBuilder::from(props)
+                .with_label(
+                    format!("{} ({index}/{count}, <TAB> next)", label_of(field)),
+                    Alignment::Left,
+                )
+                .with_value(completion.value().to_string())
+                .build();
+            self.view.update(field, props);
+        }
+        self.completion = Some((field, completion));
+        true
+    }
+
+    /// Put the label of the field being completed back
+    fn end_completion(&mut self) {
+        for field in [COMPONENT_INPUT_TO, COMPONENT_INPUT_CC, COMPONENT_INPUT_BCC] {
+            if let Some(props) = self.view.get_props(field) {
+                let props = InputPropsBuilder::from(props)
+                    .with_label(label_of(field), Alignment::Left)
+                    .build();
+                self.view.update(field, props);
+            }
+        }
+        self.completion = None;
+    }
+
+    /// ### `attach`
+    ///
+    /// Send the file at `path` along with the mail
+    fn attach(&mut self, path: &str) -> Result<()> {
+        let path = shellexpand::tilde(path.trim()).to_string();
+        if path.is_empty() {
+            return Err(anyhow!("Type the path of a file to attach"));
+        }
+        let attachment = Attachment::from_file(Path::new(&path))?;
+        self.draft.attachments.push(attachment);
+        self.mount_attachments();
+        Ok(())
+    }
+
+    /// ### `remove_attachment`
+    ///
+    /// Drop the attachment selected in the list
+    fn remove_attachment(&mut self) -> Result<()> {
+        let Some(Payload::One(Value::Usize(index))) =
+            self.view.get_state(COMPONENT_TABLE_ATTACHMENTS)
+        else {
+            return Ok(());
+        };
+        if index >= self.draft.attachments.len() {
+            return Err(anyhow!("No attachment to remove"));
+        }
+        self.draft.attachments.remove(index);
+        self.mount_attachments();
+        Ok(())
+    }
+}
+
+fn label_of(field: &str) -> &'static str {
+    match field {
+        COMPONENT_INPUT_CC => "Cc",
+        COMPONENT_INPUT_BCC => "Bcc",
+        COMPONENT_INPUT_SUBJECT => "Subject",
+        _ => "To",
+    }
+}
@@ -0,0 +1,265 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::body::BodyEditor;
+use super::{
+    ComposeActivity, COMPONENT_EDITOR_BODY, COMPONENT_INPUT_ATTACH, COMPONENT_INPUT_BCC,
+    COMPONENT_INPUT_CC, COMPONENT_INPUT_SUBJECT, COMPONENT_INPUT_TO, COMPONENT_LABEL_HELP,
+    COMPONENT_TABLE_ATTACHMENTS, COMPONENT_TEXT_ERROR,
+};
+use crate::ui::activity::main::format_size;
+use crate::ui::draw_area_in;
+use std::convert::TryFrom;
+use tui_realm_stdlib::{
+    Input, InputPropsBuilder, Label, LabelPropsBuilder, Paragraph, ParagraphPropsBuilder, Table,
+    TablePropsBuilder, TextareaPropsBuilder,
+};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::props::{TableBuilder, TextSpan};
+use tuirealm::tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tuirealm::tui::style::Color;
+use tuirealm::tui::widgets::Clear;
+use tuirealm::{PropsBuilder, View};
+
+impl ComposeActivity {
+    /// ### `init_setup`
+    ///
+    /// Initialize the view with the fields of the draft
+    pub(super) fn init_setup(&mut self) {
+        self.view = View::init();
+        let fields = [
+            (COMPONENT_INPUT_TO, "To", self.draft.to.clone()),
+            (COMPONENT_INPUT_CC, "Cc", self.draft.cc.clone()),
+            (COMPONENT_INPUT_BCC, "Bcc", self.draft.bcc.clone()),
+            (
+                COMPONENT_INPUT_SUBJECT,
+                "Subject",
+                self.draft.subject.clone(),
+            ),
+        ];
+        for (component, label, value) in fields {
+            self.mount_field(component, label, value);
+        }
+        self.view.mount(
+            COMPONENT_EDITOR_BODY,
+            Box::new(BodyEditor::new(
+                TextareaPropsBuilder::default()
+                    .with_foreground(Color::White)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::Green)
+                    .with_title(format!("From: {}", self.draft.from), Alignment::Left)
+                    .build(),
+                &self.draft.body,
+                self.dictionary.clone(),
+            )),
+        );
+        self.view.mount(
+            COMPONENT_LABEL_HELP,
+            Box::new(Label::new(
+                LabelPropsBuilder::default()
+                    .with_foreground(Color::Cyan)
+                    .with_text(format!(
+                        "{} | <TAB> next field/complete <S-TAB> back <^S/ESC> done \
+                         <^Z/^Y> undo/redo <^K> cut line <^W> delete word",
+                        self.dictionary_status
+                    ))
+                    .build(),
+            )),
+        );
+        self.mount_attachments();
+        // a reply is written below the attribution, other mails start with their recipient
+        self.focus = if self.draft.to.is_empty() {
+            COMPONENT_INPUT_TO
+        } else {
+            COMPONENT_EDITOR_BODY
+        };
+        self.view.active(self.focus);
+    }
+
+    pub(super) fn mount_field(&mut self, component: &str, label: &str, value: String) {
+        self.view.mount(
+            component,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightYellow)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightYellow)
+                    .with_label(label, Alignment::Left)
+                    .with_value(value)
+                    .build(),
+            )),
+        );
+    }
+
+    /// ### `mount_attachments`
+    ///
+    /// List the files sent along, keeping the focus where it is
+    pub(super) fn mount_attachments(&mut self) {
+        let mut table = TableBuilder::default();
+        for (i, attachment) in self.draft.attachments.iter().enumerate() {
+            if i > 0 {
+                table.add_row();
+            }
+            table.add_col(
+                TextSpan::new(format!(
+                    "\u{1f4ce} {} ({}, {})",
+                    attachment.filename,
+                    attachment.mimetype,
+                    format_size(attachment.data.len() as u64)
+                ))
+                .fg(Color::LightYellow),
+            );
+        }
+        if self.draft.attachments.is_empty() {
+            table.add_col(TextSpan::from("No attachments"));
+        }
+        self.view.mount(
+            COMPONENT_TABLE_ATTACHMENTS,
+            Box::new(Table::new(
+                TablePropsBuilder::default()
+                    .with_foreground(Color::White)
+                    .with_highlighted_str(Some("\u{1f680}"))
+                    .with_highlighted_color(Color::LightBlue)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::Cyan)
+                    .scrollable(true)
+                    .with_title("Attachments: <a> attach <d> remove", Alignment::Left)
+                    .with_widths(&[100])
+                    .with_table(table.build())
+                    .build(),
+            )),
+        );
+        if self.focus == COMPONENT_TABLE_ATTACHMENTS {
+            self.view.active(COMPONENT_TABLE_ATTACHMENTS);
+        }
+    }
+
+    /// ### `view`
+    ///
+    /// Draw the fields, the body and the popups over them
+    pub(super) fn view(&mut self) {
+        let Some(mut ctx) = self.context.take() else {
+            return;
+        };
+        let attachments = u16::try_from(self.draft.attachments.len())
+            .unwrap_or(u16::MAX)
+            .clamp(1, 4);
+        let _drop = ctx.context.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(0)
+                .constraints(
+                    [
+                        Constraint::Length(3),
+                        Constraint::Length(3),
+                        Constraint::Length(3),
+                        Constraint::Min(3),
+                        Constraint::Length(attachments + 2),
+                        Constraint::Length(1),
+                    ]
+                    .as_ref(),
+                )
+                .split(f.size());
+            let copies = Layout::default()
+                .direction(Direction::Horizontal)
+                .margin(0)
+                .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+                .split(chunks[1]);
+            self.view.render(COMPONENT_INPUT_TO, f, chunks[0]);
+            self.view.render(COMPONENT_INPUT_CC, f, copies[0]);
+            self.view.render(COMPONENT_INPUT_BCC, f, copies[1]);
+            self.view.render(COMPONENT_INPUT_SUBJECT, f, chunks[2]);
+            self.view.render(COMPONENT_EDITOR_BODY, f, chunks[3]);
+            self.view.render(COMPONENT_TABLE_ATTACHMENTS, f, chunks[4]);
+            self.view.render(COMPONENT_LABEL_HELP, f, chunks[5]);
+
+            if let Some(props) = self.view.get_props(COMPONENT_INPUT_ATTACH) {
+                if props.visible {
+                    // input over the bottom of the body
+                    let body = chunks[3];
+                    let height = body.height.min(3);
+                    let bar = Rect::new(body.x, body.y + body.height - height, body.width, height);
+                    f.render_widget(Clear, bar);
+                    self.view.render(COMPONENT_INPUT_ATTACH, f, bar);
+                }
+            }
+
+            if let Some(props) = self.view.get_props(COMPONENT_TEXT_ERROR) {
+                if props.visible {
+                    let popup = draw_area_in(f.size(), 50, 10);
+                    f.render_widget(Clear, popup);
+                    self.view.render(COMPONENT_TEXT_ERROR, f, popup);
+                }
+            }
+        });
+        self.context = Some(ctx);
+    }
+
+    /// ### `mount_attach`
+    ///
+    /// Ask for the path of a file to attach
+    pub(super) fn mount_attach(&mut self) {
+        self.view.mount(
+            COMPONENT_INPUT_ATTACH,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightYellow)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightYellow)
+                    .with_label("Attach file (path)", Alignment::Left)
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_ATTACH);
+    }
+
+    pub(super) fn umount_attach(&mut self) {
+        self.view.umount(COMPONENT_INPUT_ATTACH);
+        self.view.active(self.focus);
+    }
+
+    pub(super) fn mount_error(&mut self, text: &str) {
+        self.view.mount(
+            COMPONENT_TEXT_ERROR,
+            Box::new(Paragraph::new(
+                ParagraphPropsBuilder::default()
+                    .with_foreground(Color::Red)
+                    .bold()
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::Red)
+                    .with_title("Error", Alignment::Center)
+                    .with_texts(vec![TextSpan::from(text)])
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_TEXT_ERROR);
+    }
+
+    pub(super) fn umount_error(&mut self) {
+        self.view.umount(COMPONENT_TEXT_ERROR);
+        if self
+            .view
+            .get_props(COMPONENT_INPUT_ATTACH)
+            .is_some_and(|p| p.visible)
+        {
+            self.view.active(COMPONENT_INPUT_ATTACH);
+        } else {
+            self.view.active(self.focus);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{Attachment, Context, TermailActivity};
+use crate::config::ImageProtocol;
+use crate::mailcap::shell_quote;
+use crate::ui::image::{
+    kitty_delete_sequence, kitty_place_sequence, kitty_transmit_sequence, sixel_sequence,
+};
+use anyhow::{anyhow, Result};
+use crossterm::cursor::MoveTo;
+use crossterm::style::Print;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use crossterm::{execute, queue};
+use image::DynamicImage;
+use log::error;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use tuirealm::tui::layout::Rect;
+
+impl TermailActivity {
+    /// ### `open_attachment`
+    ///
+    /// Open the next attachment of the current mail with its interactive mailcap entry.
+    /// Each call moves on to the following attachment. Entries marked `needsterminal`
+    /// get the terminal, the others run in the background.
+    pub fn open_attachment(&mut self) -> Result<()> {
+        if self.attachments.is_empty() {
+            return Err(anyhow!("No attachment in this mail."));
+        }
+        if self.attachment_index >= self.attachments.len() {
+            self.attachment_index = 0;
+        }
+        let attachment = &self.attachments[self.attachment_index];
+        self.attachment_index += 1;
+
+        let entry = self
+            .mailcap
+            .lookup(&attachment.mimetype, &attachment.params, false)
+            .ok_or_else(|| anyhow!("No mailcap entry for {}", attachment.mimetype))?;
+        let path = entry.temp_file(&attachment.data, attachment.filename.as_deref())?;
+        let cmd = entry.command_line(&path, &attachment.mimetype, &attachment.params);
+        let cmd = if entry.uses_file() {
+            cmd
+        } else {
+            format!("{cmd} < {}", shell_quote(&path.to_string_lossy()))
+        };
+
+        if !entry.needs_terminal {
+            let child = Command::new("sh")
+                .arg("-c")
+                .arg(&cmd)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    fs::remove_file(&path).ok();
+                    return Err(e.into());
+                }
+            };
+            thread::spawn(move || {
+                match child.wait() {
+                    Ok(status) if !status.success() => error!("{cmd} exited with {status}"),
+                    Err(e) => error!("Failed to wait for {cmd}: {e}"),
+                    Ok(_) => {}
+                }
+                fs::remove_file(&path).ok();
+            });
+            return Ok(());
+        }
+
+        let status = self.run_suspended(&cmd, &[]);
+        fs::remove_file(&path).ok();
+        let status = status?;
+        if !status.success() {
+            return Err(anyhow!("{cmd} exited with {status}"));
+        }
+        Ok(())
+    }
+
+    /// ### `run_suspended`
+    ///
+    /// Leave the alternate screen and raw mode, run `cmd` in a shell with `args` after it
+    /// and wait for it, then restore the TUI. The arguments don't go through the shell.
+    pub fn run_suspended(&mut self, cmd: &str, args: &[&OsStr]) -> Result<ExitStatus> {
+        if let Some(mut ctx) = self.context.take() {
+            self.remove_image(&mut ctx);
+            ctx.leave_alternate_screen();
+            self.context = Some(ctx);
+        }
+        if let Err(err) = disable_raw_mode() {
+            error!("Failed to disable raw mode: {err}");
+        }
+
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{cmd} \"$@\""))
+            .arg("sh")
+            .args(args)
+            .status();
+
+        if let Err(err) = enable_raw_mode() {
+            error!("Failed to enter raw mode: {err}");
+        }
+        if let Some(ctx) = self.context.as_mut() {
+            ctx.enter_alternate_screen();
+            ctx.clear_screen();
+        }
+        self.redraw = true;
+        Ok(status?)
+    }
+
+    /// ### `decode_images`
+    ///
+    /// Decode the image attachments of the current mail
+    pub(super) fn decode_images(attachments: &[Attachment]) -> Vec<DynamicImage> {
+        attachments
+            .iter()
+            .filter(|a| a.mimetype.starts_with("image/"))
+            .filter_map(|a| image::load_from_memory(&a.data).ok())
+            .collect()
+    }
+
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn next_image(&mut self) {
+        if !self.images.is_empty() {
+            self.image_index = (self.image_index + 1) % self.images.len();
+        }
+    }
+
+    /// ### `image_changed`
+    ///
+    /// Whether the image on screen is not the one to draw in `area`
+    pub(super) fn image_changed(&self, area: Option<Rect>) -> bool {
+        self.image_on_screen.is_some()
+            && !matches!(&self.image_sequence, Some((index, cached_area, _)) if *index == self.image_index && Some(*cached_area) == area)
+    }
+
+    /// ### `remove_image`
+    ///
+    /// Take the kitty image off the screen and out of the terminal, before leaving it
+    pub(super) fn remove_image(&mut self, ctx: &mut Context) {
+        if self.image_protocol == ImageProtocol::Kitty {
+            let _drop = execute!(
+                ctx.context.backend_mut(),
+                Print(kitty_delete_sequence(true))
+            );
+        }
+        self.image_sequence = None;
+        self.image_on_screen = None;
+    }
+
+    /// ### `draw_image_sequence`
+    ///
+    /// Kitty and sixel images are drawn with escape sequences after the frame, since the
+    /// tui buffer can't hold them. Kitty keeps the image, which is only placed again.
+    pub(super) fn draw_image_sequence(&mut self, ctx: &mut Context, area: Option<Rect>) {
+        let backend = ctx.context.backend_mut();
+        let area = area.filter(|area| area.width > 0 && area.height > 0);
+        let (Some(area), Some(image)) = (area, self.images.get(self.image_index)) else {
+            if self.image_protocol == ImageProtocol::Kitty && self.image_on_screen.is_some() {
+                let _drop = execute!(backend, Print(kitty_delete_sequence(false)));
+            }
+            self.image_on_screen = None;
+            return;
+        };
+
+        let cached = matches!(&self.image_sequence, Some((index, cached_area, _)) if *index == self.image_index && *cached_area == area);
+        if !cached {
+            let seq = match self.image_protocol {
+                ImageProtocol::Kitty => {
+                    let _drop = queue!(backend, Print(kitty_transmit_sequence(image, area)));
+                    String::new()
+                }
+                ImageProtocol::Sixel => sixel_sequence(image, area),
+                _ => return,
+            };
+            self.image_sequence = Some((self.image_index, area, seq));
+        }
+        if let Some((_, _, seq)) = &self.image_sequence {
+            let _drop = if self.image_protocol == ImageProtocol::Kitty {
+                queue!(
+                    backend,
+                    MoveTo(area.x, area.y),
+                    Print(kitty_place_sequence(image, area))
+                )
+            } else {
+                queue!(backend, MoveTo(area.x, area.y), Print(seq))
+            };
+            let _drop = backend.flush();
+        }
+        self.image_on_screen = Some(area);
+    }
+}
@@ -0,0 +1,271 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{MailEntryNewOrRead, TermailActivity};
+use crate::config::{ColumnConfig, MaillistColumn, MaillistConfig};
+use crate::threading::ThreadRow;
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const ELLIPSIS: char = '…';
+
+impl TermailActivity {
+    /// ### `column_widths`
+    ///
+    /// Width in terminal cells of every configured column, for a list `width` cells wide
+    pub(super) fn column_widths(&self) -> Vec<usize> {
+        // borders and the highlight symbol
+        let inner = usize::from(self.maillist_width.saturating_sub(4));
+        self.columns()
+            .iter()
+            .map(|c| (inner * usize::from(c.width) / 100).saturating_sub(1))
+            .collect()
+    }
+
+    /// ### `columns`
+    ///
+    /// Configured columns, search results also show the folder of every mail
+    pub(super) fn columns(&self) -> Vec<ColumnConfig> {
+        let mut columns = self.config.maillist.columns.clone();
+        if self.fulltext.is_some() || self.unified {
+            add_column(&mut columns, MaillistColumn::Folder);
+        }
+        if self.notmuch_query.is_some() {
+            add_column(&mut columns, MaillistColumn::Tags);
+        }
+        columns
+    }
+
+    /// ### `column_text`
+    ///
+    /// Text of `column` for the mail at row `idx`, not truncated
+    pub(super) fn column_text(
+        &self,
+        column: MaillistColumn,
+        idx: usize,
+        row: &ThreadRow,
+        now: i64,
+    ) -> String {
+        let record = &self.mail_items[row.index];
+        let config = &self.config.maillist;
+        match column {
+            MaillistColumn::Index => idx.to_string(),
+            MaillistColumn::Flags => format_flags(record),
+            MaillistColumn::Date => format_date(record.date, now, config),
+            MaillistColumn::From => record.from.clone(),
+            MaillistColumn::To => record.to.clone(),
+            MaillistColumn::Subject => row.collapsed.as_ref().map_or_else(
+                || format!("{}{}", row.prefix, record.subject),
+                |indexes| {
+                    let unread = indexes
+                        .iter()
+                        .filter(|i| self.mail_items[**i].is_unread())
+                        .count();
+                    format!("▸ {} [{unread}/{}]", record.subject, indexes.len())
+                },
+            ),
+            MaillistColumn::Size => format_size(record.size),
+            MaillistColumn::Attachment => {
+                if record.attachment {
+                    "\u{1f4ce}".to_string()
+                } else {
+                    String::new()
+                }
+            }
+            MaillistColumn::Thread => {
+                if record.thread_count > 1 {
+                    record.thread_count.to_string()
+                } else {
+                    String::new()
+                }
+            }
+            MaillistColumn::List => record.list.clone(),
+            MaillistColumn::Preview => record.preview.clone(),
+            MaillistColumn::Tags => record.tags.join(" "),
+            MaillistColumn::Folder => record
+                .folder
+                .strip_prefix(&self.path)
+                .unwrap_or(&record.folder)
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+}
+
+/// Show `column` after the first one, if it is not configured already
+fn add_column(columns: &mut Vec<ColumnConfig>, column: MaillistColumn) {
+    if columns.iter().any(|c| c.column == column) {
+        return;
+    }
+    let added = ColumnConfig { column, width: 15 };
+    // make room in the widest column
+    if let Some(widest) = columns.iter_mut().max_by_key(|c| c.width) {
+        widest.width = widest.width.saturating_sub(added.width);
+    }
+    columns.insert(columns.len().min(1), added);
+}
+
+/// ### `truncate`
+///
+/// Cut `text` to `width` terminal cells, ending with an ellipsis when something was cut
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width - 1 {
+            break;
+        }
+        used += w;
+        result.push(c);
+    }
+    result.push(ELLIPSIS);
+    result
+}
+
+fn format_flags(record: &MailEntryNewOrRead) -> String {
+    let mut flags = String::new();
+    if record.is_unread() {
+        flags.push('N');
+    }
+    for (flag, c) in [('F', '!'), ('R', 'r'), ('P', 'f'), ('D', 'd'), ('T', 'x')] {
+        if record.item.flags().contains(flag) {
+            flags.push(c);
+        }
+    }
+    flags
+}
+
+/// ### `format_size`
+///
+/// Human readable size, like `512B` or `1.2K`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size}{}", UNITS[0])
+    } else if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+/// ### `format_date`
+///
+/// Format a unix timestamp with the configured format and timezone,
+/// or relative to `now` for the last week when `relative_dates` is set
+pub fn format_date(date: i64, now: i64, config: &MaillistConfig) -> String {
+    if config.relative_dates {
+        if let Some(relative) = relative_date(now - date) {
+            return relative;
+        }
+    }
+    // out of range dates show as the epoch
+    let utc = Utc
+        .timestamp_opt(date.max(0), 0)
+        .single()
+        .unwrap_or_default();
+    let datetime: DateTime<FixedOffset> = parse_timezone(&config.timezone).map_or_else(
+        || utc.with_timezone(&Local).fixed_offset(),
+        |offset| utc.with_timezone(&offset),
+    );
+    datetime.format(&config.date_format).to_string()
+}
+
+fn relative_date(seconds: i64) -> Option<String> {
+    match seconds {
+        s if s < 0 => None,
+        s if s < 60 => Some("just now".to_string()),
+        s if s < 3600 => Some(format!("{}m ago", s / 60)),
+        s if s < 86400 => Some(format!("{}h ago", s / 3600)),
+        s if s < 7 * 86400 => Some(format!("{}d ago", s / 86400)),
+        _ => None,
+    }
+}
+
+/// "utc" or "+08:00" style offsets; `None` means the local timezone
+fn parse_timezone(timezone: &str) -> Option<FixedOffset> {
+    let timezone = timezone.trim();
+    if timezone.eq_ignore_ascii_case("utc") {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match timezone.chars().next()? {
+        '+' => (1, &timezone[1..]),
+        '-' => (-1, &timezone[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_columns_truncate() {
+        assert_eq!(truncate("hello", 5), "hello");
+        assert_eq!(truncate("hello world", 6), "hello…");
+        // every CJK character takes two cells
+        assert_eq!(truncate("张三丰的邮件", 6), "张三…");
+        assert_eq!(truncate("张三丰的邮件", 5), "张三…");
+        assert_eq!(truncate("abc", 0), "");
+    }
+
+    #[test]
+    fn test_columns_format() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(20 * 1024 * 1024), "20M");
+
+        let mut config = MaillistConfig {
+            timezone: "+08:00".to_string(),
+            date_format: "%Y-%m-%d %H:%M".to_string(),
+            ..MaillistConfig::default()
+        };
+        assert_eq!(format_date(0, 0, &config), "1970-01-01 08:00");
+        config.timezone = "utc".to_string();
+        assert_eq!(format_date(0, 0, &config), "1970-01-01 00:00");
+        config.relative_dates = true;
+        assert_eq!(format_date(0, 7200, &config), "2h ago");
+        assert_eq!(format_date(0, 30 * 86400, &config), "1970-01-01 00:00");
+        assert_eq!(format_date(i64::MAX, 0, &config), "1970-01-01 00:00");
+    }
+}
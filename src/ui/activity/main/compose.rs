@@ -0,0 +1,639 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::columns::format_size;
+use super::reader::with_matches;
+use super::{
+    Context, ExitReason, TermailActivity, COMPONENT_TABLE_COMPOSE, COMPONENT_TEXTAREA_MAIL,
+};
+use crate::compose::{editor, forward, reply, Draft, ReplyKind};
+use crate::config::{AccountConfig, IdentityConfig};
+use crate::markdown::{self, LineKind};
+use crate::outbox::Queued;
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use maildir::Maildir;
+use mailparse::{addrparse, parse_mail, MailAddr};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use tui_realm_stdlib::{Table, TablePropsBuilder, TextareaPropsBuilder};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::props::{TableBuilder, TextSpan};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::{Payload, PropsBuilder, Value};
+
+impl TermailActivity {
+    /// ### `compose_new`
+    ///
+    /// Write a new mail to `to` in the editor
+    pub(super) fn compose_new(&mut self, to: &str) -> Result<()> {
+        let mut draft = Draft {
+            to: to.to_string(),
+            markdown: self.config.compose.markdown,
+            ..Draft::default()
+        };
+        self.choose_identity(&mut draft);
+        self.compose_origin = None;
+        self.edit_draft(draft)
+    }
+
+    /// ### `reply_mail`
+    ///
+    /// Answer the mail in the reader
+    pub(super) fn reply_mail(&mut self, kind: ReplyKind) -> Result<()> {
+        let (folder, id, raw) = self.read_current()?;
+        let parsed = parse_mail(&raw)?;
+        let mut draft = reply(
+            &parsed,
+            kind,
+            &self.own_addresses(),
+            &self.config.compose.attribution,
+        )?;
+        draft.markdown = self.config.compose.markdown;
+        self.choose_identity(&mut draft);
+        self.compose_origin = Some((folder, id, "R"));
+        self.edit_draft(draft)
+    }
+
+    /// ### `forward_mail`
+    ///
+    /// Pass the mail in the reader on, inline or as an attached mail
+    pub(super) fn forward_mail(&mut self, attached: bool) -> Result<()> {
+        let (folder, id, raw) = self.read_current()?;
+        let parsed = parse_mail(&raw)?;
+        let mut draft = forward(&parsed, &raw, attached);
+        draft.markdown = self.config.compose.markdown;
+        self.choose_identity(&mut draft);
+        self.compose_origin = Some((folder, id, "P"));
+        self.edit_draft(draft)
+    }
+
+    /// Folder, id and content of the mail in the reader
+    fn read_current(&self) -> Result<(PathBuf, String, Vec<u8>)> {
+        let (folder, id) = self
+            .reading
+            .clone()
+            .ok_or_else(|| anyhow!("Open a mail first"))?;
+        let entry = Maildir::from(folder.clone())
+            .find(&id)
+            .ok_or_else(|| anyhow!("The mail is gone from {}", folder.display()))?;
+        let raw = fs::read(entry.path())?;
+        Ok((folder, id, raw))
+    }
+
+    fn default_from(&self) -> String {
+        if self.config.compose.from.is_empty() {
+            std::env::var("EMAIL").unwrap_or_default()
+        } else {
+            self.config.compose.from.clone()
+        }
+    }
+
+    /// ### `identity_for`
+    ///
+    /// Identity sending from `address`, else the one of the current folder
+    pub(super) fn identity_for(&self, address: &str) -> Option<&IdentityConfig> {
+        let identities = &self.config.identities;
+        identities
+            .iter()
+            .find(|i| i.address.eq_ignore_ascii_case(address))
+            .or_else(|| {
+                let current = self.current_maildir.path();
+                identities.iter().find(|i| {
+                    i.folders
+                        .iter()
+                        .any(|folder| current.starts_with(self.path.join(folder)))
+                })
+            })
+    }
+
+    /// Send `draft` as the identity of its From address, or of the current folder, and sign it
+    fn choose_identity(&self, draft: &mut Draft) {
+        // a reply is sent from the address the mail went to
+        let address = draft.sender().unwrap_or_default();
+        match self.identity_for(&address) {
+            Some(identity) => {
+                draft.from = identity.mailbox();
+                draft.set_signature("", &identity.signature);
+            }
+            None if draft.from.is_empty() => draft.from = self.default_from(),
+            None => {}
+        }
+    }
+
+    /// ### `cycle_identity`
+    ///
+    /// Send the mail being composed from the next identity, with its signature
+    pub(super) fn cycle_identity(&mut self) -> Result<()> {
+        let identities = &self.config.identities;
+        if identities.is_empty() {
+            return Err(anyhow!("No identities are set up"));
+        }
+        let draft = self
+            .compose
+            .as_mut()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        let sender = draft.sender().unwrap_or_default();
+        let current = identities
+            .iter()
+            .position(|i| i.address.eq_ignore_ascii_case(&sender));
+        let next = &identities[current.map_or(0, |i| (i + 1) % identities.len())];
+        let old = current.map_or("", |i| identities[i].signature.as_str());
+        draft.set_signature(old, &next.signature);
+        draft.from = next.mailbox();
+        self.mount_compose();
+        Ok(())
+    }
+
+    /// Our addresses, to tell them apart in replies
+    fn own_addresses(&self) -> Vec<String> {
+        let mut own = self.config.compose.addresses.clone();
+        own.extend(self.config.identities.iter().map(|i| i.address.clone()));
+        if let Ok(list) = addrparse(&self.default_from()) {
+            own.extend(list.iter().filter_map(|addr| match addr {
+                MailAddr::Single(info) => Some(info.addr.clone()),
+                MailAddr::Group(_) => None,
+            }));
+        }
+        own
+    }
+
+    /// ### `resume_draft`
+    ///
+    /// Go on writing a postponed mail
+    pub(super) fn resume_draft(&mut self, row: usize) -> Result<()> {
+        let index = self
+            .rows
+            .get(row)
+            .ok_or_else(|| anyhow!("No mail selected"))?
+            .index;
+        let item = &self.mail_items[index];
+        let raw = fs::read(item.item.path())?;
+        let draft = Draft::from_message(&raw)?;
+        self.compose_origin = None;
+        self.compose_draft = Some((item.folder.clone(), item.item.id().to_string()));
+        self.edit_draft(draft)
+    }
+
+    pub(super) fn is_draft(&self, row: usize) -> bool {
+        self.rows
+            .get(row)
+            .is_some_and(|r| self.mail_items[r.index].item.is_draft())
+    }
+
+    /// ### `edit_draft`
+    ///
+    /// Hand the draft to `$EDITOR` with the TUI suspended, or to the built-in editor, then ask
+    /// what to do with it
+    pub(super) fn edit_draft(&mut self, draft: Draft) -> Result<()> {
+        if self.config.compose.builtin_editor {
+            self.editing = Some(draft);
+            self.exit_reason = Some(ExitReason::Compose);
+            return Ok(());
+        }
+        let template = draft.template();
+        let path = std::env::temp_dir().join(format!(
+            "termail-{}-{}.eml",
+            std::process::id(),
+            Local::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        // only readable by us, and never an existing file or link put there by someone else
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?
+            .write_all(template.as_bytes())?;
+        let cmd = editor();
+        let status = self.run_suspended(&cmd, &[path.as_os_str()]);
+        let text = fs::read_to_string(&path);
+        fs::remove_file(&path).ok();
+        let status = status?;
+        if !status.success() {
+            return Err(anyhow!("{cmd} exited with {status}"));
+        }
+        let text = text?;
+        if text == template && self.discards_unchanged() {
+            self.show_message("Compose", "Mail not changed, discarded");
+            return Ok(());
+        }
+        let edited = match Draft::parse(&text) {
+            Ok(edited) => Draft {
+                attachments: draft.attachments,
+                markdown: draft.markdown,
+                ..edited
+            },
+            Err(e) => {
+                // keep what was written for another try
+                self.compose = Some(Draft {
+                    body: text,
+                    ..draft
+                });
+                self.mount_compose();
+                return Err(e);
+            }
+        };
+        self.compose = Some(edited);
+        self.mount_compose();
+        Ok(())
+    }
+
+    /// ### `suspend_for_compose`
+    ///
+    /// The mail to write in the built-in editor, and the context to draw it with
+    pub fn suspend_for_compose(&mut self) -> Option<(Draft, Context)> {
+        self.exit_reason = None;
+        let draft = self.editing.clone()?;
+        let mut context = self.context.take()?;
+        self.remove_image(&mut context);
+        Some((draft, context))
+    }
+
+    /// ### `discards_unchanged`
+    ///
+    /// Whether a mail left unchanged in the editor is dropped. Only a new one is: a mail
+    /// edited again and a resumed draft are kept as they are.
+    const fn discards_unchanged(&self) -> bool {
+        self.compose.is_none() && self.compose_draft.is_none()
+    }
+
+    /// ### `resume_from_compose`
+    ///
+    /// Take the context back from the built-in editor, and ask what to do with the mail
+    pub fn resume_from_compose(&mut self, context: Option<Context>, draft: Draft) {
+        self.context = context;
+        self.redraw = true;
+        let before = self.editing.take();
+        if before.as_ref() == Some(&draft) && self.discards_unchanged() {
+            self.show_message("Compose", "Mail not changed, discarded");
+            return;
+        }
+        self.compose = Some(draft);
+        self.mount_compose();
+    }
+
+    pub(super) fn mount_compose(&mut self) {
+        let Some(draft) = &self.compose else {
+            return;
+        };
+        let mut table = TableBuilder::default();
+        for line in draft.summary() {
+            table.add_col(TextSpan::from(line.as_str())).add_row();
+        }
+        table.add_col(TextSpan::from(""));
+        for attachment in &draft.attachments {
+            table.add_row().add_col(
+                TextSpan::new(format!(
+                    "\u{1f4ce} {} ({}, {})",
+                    attachment.filename,
+                    attachment.mimetype,
+                    format_size(attachment.data.len() as u64)
+                ))
+                .fg(Color::LightYellow),
+            );
+        }
+        self.view.mount(
+            COMPONENT_TABLE_COMPOSE,
+            Box::new(Table::new(
+                TablePropsBuilder::default()
+                    .with_foreground(Color::White)
+                    .with_background(Color::Black)
+                    .with_highlighted_str(Some("\u{1f680}"))
+                    .with_highlighted_color(Color::LightBlue)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::Cyan)
+                    .scrollable(true)
+                    .with_title(
+                        "Compose: <y> send <q> queue <e> edit <t/c/b> to/cc/bcc <i> identity <d> postpone <a> attach <x> remove <M> markdown <p> preview <ESC> discard",
+                        Alignment::Center,
+                    )
+                    .with_widths(&[100])
+                    .with_table(table.build())
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_TABLE_COMPOSE);
+    }
+
+    /// ### `remove_attachment`
+    ///
+    /// Drop the attachment selected in the compose popup
+    pub(super) fn remove_attachment(&mut self) -> Result<()> {
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_COMPOSE)
+        else {
+            return Ok(());
+        };
+        let draft = self
+            .compose
+            .as_mut()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        // the summary and an empty line come first
+        let index = row
+            .checked_sub(draft.summary().len() + 1)
+            .filter(|i| *i < draft.attachments.len())
+            .ok_or_else(|| anyhow!("Select an attachment to remove"))?;
+        draft.attachments.remove(index);
+        self.mount_compose();
+        Ok(())
+    }
+
+    pub(super) fn umount_compose(&mut self) {
+        self.view.umount(COMPONENT_TABLE_COMPOSE);
+    }
+
+    /// ### `toggle_markdown`
+    ///
+    /// Mark the mail being composed as Markdown, or as plain text again
+    pub(super) fn toggle_markdown(&mut self) -> Result<()> {
+        let draft = self
+            .compose
+            .as_mut()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        draft.markdown = !draft.markdown;
+        self.mount_compose();
+        Ok(())
+    }
+
+    /// ### `preview_draft`
+    ///
+    /// Show the Markdown of the mail being composed, rendered, in the reader
+    pub(super) fn preview_draft(&mut self) -> Result<()> {
+        let draft = self
+            .compose
+            .as_ref()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        if !draft.markdown {
+            return Err(anyhow!(
+                "The mail is plain text, mark it as Markdown with <M>"
+            ));
+        }
+        let lines: Vec<TextSpan> = markdown::preview(&draft.body)
+            .into_iter()
+            .map(|(kind, line)| {
+                let span = TextSpan::new(line);
+                match kind {
+                    LineKind::Text => span,
+                    LineKind::Heading => span.fg(Color::LightYellow).bold(),
+                    LineKind::Code => span.fg(Color::LightGreen),
+                    LineKind::Quote => span.fg(Color::Gray).italic(),
+                    LineKind::Rule => span.fg(Color::DarkGray),
+                }
+            })
+            .collect();
+        self.set_reader(lines, "Preview: <ESC/p> back to compose");
+        self.previewing = true;
+        self.umount_compose();
+        self.view.active(COMPONENT_TEXTAREA_MAIL);
+        Ok(())
+    }
+
+    /// ### `end_preview`
+    ///
+    /// Put the mail back in the reader and return to the compose popup
+    pub(super) fn end_preview(&mut self) {
+        self.previewing = false;
+        self.set_reader(self.mail_lines.clone(), "Mail");
+        self.mount_compose();
+    }
+
+    fn set_reader(&mut self, lines: Vec<TextSpan>, title: &str) {
+        if let Some(props) = self.view.get_props(COMPONENT_TEXTAREA_MAIL) {
+            let props = TextareaPropsBuilder::from(props)
+                .with_texts(lines)
+                .with_title(title, Alignment::Left)
+                .build();
+            let props = with_matches(props, &[], None);
+            self.view.update(COMPONENT_TEXTAREA_MAIL, props);
+        }
+    }
+
+    /// ### `edit_again`
+    ///
+    /// Go back to the editor with the mail being composed
+    pub(super) fn edit_again(&mut self) -> Result<()> {
+        let draft = self
+            .compose
+            .clone()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        self.umount_compose();
+        self.edit_draft(draft)
+    }
+
+    /// ### `send_draft`
+    ///
+    /// Send the mail being composed, or only queue it in the Outbox with `queue`. Returns
+    /// what became of it
+    pub(super) fn send_draft(&mut self, queue: bool) -> Result<&'static str> {
+        let draft = self
+            .compose
+            .as_ref()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        draft.check()?;
+        let from = draft.sender()?;
+        let account = self
+            .account_for(&from)
+            .ok_or_else(|| anyhow!("No account is set up to send from {from}"))?
+            .clone();
+        let recipients = draft.recipients()?;
+        let message = draft.to_message(Local::now());
+        self.address_book.sent_to(
+            &format!("{}, {}, {}", draft.to, draft.cc, draft.bcc),
+            Local::now().timestamp(),
+        );
+        self.save_address_book();
+        let sent = account.save_sent.then(|| self.sent_folder(&account));
+        let mut queued = Queued::new(&account.name, &from, &recipients, sent, &message);
+        queued.origin = self
+            .compose_origin
+            .clone()
+            .map(|(folder, id, flag)| (folder, id, flag.to_string()));
+        queued.draft.clone_from(&self.compose_draft);
+        self.deliver(&queued, queue)
+    }
+
+    /// ### `deliver`
+    ///
+    /// Put a mail in the Outbox, which sends it in the background right away, so that a slow
+    /// server doesn't hold the UI. Unless it is only queued, how the send went is shown once
+    /// it is done
+    pub(super) fn deliver(&mut self, queued: &Queued, queue: bool) -> Result<&'static str> {
+        self.enqueue(queued)?;
+        if queue {
+            return Ok("Mail queued in the Outbox");
+        }
+        self.outbox_notify_next = true;
+        Ok("Sending the mail")
+    }
+
+    /// ### `bounce_mail`
+    ///
+    /// Resend the mail `id` of `folder`, unchanged, to `to`
+    pub(super) fn bounce_mail(
+        &mut self,
+        mail: (PathBuf, String),
+        to: &str,
+    ) -> Result<&'static str> {
+        let (folder, id) = mail;
+        let entry = Maildir::from(folder.clone())
+            .find(&id)
+            .ok_or_else(|| anyhow!("The mail is gone from {}", folder.display()))?;
+        let raw = fs::read(entry.path())?;
+        let resend = Draft {
+            from: self
+                .identity_for("")
+                .map_or_else(|| self.default_from(), IdentityConfig::mailbox),
+            to: to.trim().trim_end_matches(',').trim().to_string(),
+            ..Draft::default()
+        };
+        resend.check()?;
+        let from = resend.sender()?;
+        let account = self
+            .account_for(&from)
+            .ok_or_else(|| anyhow!("No account is set up to send from {from}"))?
+            .clone();
+        let message = resend.bounce(&raw, Local::now());
+        self.address_book
+            .sent_to(&resend.to, Local::now().timestamp());
+        self.save_address_book();
+        let queued = Queued::new(&account.name, &from, &resend.recipients()?, None, &message);
+        self.deliver(&queued, false)
+    }
+
+    /// ### `sent_folder`
+    ///
+    /// Sent maildir of the account, to keep sent mails in as they were sent
+    pub(super) fn sent_folder(&self, account: &AccountConfig) -> PathBuf {
+        match &account.sent {
+            Some(sent) => self.path.join(shellexpand::tilde(sent).as_ref()),
+            None if !self.config.compose.sent.is_empty() => self
+                .path
+                .join(shellexpand::tilde(&self.config.compose.sent).as_ref()),
+            None => self.special_folder("Sent"),
+        }
+    }
+
+    /// ### `account_for`
+    ///
+    /// Account of the identity sending from `address`, else the account sending from it, else
+    /// the one of the current folder, else the only one
+    pub(super) fn account_for(&self, address: &str) -> Option<&AccountConfig> {
+        let accounts = &self.config.accounts;
+        let identity_account = self
+            .config
+            .identities
+            .iter()
+            .find(|i| i.address.eq_ignore_ascii_case(address))
+            .and_then(|i| i.account.as_ref());
+        identity_account
+            .and_then(|name| accounts.iter().find(|a| &a.name == name))
+            .or_else(|| {
+                accounts
+                    .iter()
+                    .find(|a| a.address.eq_ignore_ascii_case(address))
+            })
+            .or_else(|| {
+                let current = self.current_maildir.path();
+                accounts.iter().find(|a| {
+                    !a.folder.is_empty() && current.starts_with(self.path.join(&a.folder))
+                })
+            })
+            .or(match accounts.as_slice() {
+                [only] => Some(only),
+                _ => None,
+            })
+    }
+
+    /// ### `save_draft`
+    ///
+    /// Keep the mail being composed in the Drafts folder
+    pub(super) fn save_draft(&mut self) -> Result<()> {
+        let draft = self
+            .compose
+            .as_ref()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        let drafts = Maildir::from(self.drafts_folder());
+        drafts.create_dirs()?;
+        drafts
+            .store_cur_with_flags(&draft.to_postponed(Local::now()), "D")
+            .map_err(|e| anyhow!("Failed to save draft: {e}"))?;
+        self.remove_resumed_draft()?;
+        self.discard_draft();
+        self.show_message("Compose", "Mail saved to Drafts");
+        Ok(())
+    }
+
+    /// Delete the draft the mail was resumed from, now that it has a newer copy
+    fn remove_resumed_draft(&mut self) -> Result<()> {
+        if let Some((folder, id)) = self.compose_draft.take() {
+            Maildir::from(folder)
+                .delete(&id)
+                .map_err(|e| anyhow!("Failed to remove the old draft: {e}"))?;
+            // the draft may be shown in the mail list
+            self.reload_current();
+        }
+        Ok(())
+    }
+
+    /// Load the folder in the mail list again, after mails in it changed
+    pub(super) fn reload_current(&mut self) {
+        if self.unified || self.notmuch_query.is_some() {
+            return;
+        }
+        let current = self.current_maildir.path().to_path_buf();
+        if let Some(node) = current.to_str() {
+            self.load_mailbox(node);
+        }
+    }
+
+    pub(super) fn discard_draft(&mut self) {
+        self.compose = None;
+        self.compose_origin = None;
+        self.compose_draft = None;
+        self.umount_compose();
+    }
+
+    /// ### `drafts_folder`
+    ///
+    /// Maildir postponed mails are saved in
+    fn drafts_folder(&self) -> PathBuf {
+        if self.config.compose.drafts.is_empty() {
+            self.special_folder("Drafts")
+        } else {
+            self.path
+                .join(shellexpand::tilde(&self.config.compose.drafts).as_ref())
+        }
+    }
+
+    /// ### `special_folder`
+    ///
+    /// Folder like Drafts or Sent next to the current folder, or under the mail root
+    pub(super) fn special_folder(&self, name: &str) -> PathBuf {
+        let current = self.current_maildir.path();
+        match current.parent() {
+            Some(parent) if current.join("cur").is_dir() => parent.join(name),
+            _ => self.path.join(name),
+        }
+    }
+}
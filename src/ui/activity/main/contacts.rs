@@ -0,0 +1,388 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{
+    TermailActivity, COMPONENT_INPUT_CONTACT_NAME, COMPONENT_TABLE_CONTACTS,
+    COMPONENT_TABLE_MAILLIST,
+};
+use crate::addressbook::Person;
+use crate::vcard::{self, Card};
+use anyhow::{anyhow, Result};
+use chrono::{Local, TimeZone};
+use log::error;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tui_realm_stdlib::{Input, InputPropsBuilder, Table, TablePropsBuilder};
+use tuirealm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::props::{TableBuilder, TextSpan};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::{Payload, PropsBuilder, Value};
+
+impl TermailActivity {
+    /// ### `collect_addresses`
+    ///
+    /// Take the addresses of the folders in the mail list into the address book. Search
+    /// results hold only part of their folders, so they are left out
+    pub(super) fn collect_addresses(&mut self) {
+        if self.fulltext.is_some() || self.notmuch_query.is_some() {
+            return;
+        }
+        let mut folders: BTreeMap<&str, Vec<(&str, i64)>> = BTreeMap::new();
+        for item in &self.mail_items {
+            let Some(folder) = item.folder.to_str() else {
+                continue;
+            };
+            let headers = folders.entry(folder).or_default();
+            for value in [&item.from, &item.to, &item.cc] {
+                headers.push((value, item.date));
+            }
+        }
+        let mut changed = false;
+        for (folder, headers) in folders {
+            changed |= self.address_book.collect(folder, headers.into_iter());
+        }
+        if changed {
+            self.save_address_book();
+        }
+    }
+
+    pub(super) fn save_address_book(&self) {
+        if let Err(e) = self.address_book.save() {
+            error!("Failed to save address book: {e}");
+        }
+    }
+
+    /// ### `mount_contacts`
+    ///
+    /// Popup listing everyone of the address book, most written to first
+    pub(super) fn mount_contacts(&mut self) {
+        self.people = self.address_book.people(Local::now().timestamp());
+        let mut table = TableBuilder::default();
+        for (i, person) in self.people.iter().enumerate() {
+            if i > 0 {
+                table.add_row();
+            }
+            let last = Local
+                .timestamp_opt(person.last, 0)
+                .single()
+                .filter(|_| person.last > 0)
+                .map(|date| date.format("%y-%m-%d").to_string())
+                .unwrap_or_default();
+            let name = if person.uid.is_some() {
+                TextSpan::new(person.name.as_str()).bold()
+            } else {
+                TextSpan::from(person.name.as_str())
+            };
+            table
+                .add_col(name)
+                .add_col(TextSpan::new(person.addresses.join(", ")).fg(Color::LightBlue))
+                .add_col(TextSpan::from(
+                    format!("{}/{}", person.count, person.sent).as_str(),
+                ))
+                .add_col(TextSpan::new(last).fg(Color::LightYellow));
+        }
+        if self.people.is_empty() {
+            table.add_col(TextSpan::from("No addresses collected yet"));
+        }
+        self.view.mount(
+            COMPONENT_TABLE_CONTACTS,
+            Box::new(Table::new(
+                TablePropsBuilder::default()
+                    .with_background(Color::Black)
+                    .with_highlighted_str(Some("\u{1f680}"))
+                    .with_highlighted_color(Color::LightBlue)
+                    .with_max_scroll_step(8)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightGreen)
+                    .scrollable(true)
+                    .with_title(
+                        "Address Book: <m> write <l> mails <e> name <d> remove <i> import <x> export <ESC> close",
+                        Alignment::Left,
+                    )
+                    .with_header(&["Name", "Addresses", "Got/Sent", "Last"])
+                    .with_widths(&[25, 47, 12, 16])
+                    .with_table(table.build())
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_TABLE_CONTACTS);
+    }
+
+    pub(super) fn umount_contacts(&mut self) {
+        self.view.umount(COMPONENT_TABLE_CONTACTS);
+        self.view.active(COMPONENT_TABLE_MAILLIST);
+    }
+
+    fn selected_person(&self) -> Result<&Person> {
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_CONTACTS)
+        else {
+            return Err(anyhow!("No contact selected"));
+        };
+        self.people
+            .get(row)
+            .ok_or_else(|| anyhow!("No contact selected"))
+    }
+
+    /// ### `mount_contact_name`
+    ///
+    /// Ask for the name of the selected contact
+    pub(super) fn mount_contact_name(&mut self) -> Result<()> {
+        let person = self.selected_person()?;
+        let label = match person.uid {
+            Some(_) => format!("Name of {}", person.name),
+            None => format!(
+                "Name of {}, empty for the one in its mails",
+                person.mailbox()
+            ),
+        };
+        let value = person.name.clone();
+        self.view.mount(
+            COMPONENT_INPUT_CONTACT_NAME,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightGreen)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightGreen)
+                    .with_label(label, Alignment::Left)
+                    .with_value(value)
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_CONTACT_NAME);
+        Ok(())
+    }
+
+    pub(super) fn umount_contact_name(&mut self) {
+        self.view.umount(COMPONENT_INPUT_CONTACT_NAME);
+        self.view.active(COMPONENT_TABLE_CONTACTS);
+    }
+
+    /// ### `rename_contact`
+    ///
+    /// Give the selected contact the name typed
+    pub(super) fn rename_contact(&mut self, name: &str) -> Result<()> {
+        let person = self.selected_person()?.clone();
+        self.umount_contact_name();
+        self.address_book.rename_person(&person, name);
+        self.save_address_book();
+        self.remount_contacts();
+        Ok(())
+    }
+
+    /// ### `remove_contact`
+    ///
+    /// Drop the selected contact from the address book
+    pub(super) fn remove_contact(&mut self) -> Result<()> {
+        let person = self.selected_person()?.clone();
+        self.address_book.remove_person(&person);
+        self.save_address_book();
+        self.remount_contacts();
+        Ok(())
+    }
+
+    /// Show the contacts again, keeping the selected row
+    fn remount_contacts(&mut self) {
+        let row = match self.view.get_state(COMPONENT_TABLE_CONTACTS) {
+            Some(Payload::One(Value::Usize(row))) => row,
+            _ => 0,
+        };
+        self.mount_contacts();
+        for _ in 0..row.min(self.people.len().saturating_sub(1)) {
+            self.view.on(Event::Key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+    }
+
+    /// ### `write_to_contact`
+    ///
+    /// Compose a new mail to the selected contact
+    pub(super) fn write_to_contact(&mut self) -> Result<()> {
+        let to = self.selected_person()?.mailbox();
+        self.umount_contacts();
+        self.compose_new(&to)
+    }
+
+    /// ### `mails_of_contact`
+    ///
+    /// List the mails from any address of the selected contact
+    pub(super) fn mails_of_contact(&mut self) -> Result<()> {
+        let person = self.selected_person()?.clone();
+        self.umount_contacts();
+        self.mails_from(&person.name, &person.addresses)
+    }
+
+    /// vCard file, or folder of them, of the config
+    fn vcards_path(&self) -> Result<PathBuf> {
+        if self.config.vcards.is_empty() {
+            return Err(anyhow!(
+                "Set vcards in the config to a vCard file or folder"
+            ));
+        }
+        Ok(PathBuf::from(
+            shellexpand::tilde(&self.config.vcards).as_ref(),
+        ))
+    }
+
+    /// ### `import_vcards`
+    ///
+    /// Take in the contacts of the vCard file, or of every vCard of the folder
+    pub(super) fn import_vcards(&mut self) -> Result<()> {
+        let path = self.vcards_path()?;
+        let mut cards = Vec::new();
+        for file in vcard_files(&path)? {
+            let text = fs::read_to_string(&file)
+                .map_err(|e| anyhow!("Failed to read {}: {e}", file.display()))?;
+            cards.extend(vcard::parse(&text));
+        }
+        let count = self.address_book.import(cards);
+        self.save_address_book();
+        self.remount_contacts();
+        self.show_message("Address Book", &format!("{count} contacts imported"));
+        Ok(())
+    }
+
+    /// ### `export_vcards`
+    ///
+    /// Write everyone to the vCard file, or each card to its own file in the folder
+    pub(super) fn export_vcards(&mut self) -> Result<()> {
+        let path = self.vcards_path()?;
+        let cards = self.address_book.export(Local::now().timestamp());
+        if path.is_dir() {
+            for (file, cards) in card_files(&path, &cards)? {
+                fs::write(&file, vcard::write(&cards))?;
+            }
+        } else {
+            fs::write(&path, vcard::write(&cards))?;
+        }
+        self.show_message(
+            "Address Book",
+            &format!("{} contacts exported", cards.len()),
+        );
+        Ok(())
+    }
+}
+
+/// ### `card_files`
+///
+/// Cards grouped by the file of `folder` they go back to: the one they came from, found
+/// by UID, or by address for a card read without one, else a new file of their own
+fn card_files(folder: &Path, cards: &[Card]) -> Result<BTreeMap<PathBuf, Vec<Card>>> {
+    let mut by_uid: HashMap<String, PathBuf> = HashMap::new();
+    let mut by_email: HashMap<String, PathBuf> = HashMap::new();
+    for file in vcard_files(folder)? {
+        if let Ok(text) = fs::read_to_string(&file) {
+            for card in vcard::parse(&text) {
+                if card.uid.is_empty() {
+                    for email in &card.emails {
+                        by_email.insert(email.to_lowercase(), file.clone());
+                    }
+                } else {
+                    by_uid.insert(card.uid, file.clone());
+                }
+            }
+        }
+    }
+    let mut files: BTreeMap<PathBuf, Vec<Card>> = BTreeMap::new();
+    for card in cards {
+        let file = by_uid
+            .get(&card.uid)
+            .or_else(|| {
+                card.emails
+                    .iter()
+                    .find_map(|email| by_email.get(&email.to_lowercase()))
+            })
+            .cloned()
+            .unwrap_or_else(|| folder.join(card_file_name(card)));
+        files.entry(file).or_default().push(card.clone());
+    }
+    Ok(files)
+}
+
+/// The vCard files of a folder, or the file itself
+fn vcard_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(path)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|file| {
+            file.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("vcf"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn card_file_name(card: &Card) -> String {
+    let name: String = card
+        .uid
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{name}.vcf")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_contacts_card_files() {
+        let folder = std::env::temp_dir().join(format!("termail-vcards-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let friends = folder.join("friends.vcf");
+        fs::write(
+            &friends,
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nUID:alice\r\nFN:Alice\r\nEMAIL:alice@example.com\r\nEND:VCARD\r\n\
+             BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Bob\r\nEMAIL:bob@example.com\r\nEND:VCARD\r\n",
+        )
+        .unwrap();
+        let card = |uid: &str, email: &str| Card {
+            uid: uid.to_string(),
+            emails: vec![email.to_string()],
+            ..Card::default()
+        };
+        let cards = vec![
+            card("alice", "alice@example.com"),
+            card("termail-1", "BOB@example.com"),
+            card("termail-2", "carol@example.com"),
+        ];
+        let files = card_files(&folder, &cards).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[&friends], cards[..2].to_vec());
+        assert_eq!(files[&folder.join("termail-2.vcf")], cards[2..].to_vec());
+    }
+}
@@ -0,0 +1,83 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{TermailActivity, COMPONENT_TABLE_MAILLIST};
+use anyhow::{anyhow, Result};
+use maildir::Maildir;
+use tuirealm::{Payload, Value};
+
+impl TermailActivity {
+    /// ### `mark_read`
+    ///
+    /// Mark a mail as seen in the maildir it lives in
+    pub(super) fn mark_read(&mut self, index: usize) -> Result<()> {
+        let item = &mut self.mail_items[index];
+        let maildir = Maildir::from(item.folder.clone());
+        let id = item.item.id().to_string();
+        if item.new {
+            maildir.move_new_to_cur(&id)?;
+            item.new = false;
+        }
+        maildir.add_flags(&id, "S")?;
+        Self::reload_entry(&maildir, &mut self.mail_items[index].item, &id)
+    }
+
+    /// ### `toggle_flag`
+    ///
+    /// Flag or unflag the selected mail in the maildir it lives in
+    pub(super) fn toggle_flag(&mut self) -> Result<()> {
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_MAILLIST)
+        else {
+            return Ok(());
+        };
+        let index = self
+            .rows
+            .get(row)
+            .ok_or_else(|| anyhow!("No mail selected"))?
+            .index;
+        let item = &mut self.mail_items[index];
+        let maildir = Maildir::from(item.folder.clone());
+        let id = item.item.id().to_string();
+        // only mails in cur carry flags
+        if item.new {
+            maildir.move_new_to_cur(&id)?;
+            item.new = false;
+        }
+        if item.item.is_flagged() {
+            maildir.remove_flags(&id, "F")?;
+        } else {
+            maildir.add_flags(&id, "F")?;
+        }
+        Self::reload_entry(&maildir, &mut self.mail_items[index].item, &id)?;
+        self.sync_maillist();
+        Ok(())
+    }
+
+    /// The file name of a mail changes with its flags, so look it up again
+    fn reload_entry(maildir: &Maildir, entry: &mut maildir::MailEntry, id: &str) -> Result<()> {
+        *entry = maildir
+            .find(id)
+            .ok_or_else(|| anyhow!("Mail {id} is gone from {}", maildir.path().display()))?;
+        Ok(())
+    }
+}
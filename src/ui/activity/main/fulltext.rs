@@ -0,0 +1,244 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{
+    MailEntryNewOrRead, TermailActivity, COMPONENT_INPUT_FULLTEXT, COMPONENT_TABLE_MAILLIST,
+};
+use crate::index::{Index, MailFields};
+use anyhow::{anyhow, Result};
+use log::error;
+use maildir::{MailEntry, Maildir};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use tui_realm_stdlib::{Input, InputPropsBuilder};
+use tui_realm_treeview::Node;
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::PropsBuilder;
+
+const MAX_RESULTS: usize = 200;
+// all the mails of a contact are wanted, within reason
+const MAX_CONTACT_RESULTS: usize = 2000;
+
+impl TermailActivity {
+    /// ### `start_indexing`
+    ///
+    /// Start the indexing thread, which loads the saved index before anything else, then
+    /// bring every folder of the tree up to date
+    pub(super) fn start_indexing(&mut self) {
+        let index = Arc::clone(&self.index);
+        let (tx, rx) = mpsc::channel::<Vec<PathBuf>>();
+        thread::spawn(move || {
+            let loaded = Index::load();
+            match index.lock() {
+                Ok(mut index) => *index = loaded,
+                Err(e) => {
+                    error!("{e}");
+                    return;
+                }
+            }
+            // one job at a time, so that two of them never save over each other
+            for folders in rx {
+                update_index(&index, &folders);
+            }
+        });
+        self.index_jobs = Some(tx);
+        let mut folders = Vec::new();
+        maildirs(self.tree.root(), &mut folders);
+        self.index_folders(folders);
+    }
+
+    /// ### `index_folders`
+    ///
+    /// Index the new mails of `folders` in the background
+    pub(super) fn index_folders(&self, folders: Vec<PathBuf>) {
+        if let Some(jobs) = &self.index_jobs {
+            jobs.send(folders).ok();
+        }
+    }
+
+    pub(super) fn mount_fulltext(&mut self) {
+        self.view.mount(
+            COMPONENT_INPUT_FULLTEXT,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightGreen)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightGreen)
+                    .with_label(
+                        "Search all mail (\"a phrase\", from: to: subject: body:)",
+                        Alignment::Left,
+                    )
+                    .with_value(self.fulltext.clone().unwrap_or_default())
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_FULLTEXT);
+    }
+
+    pub(super) fn umount_fulltext(&mut self) {
+        self.view.umount(COMPONENT_INPUT_FULLTEXT);
+        self.view.active(COMPONENT_TABLE_MAILLIST);
+    }
+
+    /// ### `fulltext_search`
+    ///
+    /// Show the mails matching `query` in the mail list, best first
+    pub(super) fn fulltext_search(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        let hits = lock(&self.index).and_then(|index| index.search(query, MAX_RESULTS));
+        let hits = match hits {
+            Ok(hits) => hits,
+            Err(e) => {
+                self.mount_error(&format!("Bad search \"{query}\": {e}"));
+                return;
+            }
+        };
+        let wanted: Vec<(PathBuf, String)> = hits
+            .into_iter()
+            .map(|hit| (PathBuf::from(hit.folder), hit.id))
+            .collect();
+        self.mail_items = MailEntryNewOrRead::find_all(&wanted);
+        self.fulltext = Some(query.to_string());
+        self.limit = None;
+        self.match_limit();
+        self.build_threads();
+        self.sync_maillist();
+        self.select_maillist_row(0);
+    }
+}
+
+impl TermailActivity {
+    /// ### `mails_from`
+    ///
+    /// Show the mails sent from any of `addresses` in the mail list, newest first
+    pub(super) fn mails_from(&mut self, name: &str, addresses: &[String]) -> Result<()> {
+        let mut wanted: Vec<(PathBuf, String)> = Vec::new();
+        {
+            let index = lock(&self.index)?;
+            for address in addresses {
+                for hit in index.search(&format!("from:\"{address}\""), MAX_CONTACT_RESULTS)? {
+                    let mail = (PathBuf::from(hit.folder), hit.id);
+                    if !wanted.contains(&mail) {
+                        wanted.push(mail);
+                    }
+                }
+            }
+        }
+        let mut items = MailEntryNewOrRead::find_all(&wanted);
+        // the phrase also matches longer addresses
+        items.retain(|item| {
+            let from = item.from.to_lowercase();
+            addresses.iter().any(|a| from.contains(&a.to_lowercase()))
+        });
+        self.mail_items = items;
+        self.fulltext = Some(format!("from {name}"));
+        self.limit = None;
+        self.match_limit();
+        self.sort_mail_items();
+        self.build_threads();
+        self.sync_maillist();
+        self.select_maillist_row(0);
+        Ok(())
+    }
+}
+
+/// Folders of the tree which are maildirs
+pub(super) fn maildirs(node: &Node, folders: &mut Vec<PathBuf>) {
+    let path = Path::new(node.id());
+    if path.join("cur").is_dir() {
+        folders.push(path.to_path_buf());
+    }
+    let mut n = 0;
+    while let Some(child) = node.node_by_route(&[n]) {
+        maildirs(child, folders);
+        n += 1;
+    }
+}
+
+fn lock(index: &Mutex<Index>) -> Result<MutexGuard<'_, Index>> {
+    index
+        .lock()
+        .map_err(|_| anyhow!("The search index is not available"))
+}
+
+fn update_index(index: &Mutex<Index>, folders: &[PathBuf]) {
+    for folder in folders {
+        if let Err(e) = index_folder(index, folder) {
+            error!("Failed to index {}: {e}", folder.display());
+        }
+    }
+    match lock(index) {
+        Ok(mut index) if index.changed() => {
+            index.compact();
+            if let Err(e) = index.save() {
+                error!("Failed to save index: {e}");
+            }
+        }
+        Ok(_) => {}
+        Err(e) => error!("{e}"),
+    }
+}
+
+/// Add the mails of `folder` which are not indexed, and forget the ones which are gone.
+/// Mails are parsed without holding the lock, so that searching stays possible.
+fn index_folder(index: &Mutex<Index>, folder: &Path) -> Result<()> {
+    let maildir = Maildir::from(folder.to_path_buf());
+    let name = folder.to_string_lossy().to_string();
+    let entries: Vec<MailEntry> = maildir
+        .list_new()
+        .chain(maildir.list_cur())
+        .filter_map(std::result::Result::ok)
+        .collect();
+    let ids: Vec<String> = entries.iter().map(|e| e.id().to_string()).collect();
+    let missing: HashSet<String> = lock(index)?
+        .missing(&name, &ids)
+        .into_iter()
+        .cloned()
+        .collect();
+
+    let mut fields = Vec::new();
+    for entry in entries.iter().filter(|e| missing.contains(e.id())) {
+        let Ok(data) = fs::read(entry.path()) else {
+            continue;
+        };
+        if let Ok(mail) = mailparse::parse_mail(&data) {
+            fields.push((entry.id(), MailFields::from_mail(&mail)));
+        }
+    }
+
+    let ids: HashSet<String> = ids.into_iter().collect();
+    let mut index = lock(index)?;
+    for (id, fields) in &fields {
+        index.add(&name, id, fields);
+    }
+    index.retain(&name, &ids);
+    drop(index);
+    Ok(())
+}
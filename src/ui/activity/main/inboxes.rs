@@ -0,0 +1,113 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::fulltext::maildirs;
+use super::{MailEntryNewOrRead, TermailActivity};
+use maildir::Maildir;
+use std::path::{Path, PathBuf};
+use std::thread;
+use tui_realm_treeview::Node;
+
+/// Tree node id of the unified inbox
+pub(super) const INBOXES_NODE: &str = "inboxes:";
+
+impl TermailActivity {
+    /// ### `inbox_folders`
+    ///
+    /// Folders merged into All Inboxes: the configured ones, or every folder named INBOX
+    pub(super) fn inbox_folders(&self, root: &Node) -> Vec<PathBuf> {
+        if !self.config.inboxes.is_empty() {
+            return self
+                .config
+                .inboxes
+                .iter()
+                .map(|folder| self.path.join(shellexpand::tilde(folder).as_ref()))
+                .collect();
+        }
+        let mut folders = Vec::new();
+        maildirs(root, &mut folders);
+        folders.retain(|folder| {
+            folder
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case("inbox"))
+        });
+        folders
+    }
+
+    /// ### `inboxes_node`
+    ///
+    /// Tree node for All Inboxes, with the number of new mails
+    pub(super) fn inboxes_node(&self, root: &Node) -> Option<Node> {
+        let folders = self.inbox_folders(root);
+        if folders.is_empty() {
+            return None;
+        }
+        let new: usize = folders
+            .iter()
+            .map(|folder| Maildir::from(folder.clone()).count_new())
+            .sum();
+        let name = if new > 0 {
+            format!("All Inboxes({new})")
+        } else {
+            "All Inboxes".to_string()
+        };
+        Some(Node::new(INBOXES_NODE.to_string(), name))
+    }
+
+    /// ### `load_inboxes`
+    ///
+    /// Show the mails of all inbox folders in one list
+    pub(super) fn load_inboxes(&mut self) {
+        let folders = self.inbox_folders(self.tree.root());
+        // the sort order is remembered for the unified inbox itself
+        self.current_maildir = Maildir::from(INBOXES_NODE);
+        self.limit = None;
+        self.fulltext = None;
+        self.notmuch_query = None;
+        self.unified = true;
+        self.index_folders(folders.clone());
+        let tx = self.sender_mail_items.clone();
+        thread::spawn(move || {
+            let mail_items = folders
+                .iter()
+                .flat_map(|folder| list_folder(folder))
+                .collect();
+            tx.send(Ok(mail_items)).ok();
+        });
+    }
+}
+
+/// ### `list_folder`
+///
+/// New and read mails of a maildir
+pub(super) fn list_folder(folder: &Path) -> Vec<MailEntryNewOrRead> {
+    let maildir = Maildir::from(folder.to_path_buf());
+    let new = maildir.list_new().filter_map(Result::ok).map(|e| (e, true));
+    let cur = maildir
+        .list_cur()
+        .filter_map(Result::ok)
+        .map(|e| (e, false));
+    new.chain(cur)
+        .map(|(entry, new)| MailEntryNewOrRead::new(entry, new, folder))
+        .collect()
+}
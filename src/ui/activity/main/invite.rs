@@ -0,0 +1,146 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{Invite, TermailActivity};
+use crate::ical::{Calendar, PartStat, Person};
+use crate::outbox::Queued;
+use anyhow::{anyhow, Result};
+use mailparse::{addrparse, MailAddr, MailHeaderMap, ParsedMail};
+use tuirealm::props::TextSpan;
+use tuirealm::tui::style::Color;
+
+impl TermailActivity {
+    /// ### `find_invite`
+    ///
+    /// Parse the first calendar part of the mail which carries an event
+    pub(super) fn find_invite(mail: &ParsedMail) -> Option<Invite> {
+        let calendar = Self::find_calendar(mail)?;
+        let request = calendar.method.as_deref().unwrap_or("REQUEST") == "REQUEST";
+        let event = calendar.events.into_iter().next()?;
+
+        let mut addresses = Vec::new();
+        for header in &["Delivered-To", "To", "Cc"] {
+            for value in mail.headers.get_all_values(header) {
+                if let Ok(list) = addrparse(&value) {
+                    for addr in list.iter() {
+                        match addr {
+                            MailAddr::Single(info) => addresses.push(info.addr.clone()),
+                            MailAddr::Group(group) => {
+                                addresses.extend(group.addrs.iter().map(|i| i.addr.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(Invite {
+            event,
+            addresses,
+            request,
+        })
+    }
+
+    /// ### `find_calendar`
+    ///
+    /// Walk the MIME tree for a `text/calendar` or `application/ics` part with an event,
+    /// wherever it is, like the last alternative of Outlook and Google invitations
+    fn find_calendar(mail: &ParsedMail) -> Option<Calendar> {
+        let mimetype = mail.ctype.mimetype.to_lowercase();
+        if mail.subparts.is_empty()
+            && (mimetype == "text/calendar" || mimetype == "application/ics")
+        {
+            let data = mail.get_body_raw().ok()?;
+            return Calendar::parse(&String::from_utf8_lossy(&data))
+                .ok()
+                .filter(|calendar| !calendar.events.is_empty());
+        }
+        mail.subparts.iter().find_map(Self::find_calendar)
+    }
+
+    pub(super) fn invite_card(invite: &Invite) -> Vec<TextSpan> {
+        let mut card: Vec<TextSpan> = invite
+            .event
+            .summary_card()
+            .iter()
+            .map(|line| TextSpan::new(line).fg(Color::LightCyan))
+            .collect();
+        if invite.request {
+            card.push(
+                TextSpan::new("Press <a> accept, <d> decline, <t> tentative")
+                    .bold()
+                    .fg(Color::Cyan),
+            );
+        }
+        card
+    }
+
+    /// ### `reply_invite`
+    ///
+    /// Send the iTIP reply for the invitation of the current mail to its organizer, from
+    /// the identity of the attendee we are
+    pub fn reply_invite(&mut self, partstat: PartStat) -> Result<()> {
+        let invite = self
+            .invite
+            .as_ref()
+            .ok_or_else(|| anyhow!("This mail is not an invitation."))?;
+        if !invite.request {
+            return Err(anyhow!("This invitation asks for no reply."));
+        }
+        let me = invite
+            .event
+            .find_attendee(&invite.addresses)
+            .map(|a| a.person.clone())
+            .or_else(|| {
+                invite.addresses.first().map(|email| Person {
+                    name: None,
+                    email: email.clone(),
+                })
+            })
+            .ok_or_else(|| anyhow!("Can't tell which attendee you are."))?;
+        let organizer = invite
+            .event
+            .organizer
+            .as_ref()
+            .ok_or_else(|| anyhow!("The invitation has no organizer."))?
+            .email
+            .clone();
+        let (from, address) = self.identity_for(&me.email).map_or_else(
+            || (me.to_string(), me.email.clone()),
+            |identity| (identity.mailbox(), identity.address.clone()),
+        );
+        let message = invite.event.reply_message(&from, &me, partstat)?;
+
+        let account = self
+            .account_for(&address)
+            .ok_or_else(|| anyhow!("No account is set up to send from {address}"))?
+            .clone();
+        let sent = account.save_sent.then(|| self.sent_folder(&account));
+        let queued = Queued::new(&account.name, &address, &[organizer], sent, &message);
+        let status = self.deliver(&queued, false)?;
+        self.show_message(
+            "Invitation",
+            &format!("{} reply: {status}", partstat.subject_prefix()),
+        );
+        Ok(())
+    }
+}
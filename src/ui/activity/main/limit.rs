@@ -0,0 +1,235 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{MailEntryNewOrRead, TermailActivity, COMPONENT_INPUT_LIMIT, COMPONENT_TABLE_MAILLIST};
+use crate::index::text_body;
+use crate::pattern::{Matchable, Pattern};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use tui_realm_stdlib::{Input, InputPropsBuilder};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::PropsBuilder;
+
+impl Matchable for MailEntryNewOrRead {
+    fn sender(&self) -> &str {
+        &self.from
+    }
+
+    fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    fn recipients(&self) -> &str {
+        &self.to
+    }
+
+    fn body(&self) -> String {
+        read_body(self.item.path())
+    }
+
+    fn date(&self) -> i64 {
+        self.date
+    }
+
+    fn is_new(&self) -> bool {
+        self.is_unread()
+    }
+
+    fn is_flagged(&self) -> bool {
+        self.item.is_flagged()
+    }
+}
+
+/// What a limit looks at in a mail, for matching bodies in the background
+struct LimitMail {
+    from: String,
+    subject: String,
+    to: String,
+    path: PathBuf,
+    date: i64,
+    new: bool,
+    flagged: bool,
+}
+
+impl From<&MailEntryNewOrRead> for LimitMail {
+    fn from(mail: &MailEntryNewOrRead) -> Self {
+        Self {
+            from: mail.from.clone(),
+            subject: mail.subject.clone(),
+            to: mail.to.clone(),
+            path: mail.item.path().clone(),
+            date: mail.date,
+            new: mail.is_unread(),
+            flagged: mail.item.is_flagged(),
+        }
+    }
+}
+
+impl Matchable for LimitMail {
+    fn sender(&self) -> &str {
+        &self.from
+    }
+
+    fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    fn recipients(&self) -> &str {
+        &self.to
+    }
+
+    fn body(&self) -> String {
+        read_body(&self.path)
+    }
+
+    fn date(&self) -> i64 {
+        self.date
+    }
+
+    fn is_new(&self) -> bool {
+        self.new
+    }
+
+    fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+}
+
+fn read_body(path: &Path) -> String {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| {
+            mailparse::parse_mail(&data)
+                .ok()
+                .map(|mail| text_body(&mail))
+        })
+        .unwrap_or_default()
+}
+
+impl TermailActivity {
+    pub(super) fn mount_limit(&mut self) {
+        let value = self
+            .limit
+            .as_ref()
+            .map(|(text, _)| text.clone())
+            .unwrap_or_default();
+        self.view.mount(
+            COMPONENT_INPUT_LIMIT,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightBlue)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightBlue)
+                    .with_label(
+                        "Limit to (~f ~s ~t ~b ~d ~N ~F ! | ( )), empty for all",
+                        Alignment::Left,
+                    )
+                    .with_value(value)
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_LIMIT);
+    }
+
+    pub(super) fn umount_limit(&mut self) {
+        self.view.umount(COMPONENT_INPUT_LIMIT);
+        self.view.active(COMPONENT_TABLE_MAILLIST);
+    }
+
+    /// ### `set_limit`
+    ///
+    /// Show only the mails matching `text`, or all of them when it is empty
+    pub(super) fn set_limit(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            self.limit = None;
+        } else {
+            match Pattern::parse(text) {
+                Ok(pattern) => self.limit = Some((text.to_string(), Arc::new(pattern))),
+                Err(e) => {
+                    self.mount_error(&format!("Bad limit pattern \"{text}\" at {e}"));
+                    return;
+                }
+            }
+        }
+        self.match_limit();
+        self.build_rows();
+        self.sync_maillist();
+        self.select_maillist_row(0);
+    }
+
+    /// ### `match_limit`
+    ///
+    /// Check the limit against every mail, whenever the mails change. Patterns reading
+    /// the bodies are matched in the background, and no mail is shown until they are done.
+    pub(super) fn match_limit(&mut self) {
+        self.limit_generation += 1;
+        self.limit_pending = false;
+        self.limit_match = match &self.limit {
+            Some((_, pattern)) if pattern.needs_body() => {
+                let pattern = Arc::clone(pattern);
+                let mails: Vec<LimitMail> = self.mail_items.iter().map(LimitMail::from).collect();
+                let generation = self.limit_generation;
+                let tx = self.sender_limit.clone();
+                thread::spawn(move || {
+                    let matches = mails.iter().map(|mail| pattern.matches(mail)).collect();
+                    tx.send((generation, matches)).ok();
+                });
+                self.limit_pending = true;
+                Vec::new()
+            }
+            Some((_, pattern)) => self
+                .mail_items
+                .iter()
+                .map(|item| pattern.matches(item))
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    /// ### `update_limit`
+    ///
+    /// Show the mails matching a limit once their bodies are read
+    pub fn update_limit(&mut self) {
+        if let Ok((generation, matches)) = self.receiver_limit.try_recv() {
+            if generation != self.limit_generation {
+                return;
+            }
+            self.limit_match = matches;
+            self.limit_pending = false;
+            self.build_rows();
+            self.sync_maillist();
+            self.select_maillist_row(0);
+            self.redraw = true;
+        }
+    }
+
+    /// ### `in_limit`
+    ///
+    /// Whether the mail at `index` is shown with the current limit
+    pub(super) fn in_limit(&self, index: usize) -> bool {
+        self.limit.is_none() || self.limit_match.get(index).copied().unwrap_or(false)
+    }
+}
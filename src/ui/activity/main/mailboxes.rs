@@ -30,14 +30,20 @@ use tui_realm_treeview::{Node, Tree};
 impl TermailActivity {
     pub fn scan_dir(&mut self, p: &Path) {
         self.path = p.to_path_buf();
-        self.tree = Tree::new(Self::dir_tree(p, 2));
+        let mut root = Self::dir_tree(p, 2);
+        if let Some(inboxes) = self.inboxes_node(&root) {
+            root.add_child(inboxes);
+        }
+        if let Some(queries) = self.queries_node() {
+            root.add_child(queries);
+        }
+        self.tree = Tree::new(root);
     }
 
     pub fn dir_tree(p: &Path, depth: usize) -> Node {
-        let mut name: String = match p.file_name() {
-            None => "/".to_string(),
-            Some(n) => n.to_string_lossy().into_owned(),
-        };
+        let mut name: String = p
+            .file_name()
+            .map_or_else(|| "/".to_string(), |n| n.to_string_lossy().into_owned());
 
         let mut new_items_total = 0;
         if p.is_dir() {
@@ -22,130 +22,216 @@
  * SOFTWARE.
  */
 use super::{
-    MailEntryNewOrRead, TermailActivity, COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL,
-    COMPONENT_TREEVIEW_MAILBOXES,
+    Attachment, MailEntryNewOrRead, TermailActivity, COMPONENT_INPUT_SEARCH,
+    COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL, COMPONENT_TREEVIEW_MAILBOXES,
 };
 // use std::path::Path;
 // use tui_realm_treeview::{Node, Tree};
 // use tuirealm::{Payload, PropPayload, PropValue, PropsBuilder, Value};
+use super::columns::truncate;
+use super::inboxes::{list_folder, INBOXES_NODE};
+use super::reader::with_matches;
+use crate::config::{ImageProtocol, MaillistColumn};
+use crate::mailcap::Mailcap;
+use crate::notmuch::NODE_PREFIX;
 use anyhow::{anyhow, Result};
-use chrono::prelude::DateTime;
 use chrono::Local;
-use maildir::Maildir;
-use mailparse::{MailHeaderMap, ParsedMail};
+use maildir::{MailEntry, Maildir};
+use mailparse::{DispositionType, MailHeaderMap, ParsedMail};
 // use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
 use std::thread;
-use std::time::{Duration, UNIX_EPOCH};
 use tui_realm_stdlib::TablePropsBuilder;
 use tui_realm_stdlib::TextareaPropsBuilder;
 use tui_realm_treeview::TreeViewPropsBuilder;
 use tuirealm::props::{TableBuilder, TextSpan};
+use tuirealm::tui::layout::Alignment;
 use tuirealm::tui::style::Color;
 use tuirealm::PropsBuilder;
 
 impl TermailActivity {
     pub fn load_mailbox(&mut self, node_id: &str) {
+        if let Some(query) = node_id.strip_prefix(NODE_PREFIX) {
+            if let Err(e) = self.load_notmuch(query) {
+                self.mount_error(&e.to_string());
+            }
+            return;
+        }
+        if node_id == INBOXES_NODE {
+            self.load_inboxes();
+            return;
+        }
+        self.notmuch_query = None;
+        self.unified = false;
         let mail_dir = Maildir::from(node_id);
         self.current_maildir = mail_dir;
-        let mail_dir = Maildir::from(node_id);
-        let mut mail_items = Vec::new();
+        // a limit is for one folder
+        self.limit = None;
+        self.fulltext = None;
+        self.index_folders(vec![PathBuf::from(node_id)]);
+        let folder = PathBuf::from(node_id);
         let tx = self.sender_mail_items.clone();
         thread::spawn(move || {
-            let mail_new_entries = mail_dir.list_new();
-            let mail_cur_entries = mail_dir.list_cur();
-
-            // Add new items
-            for record in mail_new_entries {
-                if record.is_err() {
-                    continue;
-                }
-                let mut record = record.unwrap();
-                mail_items.push(MailEntryNewOrRead {
-                    date: record.date().unwrap_or(0),
-                    item: record,
-                    new: true,
-                });
-            }
-
-            // Add read items
-            for record in mail_cur_entries {
-                if record.is_err() {
-                    continue;
-                }
-                let mut record = record.unwrap();
-                mail_items.push(MailEntryNewOrRead {
-                    date: record.date().unwrap_or(0),
-                    item: record,
-                    new: false,
-                });
-            }
-            mail_items.sort_by(|b, a| a.date.cmp(&b.date));
-            mail_items.sort_by(|b, a| a.new.cmp(&b.new));
-            tx.send(mail_items).ok();
+            tx.send(Ok(list_folder(&folder))).ok();
         });
     }
 
     pub fn sync_maillist(&mut self) {
         let mut table: TableBuilder = TableBuilder::default();
+        let columns = self.columns();
+        let widths = self.column_widths();
+        let now = Local::now().timestamp();
         // Add new items
-        for (idx, record) in self.mail_items.iter_mut().enumerate() {
+        for (idx, row) in self.rows.iter().enumerate() {
             if idx > 0 {
                 table.add_row();
             }
 
-            let date = record.date;
-            let header = record.item.headers().unwrap();
-            let sender = header
-                .get_first_value("From")
-                .unwrap_or_else(|| "No Sender".to_string());
-            let subject = header
-                .get_first_value("Subject")
-                .unwrap_or_else(|| "No Subject".to_string());
-            // Creates a new SystemTime from the specified number of whole seconds
-            let date_u64 = if date.is_negative() {
-                0
-            } else {
-                date.unsigned_abs()
-            };
-            // let result = if a > b { a } else { b };
-            let d = UNIX_EPOCH + Duration::from_secs(date_u64);
-            // Create DateTime from SystemTime
-            let datetime = DateTime::<Local>::from(d);
-            // Formats the combined date and time with the specified format string.
-            let timestamp_str = datetime.format("%y-%m-%d %H:%M").to_string();
-            table
-                .add_col(TextSpan::new(idx.to_string()))
-                .add_col(TextSpan::new(timestamp_str).fg(Color::LightYellow));
-
-            if record.new {
-                table
-                    .add_col(TextSpan::new(sender).bold().fg(Color::Green))
-                    .add_col(TextSpan::new(subject).bold().fg(Color::Green));
-            } else {
-                table
-                    .add_col(TextSpan::new(sender))
-                    .add_col(TextSpan::new(subject));
+            let record = &self.mail_items[row.index];
+            for (column, width) in columns.iter().zip(&widths) {
+                let text = self.column_text(column.column, idx, row, now);
+                let span = TextSpan::new(truncate(&text, *width));
+                let span = match column.column {
+                    MaillistColumn::Date => span.fg(Color::LightYellow),
+                    MaillistColumn::Preview | MaillistColumn::List => span.fg(Color::Gray),
+                    MaillistColumn::From | MaillistColumn::Subject if record.new => {
+                        span.bold().fg(Color::Green)
+                    }
+                    _ => span,
+                };
+                table.add_col(span);
             }
         }
 
         let table = table.build();
 
+        let title = match (&self.fulltext, &self.limit) {
+            (Some(query), _) => format!("Search: {query} ({} found)", self.mail_items.len()),
+            (None, Some((text, _))) if self.limit_pending => {
+                format!("Mail List (limit: {text}, reading the mails)")
+            }
+            (None, Some((text, _))) => format!("Mail List (limit: {text})"),
+            (None, None) => "Mail List".to_string(),
+        };
+        let columns = self.columns();
+        let header: Vec<&str> = columns.iter().map(|c| c.column.title()).collect();
+        let widths: Vec<u16> = columns.iter().map(|c| c.width).collect();
         if let Some(props) = self.view.get_props(COMPONENT_TABLE_MAILLIST) {
-            let props = TablePropsBuilder::from(props).with_table(table).build();
+            let props = TablePropsBuilder::from(props)
+                .with_table(table)
+                .with_header(&header)
+                .with_widths(&widths)
+                .with_title(title, Alignment::Left)
+                .build();
             let msg = self.view.update(COMPONENT_TABLE_MAILLIST, props);
-            self.update(&msg);
-            self.view.active(COMPONENT_TABLE_MAILLIST);
+            self.update(msg.as_ref());
         }
     }
 
-    pub fn load_mail(&mut self, index: usize) -> Result<()> {
+    pub fn load_mail(&mut self, row: usize) -> Result<()> {
+        let index = self
+            .rows
+            .get(row)
+            .ok_or_else(|| anyhow!("error get mail_item"))?
+            .index;
         let mail_item = self
             .mail_items
             .get_mut(index)
             .ok_or_else(|| anyhow!("error get mail_item"))?;
+        let reading = (mail_item.folder.clone(), mail_item.item.id().to_string());
+        self.reading = Some(reading.clone());
+        let path = mail_item.item.path().clone();
         let parsed_mail = mail_item.item.parsed()?;
-        let content = Self::get_body_recursive(&parsed_mail)?;
-        let mut vec_lines: Vec<TextSpan> = vec![];
+        let mut attachments = Vec::new();
+        // mailcap viewers may be slow, their output is added once they are done
+        let content = Self::get_body_recursive(&parsed_mail, None, &mut attachments)?;
+        if !attachments.is_empty() {
+            let mailcap = self.mailcap.clone();
+            let tx = self.sender_mail_body.clone();
+            thread::spawn(move || {
+                let content = std::fs::read(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|data| {
+                        let parsed = mailparse::parse_mail(&data)?;
+                        Self::get_body_recursive(&parsed, Some(&mailcap), &mut Vec::new())
+                    });
+                tx.send((reading, content)).ok();
+            });
+        }
+        self.attachments = attachments;
+        self.attachment_index = 0;
+        self.images = if self.image_protocol == ImageProtocol::None {
+            Vec::new()
+        } else {
+            Self::decode_images(&self.attachments)
+        };
+        self.image_index = 0;
+        // the sequence drawn last was made for an image of the mail before
+        self.image_sequence = None;
+        self.invite = Self::find_invite(&parsed_mail);
+        self.search.query.clear();
+        self.search.matches.clear();
+        self.view.umount(COMPONENT_INPUT_SEARCH);
+        self.show_mail_body(&content)?;
+
+        let mail_item = &mut self.mail_items[index];
+
+        if mail_item.tags.iter().any(|t| t == "unread") {
+            mail_item.tags.retain(|t| t != "unread");
+            let message_id = mail_item.message_id.clone();
+            self.untag_unread(message_id.as_deref());
+            self.sync_maillist();
+        }
+        if self.mail_items[index].is_unread() {
+            // update mail list
+            self.mark_read(index)?;
+            self.sync_maillist();
+
+            // update mail box tree view
+            let path = self.path.clone();
+            self.scan_dir(&path);
+            if let Some(props) = self.view.get_props(COMPONENT_TREEVIEW_MAILBOXES) {
+                let props = TreeViewPropsBuilder::from(props)
+                    .with_tree_and_depth(self.tree.root(), 2)
+                    .build();
+                self.view.update(COMPONENT_TREEVIEW_MAILBOXES, props);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// ### `update_mail_body`
+    ///
+    /// Show the mail again with the output of its mailcap viewers, if it is still in the reader
+    pub fn update_mail_body(&mut self) {
+        if let Ok((reading, content)) = self.receiver_mail_body.try_recv() {
+            if self.reading.as_ref() != Some(&reading) {
+                return;
+            }
+            let shown = content.and_then(|content| self.show_mail_body(&content));
+            if let Err(e) = shown {
+                self.mount_error(&e.to_string());
+            }
+            if !self.search.query.is_empty() {
+                let query = self.search.query.clone();
+                self.update_search(&query);
+            }
+            self.redraw = true;
+        }
+    }
+
+    /// ### `show_mail_body`
+    ///
+    /// Put the text of the mail, below the invitation card if any, in the reader
+    fn show_mail_body(&mut self, content: &str) -> Result<()> {
+        let mut vec_lines: Vec<TextSpan> = self
+            .invite
+            .as_ref()
+            .map(Self::invite_card)
+            .unwrap_or_default();
         for line in content.split('\n') {
             let trimed = line.trim();
             if !trimed.is_empty() {
@@ -153,6 +239,8 @@ impl TermailActivity {
             }
         }
 
+        self.mail_lines.clone_from(&vec_lines);
+
         // let mut file = std::fs::File::create("data.txt").expect("create failed");
         // file.write_all(&parsed_mail.get_body_raw().unwrap())
         // .expect("write failed");
@@ -165,48 +253,269 @@ impl TermailActivity {
             // .with_texts(vec![TextSpan::new(body)])
             .with_texts(vec_lines)
             .build();
+        let props = with_matches(props, &[], None);
         self.view.update(COMPONENT_TEXTAREA_MAIL, props);
-
-        if mail_item.new {
-            // update mail list
-            self.current_maildir.move_new_to_cur(mail_item.item.id())?;
-            mail_item.new = false;
-            self.sync_maillist();
-
-            // update mail box tree view
-            let path = self.path.clone();
-            self.scan_dir(&path);
-            if let Some(props) = self.view.get_props(COMPONENT_TREEVIEW_MAILBOXES) {
-                let props = TreeViewPropsBuilder::from(props)
-                    .with_tree_and_depth(self.tree.root(), 2)
-                    .build();
-                self.view.update(COMPONENT_TREEVIEW_MAILBOXES, props);
-            }
-        }
-
         Ok(())
     }
 
-    fn get_body_recursive(mail: &ParsedMail) -> Result<String> {
+    /// ### `get_body_recursive`
+    ///
+    /// Text of the mail, with the non text parts collected as attachments. Without a
+    /// mailcap, they are only listed.
+    fn get_body_recursive(
+        mail: &ParsedMail,
+        mailcap: Option<&Mailcap>,
+        attachments: &mut Vec<Attachment>,
+    ) -> Result<String> {
         let mut content = String::new();
-        let parts_quantity = mail.subparts.len();
-        if parts_quantity == 0 {
-            if mail.ctype.mimetype.starts_with("text/plain") {
+        if mail.subparts.is_empty() {
+            let mimetype = mail.ctype.mimetype.to_lowercase();
+            if mimetype.starts_with("text/plain") {
                 content = mail.get_body()?;
-            } else if mail.ctype.mimetype.starts_with("text/html") {
+            } else if mimetype.starts_with("text/html") {
                 let frag = scraper::Html::parse_fragment(&mail.get_body()?);
                 for node in frag.tree {
                     if let scraper::node::Node::Text(text) = node {
                         content.push_str(&text.text);
                     }
                 }
+            } else {
+                content = Self::get_part_with_mailcap(mail, mailcap, attachments)?;
+            }
+        } else if mail
+            .ctype
+            .mimetype
+            .eq_ignore_ascii_case("multipart/alternative")
+        {
+            // Show only the preferred alternative; other non text alternatives, such as
+            // an invitation's text/calendar part, stay reachable as attachments
+            let preferred = ["text/plain", "text/html"]
+                .iter()
+                .find_map(|mimetype| {
+                    mail.subparts
+                        .iter()
+                        .position(|part| part.ctype.mimetype.eq_ignore_ascii_case(mimetype))
+                })
+                .unwrap_or(mail.subparts.len() - 1);
+            content.push_str(&Self::get_body_recursive(
+                &mail.subparts[preferred],
+                mailcap,
+                attachments,
+            )?);
+            for (i, part) in mail.subparts.iter().enumerate() {
+                let mimetype = part.ctype.mimetype.to_lowercase();
+                if i != preferred
+                    && part.subparts.is_empty()
+                    && mimetype != "text/plain"
+                    && mimetype != "text/html"
+                {
+                    content.push_str(&Self::get_part_with_mailcap(part, None, attachments)?);
+                }
             }
         } else {
-            for i in 0..parts_quantity - 1 {
-                content.push_str(&Self::get_body_recursive(&mail.subparts[i])?);
+            for part in &mail.subparts {
+                content.push_str(&Self::get_body_recursive(part, mailcap, attachments)?);
             }
         }
 
         Ok(content)
     }
+
+    /// ### `get_part_with_mailcap`
+    ///
+    /// Render a non text part through a `copiousoutput` mailcap entry, and remember it
+    /// as an attachment so that it can be opened with an interactive entry later.
+    fn get_part_with_mailcap(
+        mail: &ParsedMail,
+        mailcap: Option<&Mailcap>,
+        attachments: &mut Vec<Attachment>,
+    ) -> Result<String> {
+        let mimetype = mail.ctype.mimetype.to_lowercase();
+        let data = mail.get_body_raw()?;
+        let disposition = mail.get_content_disposition();
+        let filename = disposition
+            .params
+            .get("filename")
+            .or_else(|| mail.ctype.params.get("name"))
+            .cloned();
+
+        let mut content = String::new();
+        if let Some(entry) = mailcap.and_then(|m| m.lookup(&mimetype, &mail.ctype.params, true)) {
+            match entry.render(&data, &mimetype, &mail.ctype.params, filename.as_deref()) {
+                Ok(output) => content.push_str(&output),
+                Err(e) => {
+                    let _ = writeln!(content, "[{e}]");
+                }
+            }
+        } else if mimetype.starts_with("text/") && mimetype != "text/calendar" {
+            content.push_str(&mail.get_body()?);
+        }
+
+        let _ = writeln!(
+            content,
+            "\n[Attachment {}: {} ({}, {} bytes)]",
+            attachments.len() + 1,
+            filename.as_deref().unwrap_or("unnamed"),
+            mimetype,
+            data.len()
+        );
+        attachments.push(Attachment {
+            mimetype,
+            params: mail.ctype.params.clone(),
+            filename,
+            data,
+        });
+        Ok(content)
+    }
+}
+
+const PREVIEW_LENGTH: usize = 200;
+
+impl MailEntryNewOrRead {
+    /// ### `location`
+    ///
+    /// Maildir and id of a mail file
+    pub(super) fn location(file: &Path) -> Option<(PathBuf, String)> {
+        let folder = file.parent()?.parent()?.to_path_buf();
+        let name = file.file_name()?.to_string_lossy();
+        let id = name.split(":2,").next().unwrap_or_default().to_string();
+        Some((folder, id))
+    }
+
+    /// ### `find_all`
+    ///
+    /// Mails from their maildir and id, in the same order, reading every maildir once
+    pub(super) fn find_all(wanted: &[(PathBuf, String)]) -> Vec<Self> {
+        let mut entries: HashMap<(PathBuf, String), (MailEntry, bool)> = HashMap::new();
+        let folders: HashSet<&PathBuf> = wanted.iter().map(|(folder, _)| folder).collect();
+        for folder in folders {
+            let maildir = Maildir::from(folder.clone());
+            let new = maildir.list_new().filter_map(Result::ok).map(|e| (e, true));
+            let cur = maildir
+                .list_cur()
+                .filter_map(Result::ok)
+                .map(|e| (e, false));
+            for (entry, is_new) in new.chain(cur) {
+                entries.insert((folder.clone(), entry.id().to_string()), (entry, is_new));
+            }
+        }
+        wanted
+            .iter()
+            .filter_map(|key| {
+                let (entry, new) = entries.remove(key)?;
+                Some(Self::new(entry, new, &key.0))
+            })
+            .collect()
+    }
+
+    pub fn is_unread(&self) -> bool {
+        self.new || !self.item.is_seen()
+    }
+
+    /// ### `new`
+    ///
+    /// Read the headers and a summary of the mail, used by the mail list columns
+    pub(super) fn new(mut item: MailEntry, new: bool, folder: &Path) -> Self {
+        let date = item.date().unwrap_or(0);
+        let size = std::fs::metadata(item.path()).map_or(0, |m| m.len());
+        let mut entry = Self {
+            item,
+            new,
+            date,
+            folder: folder.to_path_buf(),
+            from: "No Sender".to_string(),
+            to: String::new(),
+            cc: String::new(),
+            subject: "No Subject".to_string(),
+            message_id: None,
+            in_reply_to: None,
+            references: Vec::new(),
+            list: String::new(),
+            size,
+            attachment: false,
+            preview: String::new(),
+            thread_count: 1,
+            tags: Vec::new(),
+        };
+        let Ok(parsed) = entry.item.parsed() else {
+            return entry;
+        };
+        let headers = parsed.get_headers();
+        if let Some(from) = headers.get_first_value("From") {
+            entry.from = from;
+        }
+        entry.to = headers.get_first_value("To").unwrap_or_default();
+        entry.cc = headers.get_first_value("Cc").unwrap_or_default();
+        if let Some(subject) = headers.get_first_value("Subject") {
+            entry.subject = subject;
+        }
+        entry.message_id = headers
+            .get_first_value("Message-ID")
+            .and_then(|v| mailparse::msgidparse(&v).ok())
+            .and_then(|ids| ids.first().cloned());
+        entry.in_reply_to = headers
+            .get_first_value("In-Reply-To")
+            .and_then(|v| mailparse::msgidparse(&v).ok())
+            .and_then(|ids| ids.first().cloned());
+        entry.references = headers
+            .get_first_value("References")
+            .and_then(|v| mailparse::msgidparse(&v).ok())
+            .map(|ids| ids.to_vec())
+            .unwrap_or_default();
+        entry.list = headers
+            .get_first_value("List-Id")
+            .map(|v| list_name(&v))
+            .unwrap_or_default();
+        entry.attachment = has_attachment(&parsed);
+        entry.preview = preview(&parsed).unwrap_or_default();
+        entry
+    }
+}
+
+/// Display name of a List-Id, or the id itself
+fn list_name(list_id: &str) -> String {
+    match list_id.split_once('<') {
+        Some((name, _)) if !name.trim().is_empty() => name.trim().trim_matches('"').to_string(),
+        Some((_, id)) => id.trim_end_matches('>').trim().to_string(),
+        None => list_id.trim().to_string(),
+    }
+}
+
+fn has_attachment(mail: &ParsedMail) -> bool {
+    if mail.subparts.is_empty() {
+        let disposition = mail.get_content_disposition();
+        disposition.disposition == DispositionType::Attachment
+            || disposition.params.contains_key("filename")
+    } else {
+        mail.subparts.iter().any(has_attachment)
+    }
+}
+
+/// First lines of the text body, without quotes, on a single line
+fn preview(mail: &ParsedMail) -> Option<String> {
+    if !mail.subparts.is_empty() {
+        return mail.subparts.iter().find_map(preview);
+    }
+    if !mail.ctype.mimetype.eq_ignore_ascii_case("text/plain") {
+        return None;
+    }
+    let body = mail.get_body().ok()?;
+    let mut text = String::new();
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('>') {
+            continue;
+        }
+        if line == "--" {
+            break;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&line.split_whitespace().collect::<Vec<_>>().join(" "));
+        if text.chars().count() >= PREVIEW_LENGTH {
+            break;
+        }
+    }
+    Some(text.chars().take(PREVIEW_LENGTH).collect())
 }
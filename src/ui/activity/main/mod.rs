@@ -25,19 +25,51 @@
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
+mod attachment;
+mod columns;
+mod compose;
+mod contacts;
+mod flags;
+mod fulltext;
+mod inboxes;
+mod invite;
+mod limit;
 mod mailboxes;
 mod maillist;
+mod outbox;
+mod picker;
+mod queries;
+mod reader;
+mod recipients;
+mod search;
+mod sort;
+mod threads;
 mod update;
 mod view;
 use super::{Activity, Context, ExitReason};
-use crate::config::{TermailConfig, MAIL_DIR};
+use crate::addressbook::{AddressBook, Person};
+use crate::compose::Draft;
+use crate::config::{ImageProtocol, TermailConfig, MAIL_DIR};
+use crate::ical::Event;
+use crate::index::Index;
+use crate::mailcap::Mailcap;
+use crate::notmuch::Notmuch;
+use crate::pattern::Pattern;
+use crate::threading::{ThreadNode, ThreadRow};
+pub use columns::format_size;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use image::DynamicImage;
 use log::error;
 use maildir::MailEntry;
 use maildir::Maildir;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tui_realm_treeview::Tree;
+use tuirealm::props::TextSpan;
+use tuirealm::tui::layout::Rect;
 use tuirealm::View;
 
 // -- components
@@ -48,8 +80,15 @@ const COMPONENT_TREEVIEW_MAILBOXES: &str = "TREEVIEW_MAILBOXES";
 const COMPONENT_TEXT_HELP: &str = "TEXT_HELP";
 const COMPONENT_TEXT_ERROR: &str = "TEXT_ERROR";
 const COMPONENT_TEXT_MESSAGE: &str = "TEXT_MESSAGE";
-
-/// ### `ViewLayout`
+const COMPONENT_TABLE_COMPOSE: &str = "TABLE_COMPOSE";
+const COMPONENT_TABLE_FILES: &str = "TABLE_FILES";
+const COMPONENT_TABLE_CONTACTS: &str = "TABLE_CONTACTS";
+const COMPONENT_INPUT_SEARCH: &str = "INPUT_SEARCH";
+const COMPONENT_INPUT_LIMIT: &str = "INPUT_LIMIT";
+const COMPONENT_INPUT_FULLTEXT: &str = "INPUT_FULLTEXT";
+const COMPONENT_INPUT_TAGS: &str = "INPUT_TAGS";
+const COMPONENT_INPUT_RECIPIENTS: &str = "INPUT_RECIPIENTS";
+const COMPONENT_INPUT_CONTACT_NAME: &str = "INPUT_CONTACT_NAME";
 
 /// ## `MainActivity`
 ///
@@ -58,7 +97,38 @@ struct MailEntryNewOrRead {
     item: MailEntry,
     new: bool,
     date: i64,
+    folder: PathBuf,
+    // headers and summary, read once by the loader thread
+    from: String,
+    to: String,
+    cc: String,
+    subject: String,
+    message_id: Option<String>,
+    in_reply_to: Option<String>,
+    references: Vec<String>,
+    list: String,
+    size: u64,
+    attachment: bool,
+    preview: String,
+    thread_count: usize,
+    // notmuch tags, for mails found by a notmuch query
+    tags: Vec<String>,
+}
+/// A mail part which is not shown as text, kept to be opened with mailcap
+struct Attachment {
+    mimetype: String,
+    params: BTreeMap<String, String>,
+    filename: Option<String>,
+    data: Vec<u8>,
 }
+/// Meeting invitation found in the current mail, with the addresses the mail was sent to
+struct Invite {
+    event: Event,
+    addresses: Vec<String>,
+    /// the organizer asks for a reply, with METHOD:REQUEST
+    request: bool,
+}
+#[allow(clippy::struct_excessive_bools)]
 pub struct TermailActivity {
     exit_reason: Option<ExitReason>,
     context: Option<Context>, // Context holder
@@ -68,9 +138,92 @@ pub struct TermailActivity {
     tree: Tree,
     config: TermailConfig,
     mail_items: Vec<MailEntryNewOrRead>,
+    threads: Vec<ThreadNode>,
+    // lines of the mail list, pointing into mail_items
+    rows: Vec<ThreadRow>,
+    // ids of the first mail of collapsed threads
+    collapsed: HashSet<String>,
+    // limit pattern with its text, and whether each mail matches it
+    limit: Option<(String, Arc<Pattern>)>,
+    limit_match: Vec<bool>,
+    // bodies are being read to match the limit, the result is sent with its generation
+    limit_pending: bool,
+    limit_generation: u64,
+    receiver_limit: Receiver<(u64, Vec<bool>)>,
+    sender_limit: Sender<(u64, Vec<bool>)>,
+    index: Arc<Mutex<Index>>,
+    // folders to index, sent to the indexing thread
+    index_jobs: Option<Sender<Vec<PathBuf>>>,
+    // query whose results are in the mail list
+    fulltext: Option<String>,
+    // the mail list merges all inbox folders
+    unified: bool,
+    notmuch: Option<Notmuch>,
+    // saved queries, as name and query
+    notmuch_queries: Vec<(String, String)>,
+    // query whose results are in the mail list
+    notmuch_query: Option<String>,
     current_maildir: Maildir,
-    receiver_mail_items: Receiver<Vec<MailEntryNewOrRead>>,
-    sender_mail_items: Sender<Vec<MailEntryNewOrRead>>,
+    receiver_mail_items: Receiver<anyhow::Result<Vec<MailEntryNewOrRead>>>,
+    sender_mail_items: Sender<anyhow::Result<Vec<MailEntryNewOrRead>>>,
+    mailcap: Mailcap,
+    // text of a mail with the output of its mailcap viewers, rendered in the background
+    receiver_mail_body: Receiver<((PathBuf, String), anyhow::Result<String>)>,
+    sender_mail_body: Sender<((PathBuf, String), anyhow::Result<String>)>,
+    attachments: Vec<Attachment>,
+    attachment_index: usize,
+    images: Vec<DynamicImage>,
+    image_index: usize,
+    image_protocol: ImageProtocol,
+    // escape sequence of the image drawn last, with the image index and area it was made for.
+    // Kitty keeps the image itself, so its sequence is empty.
+    image_sequence: Option<(usize, Rect, String)>,
+    // area of the kitty or sixel image on screen
+    image_on_screen: Option<Rect>,
+    invite: Option<Invite>,
+    // mail being composed, waiting to be sent or saved
+    compose: Option<Draft>,
+    // mail handed to the built-in editor, as it was before
+    editing: Option<Draft>,
+    // the reader shows the rendered Markdown of the mail being composed
+    previewing: bool,
+    // mail answered or forwarded by the draft, and the flag it gets once sent
+    compose_origin: Option<(PathBuf, String, &'static str)>,
+    // draft the mail was resumed from, removed once it is sent or saved again
+    compose_draft: Option<(PathBuf, String)>,
+    // folder shown in the attachment picker, and its entries with the parent first
+    picker_dir: PathBuf,
+    picker_entries: Vec<PathBuf>,
+    // reports of the background sends of the Outbox
+    receiver_outbox: Receiver<anyhow::Result<crate::outbox::Report>>,
+    sender_outbox: Sender<anyhow::Result<crate::outbox::Report>>,
+    // a send is running, and when the Outbox is looked at next
+    outbox_busy: bool,
+    outbox_check: Instant,
+    // the running send, or the next one, was asked for and shows how it went
+    outbox_notify: bool,
+    outbox_notify_next: bool,
+    // queued mails, shown in the status line
+    outbox_status: Option<String>,
+    // addresses collected from the mails, to complete recipients
+    address_book: AddressBook,
+    // why it could not be loaded, shown once the view is set up
+    address_book_error: Option<String>,
+    // rows of the address book popup
+    people: Vec<Person>,
+    // recipient field being edited in compose, and its tab completion
+    recipients_field: &'static str,
+    completion: Option<crate::addressbook::Completion>,
+    // folder and id of the mail being bounced, while its recipients are asked for
+    bouncing: Option<(PathBuf, String)>,
+    // folder and id of the mail in the reader
+    reading: Option<(PathBuf, String)>,
+    message_expire: Option<Instant>,
+    // lines of the mail in the reader, before search highlighting
+    mail_lines: Vec<TextSpan>,
+    search: search::MailSearch,
+    // width of the mail list on screen, to truncate its columns
+    maillist_width: u16,
 }
 impl Default for TermailActivity {
     fn default() -> Self {
@@ -78,6 +231,9 @@ impl Default for TermailActivity {
         let p: &Path = Path::new(full_path.as_ref());
         let config = TermailConfig::default();
         let (tx, rx) = mpsc::channel();
+        let (tx_outbox, rx_outbox) = mpsc::channel();
+        let (tx_body, rx_body) = mpsc::channel();
+        let (tx_limit, rx_limit) = mpsc::channel();
         Self {
             exit_reason: None,
             context: None,
@@ -87,9 +243,61 @@ impl Default for TermailActivity {
             tree: Tree::new(Self::dir_tree(p, 2)),
             config,
             mail_items: Vec::new(),
+            threads: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            limit: None,
+            limit_match: Vec::new(),
+            limit_pending: false,
+            limit_generation: 0,
+            receiver_limit: rx_limit,
+            sender_limit: tx_limit,
+            index: Arc::new(Mutex::new(Index::default())),
+            index_jobs: None,
+            fulltext: None,
+            unified: false,
+            notmuch: None,
+            notmuch_queries: Vec::new(),
+            notmuch_query: None,
             current_maildir: Maildir::from(p.to_path_buf()),
             sender_mail_items: tx,
             receiver_mail_items: rx,
+            mailcap: Mailcap::default(),
+            receiver_mail_body: rx_body,
+            sender_mail_body: tx_body,
+            attachments: Vec::new(),
+            attachment_index: 0,
+            images: Vec::new(),
+            image_index: 0,
+            image_protocol: ImageProtocol::None,
+            image_sequence: None,
+            image_on_screen: None,
+            invite: None,
+            compose: None,
+            editing: None,
+            previewing: false,
+            compose_origin: None,
+            compose_draft: None,
+            picker_dir: PathBuf::new(),
+            picker_entries: Vec::new(),
+            receiver_outbox: rx_outbox,
+            sender_outbox: tx_outbox,
+            outbox_busy: false,
+            outbox_check: Instant::now(),
+            outbox_notify: false,
+            outbox_notify_next: false,
+            outbox_status: None,
+            address_book: AddressBook::default(),
+            address_book_error: None,
+            people: Vec::new(),
+            recipients_field: "To",
+            completion: None,
+            bouncing: None,
+            reading: None,
+            message_expire: None,
+            mail_lines: Vec::new(),
+            search: search::MailSearch::default(),
+            maillist_width: 0,
         }
     }
 }
@@ -97,16 +305,26 @@ impl Default for TermailActivity {
 impl TermailActivity {
     pub fn init_config(&mut self, config: &TermailConfig) {
         self.config = config.clone();
-        if let Some(mail_dir) = self.config.mail_dir_from_cli.clone() {
-            let full_path = shellexpand::tilde(&mail_dir);
-            let p: &Path = Path::new(full_path.as_ref());
-            self.scan_dir(p);
-        } else {
-            let mail_dir = self.config.mail_dir.clone();
-            let full_path = shellexpand::tilde(&mail_dir);
-            let p: &Path = Path::new(full_path.as_ref());
-            self.scan_dir(p);
+        self.mailcap = Mailcap::load();
+        match AddressBook::load() {
+            Ok(book) => self.address_book = book,
+            Err(e) => {
+                error!("{e}");
+                self.address_book_error = Some(e.to_string());
+            }
         }
+        self.image_protocol = crate::ui::image::detect_protocol(self.config.image_protocol);
+        let mail_dir = self
+            .config
+            .mail_dir_from_cli
+            .clone()
+            .unwrap_or_else(|| self.config.mail_dir.clone());
+        let full_path = shellexpand::tilde(&mail_dir);
+        let p: &Path = Path::new(full_path.as_ref());
+        // the saved queries go into the tree
+        self.init_notmuch(p);
+        self.scan_dir(p);
+        self.start_indexing();
     }
 }
 
@@ -125,7 +343,7 @@ impl Activity for TermailActivity {
         }
         // // Put raw mode on enabled
         if let Err(err) = enable_raw_mode() {
-            error!("Failed to enter raw mode: {}", err);
+            error!("Failed to enter raw mode: {err}");
         }
         // // Init view
         self.init_setup();
@@ -147,7 +365,7 @@ impl Activity for TermailActivity {
             self.redraw = true;
             // Handle event
             let msg = self.view.on(event);
-            self.update(&msg);
+            self.update(msg.as_ref());
         }
         // }
         // Redraw if necessary
@@ -175,15 +393,16 @@ impl Activity for TermailActivity {
     /// This function finally releases the context
     fn on_destroy(&mut self) -> Option<Context> {
         if let Err(err) = self.config.save() {
-            error!("Failed to save config: {}", err);
+            error!("Failed to save config: {err}");
         }
         // Disable raw mode
         if let Err(err) = disable_raw_mode() {
-            error!("Failed to disable raw mode: {}", err);
+            error!("Failed to disable raw mode: {err}");
         }
         self.context.as_ref()?;
         // Clear terminal and return
         if let Some(mut ctx) = self.context.take() {
+            self.remove_image(&mut ctx);
             ctx.clear_screen();
             return Some(ctx);
         }
@@ -0,0 +1,139 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{TermailActivity, COMPONENT_LABEL_HELP};
+use crate::outbox::{self, Outbox, Queued, Report};
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use log::error;
+use std::fmt::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+use tui_realm_stdlib::LabelPropsBuilder;
+use tuirealm::PropsBuilder;
+
+// how often queued mails are looked at
+const OUTBOX_INTERVAL: Duration = Duration::from_secs(30);
+
+impl TermailActivity {
+    /// ### `enqueue`
+    ///
+    /// Keep a mail in the Outbox, to be sent in the background
+    pub(super) fn enqueue(&mut self, queued: &Queued) -> Result<()> {
+        let outbox = Outbox::new(outbox::folder(&self.config));
+        outbox.enqueue(queued)?;
+        // show it in the status line right away
+        self.outbox_check = Instant::now();
+        self.reload_outbox(&outbox);
+        Ok(())
+    }
+
+    /// ### `update_outbox`
+    ///
+    /// Send queued mails whose next try is due in the background, and show what is left
+    pub fn update_outbox(&mut self) {
+        if let Ok(report) = self.receiver_outbox.try_recv() {
+            self.outbox_busy = false;
+            let notify = std::mem::take(&mut self.outbox_notify);
+            match report {
+                Ok(report) => {
+                    if report.sent > 0 {
+                        // sent mails leave the Outbox, and flag or remove mails elsewhere
+                        self.reload_current();
+                    }
+                    if notify && !report.errors.is_empty() {
+                        self.mount_error(&report.errors.join("\n"));
+                    } else if notify && report.sent > 0 {
+                        self.show_message("Outbox", "Mail sent");
+                    }
+                    self.outbox_status = outbox_status(&report);
+                    self.sync_status_line();
+                }
+                // another termail is sending them
+                Err(e) if notify => self.mount_error(&format!("The mail waits in the Outbox: {e}")),
+                Err(e) => error!("Failed to send the outbox: {e}"),
+            }
+        }
+        if self.outbox_busy || Instant::now() < self.outbox_check {
+            return;
+        }
+        self.outbox_check = Instant::now() + OUTBOX_INTERVAL;
+        self.outbox_busy = true;
+        self.outbox_notify = std::mem::take(&mut self.outbox_notify_next);
+        let outbox = Outbox::new(outbox::folder(&self.config));
+        let accounts = self.config.accounts.clone();
+        let tx = self.sender_outbox.clone();
+        thread::spawn(move || {
+            tx.send(outbox.flush(&accounts, Local::now().timestamp(), false))
+                .ok();
+        });
+    }
+
+    /// ### `status_line`
+    ///
+    /// Text of the line at the bottom
+    pub(super) fn status_line(&self) -> String {
+        let help = format!("Press <CTRL+H> for help. Version: {}", crate::VERSION);
+        match &self.outbox_status {
+            Some(status) => format!("{help}  {status}"),
+            None => help,
+        }
+    }
+
+    fn sync_status_line(&mut self) {
+        if let Some(props) = self.view.get_props(COMPONENT_LABEL_HELP) {
+            let props = LabelPropsBuilder::from(props)
+                .with_text(self.status_line())
+                .build();
+            self.view.update(COMPONENT_LABEL_HELP, props);
+            self.redraw = true;
+        }
+    }
+
+    /// Show the Outbox again if it is in the mail list
+    fn reload_outbox(&mut self, outbox: &Outbox) {
+        if self.current_maildir.path() == outbox.path() && !self.unified {
+            if let Some(node) = outbox.path().to_str() {
+                self.load_mailbox(node);
+            }
+        }
+    }
+}
+
+/// How many mails wait in the Outbox, and when they are tried next
+fn outbox_status(report: &Report) -> Option<String> {
+    if report.queued == 0 {
+        return None;
+    }
+    let mut status = format!("Outbox: {} queued", report.queued);
+    if let Some(retry) = report
+        .retry
+        .and_then(|r| Local.timestamp_opt(r, 0).single())
+    {
+        let _ = write!(status, ", next try at {}", retry.format("%H:%M"));
+    }
+    if let Some(error) = report.errors.last() {
+        let _ = write!(status, " ({error})");
+    }
+    Some(status)
+}
@@ -0,0 +1,146 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::columns::format_size;
+use super::{TermailActivity, COMPONENT_TABLE_FILES};
+use crate::mime::Attachment;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+use tui_realm_stdlib::{Table, TablePropsBuilder};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::props::{TableBuilder, TextSpan};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::{Payload, PropsBuilder, Value};
+
+impl TermailActivity {
+    /// ### `mount_picker`
+    ///
+    /// Popup to pick a file to attach, in the folder last picked from
+    pub(super) fn mount_picker(&mut self) {
+        if self.picker_dir.as_os_str().is_empty() {
+            self.picker_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+        }
+        let mut entries: Vec<(bool, PathBuf)> = fs::read_dir(&self.picker_dir)
+            .map(|dir| {
+                dir.filter_map(Result::ok)
+                    .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+                    .map(|e| (!e.path().is_dir(), e.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // folders first
+        entries.sort();
+        self.picker_entries = self
+            .picker_dir
+            .parent()
+            .map(PathBuf::from)
+            .into_iter()
+            .collect();
+        self.picker_entries
+            .extend(entries.into_iter().map(|(_, path)| path));
+
+        let mut table = TableBuilder::default();
+        for (i, path) in self.picker_entries.iter().enumerate() {
+            if i > 0 {
+                table.add_row();
+            }
+            let name = if i == 0 && self.picker_dir.parent().is_some() {
+                "../".to_string()
+            } else {
+                path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            if path.is_dir() {
+                table
+                    .add_col(TextSpan::new(format!("{name}/")).fg(Color::LightBlue))
+                    .add_col(TextSpan::from(""));
+            } else {
+                let size = path.metadata().map(|m| m.len()).unwrap_or_default();
+                table
+                    .add_col(TextSpan::from(name.as_str()))
+                    .add_col(TextSpan::from(format_size(size).as_str()));
+            }
+        }
+        self.view.mount(
+            COMPONENT_TABLE_FILES,
+            Box::new(Table::new(
+                TablePropsBuilder::default()
+                    .with_background(Color::Black)
+                    .with_highlighted_str(Some("\u{1f680}"))
+                    .with_highlighted_color(Color::LightBlue)
+                    .with_max_scroll_step(8)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightYellow)
+                    .scrollable(true)
+                    .with_title(
+                        format!("Attach: {}", self.picker_dir.display()),
+                        Alignment::Left,
+                    )
+                    .with_widths(&[80, 20])
+                    .with_table(table.build())
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_TABLE_FILES);
+    }
+
+    pub(super) fn umount_picker(&mut self) {
+        self.view.umount(COMPONENT_TABLE_FILES);
+    }
+
+    /// ### `picker_open`
+    ///
+    /// Go into the selected folder, or attach the selected file
+    pub(super) fn picker_open(&mut self) -> Result<()> {
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_FILES)
+        else {
+            return Ok(());
+        };
+        let Some(path) = self.picker_entries.get(row).cloned() else {
+            return Ok(());
+        };
+        if path.is_dir() {
+            self.picker_dir = path;
+            self.mount_picker();
+            return Ok(());
+        }
+        let attachment = Attachment::from_file(&path)?;
+        self.compose
+            .as_mut()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?
+            .attachments
+            .push(attachment);
+        self.umount_picker();
+        self.mount_compose();
+        Ok(())
+    }
+
+    pub(super) fn picker_up(&mut self) {
+        if let Some(parent) = self.picker_dir.parent() {
+            self.picker_dir = parent.to_path_buf();
+            self.mount_picker();
+        }
+    }
+}
@@ -0,0 +1,181 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{MailEntryNewOrRead, TermailActivity, COMPONENT_INPUT_TAGS, COMPONENT_TABLE_MAILLIST};
+use crate::notmuch::{Message, Notmuch, NODE_PREFIX};
+use anyhow::{anyhow, Result};
+use log::error;
+use maildir::Maildir;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+use tui_realm_stdlib::{Input, InputPropsBuilder};
+use tui_realm_treeview::Node;
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::{Payload, PropsBuilder, Value};
+
+impl TermailActivity {
+    /// ### `init_notmuch`
+    ///
+    /// Use the notmuch database of the mail root `path`, if there is one
+    pub(super) fn init_notmuch(&mut self, path: &Path) {
+        self.notmuch = Notmuch::detect(path);
+        if let Some(notmuch) = &self.notmuch {
+            self.notmuch_queries = notmuch.saved_queries();
+        }
+    }
+
+    /// ### `queries_node`
+    ///
+    /// Tree node with the saved notmuch queries
+    pub(super) fn queries_node(&self) -> Option<Node> {
+        if self.notmuch_queries.is_empty() {
+            return None;
+        }
+        let mut node = Node::new(NODE_PREFIX, "notmuch");
+        for (name, query) in &self.notmuch_queries {
+            node.add_child(Node::new(format!("{NODE_PREFIX}{query}"), name.clone()));
+        }
+        Some(node)
+    }
+
+    /// ### `load_notmuch`
+    ///
+    /// Show the result of a notmuch query in the mail list
+    pub(super) fn load_notmuch(&mut self, query: &str) -> Result<()> {
+        let notmuch = self
+            .notmuch
+            .clone()
+            .ok_or_else(|| anyhow!("No notmuch database in {}", self.path.display()))?;
+        if query.is_empty() {
+            return Ok(());
+        }
+        // the sort order is remembered for the query
+        self.current_maildir = Maildir::from(format!("{NODE_PREFIX}{query}"));
+        self.limit = None;
+        self.fulltext = None;
+        self.notmuch_query = Some(query.to_string());
+        let query = query.to_string();
+        let tx = self.sender_mail_items.clone();
+        thread::spawn(move || {
+            let messages = match notmuch.search(&query) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    tx.send(Err(e)).ok();
+                    return;
+                }
+            };
+            let wanted: Vec<(PathBuf, String)> = messages
+                .iter()
+                .filter_map(|m| MailEntryNewOrRead::location(&m.filename))
+                .collect();
+            let by_id: HashMap<&str, &Message> =
+                messages.iter().map(|m| (m.id.as_str(), m)).collect();
+            let mut mail_items = MailEntryNewOrRead::find_all(&wanted);
+            for item in &mut mail_items {
+                if let Some(message) = item.message_id.as_deref().and_then(|id| by_id.get(id)) {
+                    item.tags.clone_from(&message.tags);
+                }
+            }
+            tx.send(Ok(mail_items)).ok();
+        });
+        Ok(())
+    }
+
+    pub(super) fn mount_tags(&mut self) {
+        if self.notmuch.is_none() {
+            self.mount_error("Tags need a notmuch database under the mail root");
+            return;
+        }
+        self.view.mount(
+            COMPONENT_INPUT_TAGS,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightMagenta)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightMagenta)
+                    .with_label("Change tags (+tag -tag)", Alignment::Left)
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_TAGS);
+    }
+
+    pub(super) fn umount_tags(&mut self) {
+        self.view.umount(COMPONENT_INPUT_TAGS);
+        self.view.active(COMPONENT_TABLE_MAILLIST);
+    }
+
+    /// ### `change_tags`
+    ///
+    /// Write tag changes of the selected mail to the notmuch database
+    pub(super) fn change_tags(&mut self, changes: &str) -> Result<()> {
+        let changes: Vec<String> = changes
+            .split_whitespace()
+            .map(ToString::to_string)
+            .collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let notmuch = self
+            .notmuch
+            .clone()
+            .ok_or_else(|| anyhow!("No notmuch database"))?;
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_MAILLIST)
+        else {
+            return Ok(());
+        };
+        let index = self
+            .rows
+            .get(row)
+            .ok_or_else(|| anyhow!("No mail selected"))?
+            .index;
+        let item = &mut self.mail_items[index];
+        let message_id = item
+            .message_id
+            .clone()
+            .ok_or_else(|| anyhow!("The mail has no Message-ID to tag"))?;
+        notmuch.tag(&message_id, &changes)?;
+        for change in &changes {
+            let tag = &change[1..];
+            item.tags.retain(|t| t != tag);
+            if change.starts_with('+') {
+                item.tags.push(tag.to_string());
+            }
+        }
+        self.sync_maillist();
+        Ok(())
+    }
+
+    /// ### `untag_unread`
+    ///
+    /// Tell notmuch the mail has been read
+    pub(super) fn untag_unread(&self, message_id: Option<&str>) {
+        if let (Some(notmuch), Some(id)) = (&self.notmuch, message_id) {
+            if let Err(e) = notmuch.tag(id, &["-unread".to_string()]) {
+                error!("{e}");
+            }
+        }
+    }
+}
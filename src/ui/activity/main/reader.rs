@@ -0,0 +1,284 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::LinkedList;
+use std::ops::Range;
+use tui_realm_stdlib::utils::{get_block, wrap_spans};
+use tuirealm::event::{Event, KeyCode};
+use tuirealm::props::{PropPayload, PropValue, Props, TextSpan};
+use tuirealm::tui::layout::{Corner, Rect};
+use tuirealm::tui::style::{Color, Style};
+use tuirealm::tui::widgets::{List, ListItem, ListState};
+use tuirealm::{Component, Frame, Msg, Payload};
+use unicode_width::UnicodeWidthStr;
+
+// props of the stdlib textarea, so that its builder sets up the reader too
+const PROP_HIGHLIGHTED_TXT: &str = "highlighted-txt";
+const PROP_MAX_STEP: &str = "max-step";
+const PROP_SPANS: &str = "spans";
+// search matches as (line, start, end) in bytes, and the index of the current one
+const PROP_MATCHES: &str = "matches";
+const PROP_CURRENT_MATCH: &str = "current-match";
+
+/// ### `with_matches`
+///
+/// `props` highlighting the search `matches` of the lines, `current` standing out
+pub fn with_matches(
+    mut props: Props,
+    matches: &[(usize, Range<usize>)],
+    current: Option<usize>,
+) -> Props {
+    let matches = matches
+        .iter()
+        .map(|(line, range)| {
+            PropPayload::Tup3((
+                PropValue::Usize(*line),
+                PropValue::Usize(range.start),
+                PropValue::Usize(range.end),
+            ))
+        })
+        .collect::<LinkedList<_>>();
+    props.own.insert(PROP_MATCHES, PropPayload::Linked(matches));
+    match current {
+        Some(current) => {
+            props.own.insert(
+                PROP_CURRENT_MATCH,
+                PropPayload::One(PropValue::Usize(current)),
+            );
+        }
+        None => {
+            props.own.remove(PROP_CURRENT_MATCH);
+        }
+    }
+    props
+}
+
+/// ## `MailReader`
+///
+/// The stdlib textarea, which can highlight parts of a line: the search matches
+pub struct MailReader {
+    props: Props,
+    // selected line, and number of lines
+    index: usize,
+    len: usize,
+    focus: bool,
+}
+
+impl MailReader {
+    pub fn new(props: Props) -> Self {
+        let len = Self::lines(&props).len();
+        Self {
+            props,
+            index: 0,
+            len,
+            focus: false,
+        }
+    }
+
+    fn lines(props: &Props) -> Vec<&TextSpan> {
+        match props.own.get(PROP_SPANS) {
+            Some(PropPayload::Vec(spans)) => spans
+                .iter()
+                .filter_map(|value| match value {
+                    PropValue::TextSpan(span) => Some(span),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn max_step(&self) -> usize {
+        match self.props.own.get(PROP_MAX_STEP) {
+            Some(PropPayload::One(PropValue::Usize(step))) => *step,
+            _ => 8,
+        }
+    }
+
+    /// Matches of line `row` in order, with whether each is the current one
+    fn line_matches(&self, row: usize) -> Vec<(Range<usize>, bool)> {
+        let current = match self.props.own.get(PROP_CURRENT_MATCH) {
+            Some(PropPayload::One(PropValue::Usize(current))) => Some(*current),
+            _ => None,
+        };
+        let Some(PropPayload::Linked(matches)) = self.props.own.get(PROP_MATCHES) else {
+            return Vec::new();
+        };
+        matches
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, m)| match m {
+                PropPayload::Tup3((
+                    PropValue::Usize(line),
+                    PropValue::Usize(start),
+                    PropValue::Usize(end),
+                )) if *line == row => Some((*start..*end, Some(idx) == current)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The line cut in spans around its matches, which get the search colors
+    fn line_spans(&self, row: usize, line: &TextSpan) -> Vec<TextSpan> {
+        let mut spans = Vec::new();
+        let mut at = 0;
+        for (range, current) in self.line_matches(row) {
+            // matches made for other lines are left out
+            let (Some(before), Some(matched)) = (
+                line.content.get(at..range.start),
+                line.content.get(range.clone()),
+            ) else {
+                continue;
+            };
+            if !before.is_empty() {
+                spans.push(TextSpan {
+                    content: before.to_string(),
+                    ..line.clone()
+                });
+            }
+            let span = TextSpan::new(matched).fg(Color::Black);
+            spans.push(if current {
+                span.bg(Color::LightRed).bold()
+            } else {
+                span.bg(Color::Yellow)
+            });
+            at = range.end;
+        }
+        if spans.is_empty() || at < line.content.len() {
+            spans.push(TextSpan {
+                content: line.content[at..].to_string(),
+                ..line.clone()
+            });
+        }
+        spans
+    }
+}
+
+impl Component for MailReader {
+    fn render(&self, render: &mut Frame, area: Rect) {
+        if !self.props.visible {
+            return;
+        }
+        let highlight = match self.props.own.get(PROP_HIGHLIGHTED_TXT) {
+            Some(PropPayload::One(PropValue::Str(highlight))) => Some(highlight.as_str()),
+            _ => None,
+        };
+        let width = usize::from(area.width)
+            .saturating_sub(highlight.map_or(0, UnicodeWidthStr::width))
+            .saturating_sub(2);
+        let items: Vec<ListItem> = Self::lines(&self.props)
+            .into_iter()
+            .enumerate()
+            .map(|(row, line)| {
+                ListItem::new(wrap_spans(&self.line_spans(row, line), width, &self.props))
+            })
+            .collect();
+        let mut state = ListState::default();
+        state.select(Some(self.index));
+        let mut list = List::new(items)
+            .block(get_block(
+                &self.props.borders,
+                self.props.title.as_ref(),
+                self.focus,
+            ))
+            .start_corner(Corner::TopLeft)
+            .style(
+                Style::default()
+                    .fg(self.props.foreground)
+                    .bg(self.props.background),
+            );
+        if let Some(highlight) = highlight {
+            list = list.highlight_symbol(highlight);
+        }
+        render.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn update(&mut self, props: Props) -> Msg {
+        self.len = Self::lines(&props).len();
+        self.index = self.index.min(self.len.saturating_sub(1));
+        self.props = props;
+        Msg::None
+    }
+
+    fn get_props(&self) -> Props {
+        self.props.clone()
+    }
+
+    fn on(&mut self, ev: Event) -> Msg {
+        let Event::Key(key) = ev else {
+            return Msg::None;
+        };
+        let last = self.len.saturating_sub(1);
+        match key.code {
+            KeyCode::Down => self.index = (self.index + 1).min(last),
+            KeyCode::Up => self.index = self.index.saturating_sub(1),
+            KeyCode::PageDown => self.index = (self.index + self.max_step()).min(last),
+            KeyCode::PageUp => self.index = self.index.saturating_sub(self.max_step()),
+            KeyCode::End => self.index = last,
+            KeyCode::Home => self.index = 0,
+            _ => {}
+        }
+        Msg::OnKey(key)
+    }
+
+    fn get_state(&self) -> Payload {
+        Payload::None
+    }
+
+    fn blur(&mut self) {
+        self.focus = false;
+    }
+
+    fn active(&mut self) {
+        self.focus = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use tui_realm_stdlib::TextareaPropsBuilder;
+    use tuirealm::PropsBuilder;
+
+    #[test]
+    fn test_reader_line_spans() {
+        let line = TextSpan::from("hello big hello");
+        let props = TextareaPropsBuilder::default()
+            .with_texts(vec![line.clone()])
+            .build();
+        let reader = MailReader::new(with_matches(
+            props,
+            &[(0, 0..5), (0, 10..15), (1, 0..3)],
+            Some(1),
+        ));
+        let spans = reader.line_spans(0, &line);
+        let contents: Vec<&str> = spans.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["hello", " big ", "hello"]);
+        assert_eq!(spans[0].bg, Color::Yellow);
+        assert_eq!(spans[1].bg, Color::Reset);
+        assert_eq!(spans[2].bg, Color::LightRed);
+    }
+}
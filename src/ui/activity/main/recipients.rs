@@ -0,0 +1,141 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{
+    TermailActivity, COMPONENT_INPUT_RECIPIENTS, COMPONENT_TABLE_COMPOSE, COMPONENT_TABLE_MAILLIST,
+};
+use crate::addressbook::Completion;
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use tui_realm_stdlib::{Input, InputPropsBuilder};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::{Payload, PropsBuilder, Value};
+
+impl TermailActivity {
+    /// ### `mount_recipients`
+    ///
+    /// Edit the To, Cc or Bcc field of the mail being composed, completing addresses with <TAB>
+    pub(super) fn mount_recipients(&mut self, field: &'static str) {
+        let Some(draft) = &self.compose else {
+            return;
+        };
+        let value = match field {
+            "Cc" => draft.cc.clone(),
+            "Bcc" => draft.bcc.clone(),
+            _ => draft.to.clone(),
+        };
+        self.mount_recipients_input(field, value);
+    }
+
+    /// ### `mount_bounce`
+    ///
+    /// Ask who to bounce the selected mail to
+    pub(super) fn mount_bounce(&mut self) -> Result<()> {
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_MAILLIST)
+        else {
+            return Ok(());
+        };
+        let index = self
+            .rows
+            .get(row)
+            .ok_or_else(|| anyhow!("No mail selected"))?
+            .index;
+        let item = &self.mail_items[index];
+        self.bouncing = Some((item.folder.clone(), item.item.id().to_string()));
+        self.mount_recipients_input("Bounce to", String::new());
+        Ok(())
+    }
+
+    fn mount_recipients_input(&mut self, field: &'static str, value: String) {
+        self.recipients_field = field;
+        self.completion = None;
+        self.view.mount(
+            COMPONENT_INPUT_RECIPIENTS,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::LightYellow)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::LightYellow)
+                    .with_label(format!("{field} (<TAB> complete)"), Alignment::Left)
+                    .with_value(value)
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_RECIPIENTS);
+    }
+
+    pub(super) fn umount_recipients(&mut self) {
+        self.completion = None;
+        self.view.umount(COMPONENT_INPUT_RECIPIENTS);
+        if self.bouncing.take().is_some() {
+            self.view.active(COMPONENT_TABLE_MAILLIST);
+        } else {
+            self.view.active(COMPONENT_TABLE_COMPOSE);
+        }
+    }
+
+    /// ### `set_recipients`
+    ///
+    /// Put the edited field back in the mail being composed
+    pub(super) fn set_recipients(&mut self, value: &str) -> Result<()> {
+        let draft = self
+            .compose
+            .as_mut()
+            .ok_or_else(|| anyhow!("No mail is being composed"))?;
+        let value = value.trim().trim_end_matches(',').trim().to_string();
+        match self.recipients_field {
+            "Cc" => draft.cc = value,
+            "Bcc" => draft.bcc = value,
+            _ => draft.to = value,
+        }
+        self.umount_recipients();
+        self.mount_compose();
+        Ok(())
+    }
+
+    /// ### `complete_recipient`
+    ///
+    /// Complete the address typed last from the address book, or go to the next candidate
+    /// when it was just completed
+    pub(super) fn complete_recipient(&mut self, value: &str) {
+        let Some(completion) = Completion::next(
+            self.completion.take(),
+            value,
+            &self.address_book,
+            Local::now().timestamp(),
+        ) else {
+            return;
+        };
+        let (index, count) = completion.position();
+        let label = format!("{} ({index}/{count}, <TAB> next)", self.recipients_field);
+        if let Some(props) = self.view.get_props(COMPONENT_INPUT_RECIPIENTS) {
+            let props = InputPropsBuilder::from(props)
+                .with_label(label, Alignment::Left)
+                .with_value(completion.value().to_string())
+                .build();
+            self.view.update(COMPONENT_INPUT_RECIPIENTS, props);
+        }
+        self.completion = Some(completion);
+    }
+}
@@ -0,0 +1,271 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::reader::with_matches;
+use super::{TermailActivity, COMPONENT_INPUT_SEARCH, COMPONENT_TEXTAREA_MAIL};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use tui_realm_stdlib::{Input, InputPropsBuilder};
+use tuirealm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tuirealm::props::borders::{BorderType, Borders};
+use tuirealm::props::TextSpan;
+use tuirealm::tui::layout::Alignment;
+use tuirealm::tui::style::Color;
+use tuirealm::PropsBuilder;
+
+/// Search state of the mail reader
+#[derive(Default)]
+pub struct MailSearch {
+    pub query: String,
+    pub regex: bool,
+    // line index and byte range of every match
+    pub matches: Vec<(usize, Range<usize>)>,
+    pub current: usize,
+    pub error: Option<String>,
+}
+
+impl MailSearch {
+    fn compile(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        // smartcase: an upper case letter makes the search case sensitive
+        let case_insensitive = !self.query.chars().any(char::is_uppercase);
+        RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+    }
+
+    /// ### `find`
+    ///
+    /// Search `lines` again, keeping the current match on or after line `from`
+    pub fn find(&mut self, lines: &[TextSpan], from: usize) {
+        self.matches.clear();
+        self.current = 0;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+        let re = match self.compile() {
+            Ok(re) => re,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        for (idx, line) in lines.iter().enumerate() {
+            // a pattern like `a*` matches nothing between every character
+            self.matches.extend(
+                re.find_iter(&line.content)
+                    .filter(|m| !m.range().is_empty())
+                    .map(|m| (idx, m.range())),
+            );
+        }
+        self.current = self
+            .matches
+            .iter()
+            .position(|(l, _)| *l >= from)
+            .unwrap_or(0);
+    }
+
+    pub fn current_line(&self) -> Option<usize> {
+        self.matches.get(self.current).map(|(line, _)| *line)
+    }
+
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn next(&mut self, forward: bool) {
+        let len = self.matches.len();
+        if len == 0 {
+            return;
+        }
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+    }
+}
+
+impl TermailActivity {
+    pub(super) fn mount_search(&mut self) {
+        self.view.mount(
+            COMPONENT_INPUT_SEARCH,
+            Box::new(Input::new(
+                InputPropsBuilder::default()
+                    .with_foreground(Color::Yellow)
+                    .with_borders(Borders::ALL, BorderType::Rounded, Color::Yellow)
+                    .with_label(Self::search_title(&self.search), Alignment::Left)
+                    .with_value(self.search.query.clone())
+                    .build(),
+            )),
+        );
+        self.view.active(COMPONENT_INPUT_SEARCH);
+    }
+
+    pub(super) fn umount_search(&mut self) {
+        self.view.umount(COMPONENT_INPUT_SEARCH);
+        self.view.active(COMPONENT_TEXTAREA_MAIL);
+    }
+
+    fn search_title(search: &MailSearch) -> String {
+        let mode = if search.regex {
+            "Search (regex)"
+        } else {
+            "Search"
+        };
+        if let Some(error) = &search.error {
+            return format!("{mode}: {}", error.lines().last().unwrap_or_default());
+        }
+        if search.query.is_empty() {
+            format!("{mode} <CTRL+R> toggles regex")
+        } else if search.matches.is_empty() {
+            format!("{mode}: no match")
+        } else {
+            format!("{mode}: {}/{}", search.current + 1, search.matches.len())
+        }
+    }
+
+    /// ### `update_search`
+    ///
+    /// Incremental search, called whenever the query changes
+    pub(super) fn update_search(&mut self, query: &str) {
+        let from = self.search.current_line().unwrap_or(0);
+        self.search.query = query.to_string();
+        self.search.find(&self.mail_lines, from);
+        self.sync_search();
+    }
+
+    pub(super) fn toggle_search_regex(&mut self) {
+        self.search.regex = !self.search.regex;
+        let from = self.search.current_line().unwrap_or(0);
+        self.search.find(&self.mail_lines, from);
+        self.sync_search();
+    }
+
+    pub(super) fn search_next(&mut self, forward: bool) {
+        self.search.next(forward);
+        self.sync_search();
+    }
+
+    pub(super) fn clear_search(&mut self) {
+        self.search = MailSearch {
+            regex: self.search.regex,
+            ..MailSearch::default()
+        };
+        self.sync_search();
+    }
+
+    /// ### `sync_search`
+    ///
+    /// Highlight the matches and scroll to the current one
+    fn sync_search(&mut self) {
+        let current = self.search.current_line();
+        if let Some(props) = self.view.get_props(COMPONENT_TEXTAREA_MAIL) {
+            let props = with_matches(
+                props,
+                &self.search.matches,
+                current.map(|_| self.search.current),
+            );
+            self.view.update(COMPONENT_TEXTAREA_MAIL, props);
+        }
+        if let Some(props) = self.view.get_props(COMPONENT_INPUT_SEARCH) {
+            let props = InputPropsBuilder::from(props)
+                .with_label(Self::search_title(&self.search), Alignment::Left)
+                .build();
+            self.view.update(COMPONENT_INPUT_SEARCH, props);
+        }
+        if let Some(line) = current {
+            self.scroll_mail_to(line);
+        }
+    }
+
+    /// ### `scroll_mail_to`
+    ///
+    /// The textarea has no way to set its position, so move there with key events
+    fn scroll_mail_to(&mut self, line: usize) {
+        let searching = self.view.get_props(COMPONENT_INPUT_SEARCH).is_some();
+        self.view.active(COMPONENT_TEXTAREA_MAIL);
+        self.view.on(Event::Key(KeyEvent {
+            code: KeyCode::Home,
+            modifiers: KeyModifiers::NONE,
+        }));
+        for _ in 0..line {
+            self.view.on(Event::Key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+        if searching {
+            self.view.active(COMPONENT_INPUT_SEARCH);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mail_search() {
+        let lines = vec![
+            TextSpan::from("Hello world"),
+            TextSpan::from("nothing here"),
+            TextSpan::from("hello hello"),
+        ];
+        let mut search = MailSearch {
+            query: "hello".to_string(),
+            ..MailSearch::default()
+        };
+        search.find(&lines, 1);
+        assert_eq!(search.matches, vec![(0, 0..5), (2, 0..5), (2, 6..11)]);
+        assert_eq!(search.current_line(), Some(2));
+        search.next(true);
+        search.next(true);
+        assert_eq!(search.current_line(), Some(0));
+        search.next(false);
+        assert_eq!(search.current_line(), Some(2));
+
+        search.query = "Hello".to_string();
+        search.find(&lines, 0);
+        assert_eq!(search.matches, vec![(0, 0..5)]);
+
+        search.regex = true;
+        search.query = "h(e|o)re?".to_string();
+        search.find(&lines, 0);
+        assert_eq!(search.matches, vec![(1, 8..12)]);
+
+        // empty matches are no matches
+        search.query = "x*".to_string();
+        search.find(&lines, 0);
+        assert!(search.matches.is_empty());
+
+        search.query = "(".to_string();
+        search.find(&lines, 0);
+        assert!(search.error.is_some());
+    }
+}
@@ -0,0 +1,127 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{MailEntryNewOrRead, TermailActivity, COMPONENT_TABLE_MAILLIST};
+use crate::config::{SortKey, SortOrder};
+use crate::threading::normalize_subject;
+use std::cmp::{Ordering, Reverse};
+use tuirealm::{Payload, Value};
+
+impl TermailActivity {
+    /// ### `sort_order`
+    ///
+    /// Sort order saved for the current folder
+    pub(super) fn sort_order(&self) -> SortOrder {
+        let folder = self.current_maildir.path().to_string_lossy();
+        self.config
+            .sort_orders
+            .get(folder.as_ref())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// ### `sort_mail_items`
+    ///
+    /// Sort the mails of the folder, ties are broken by date, newest first
+    pub(super) fn sort_mail_items(&mut self) {
+        let order = self.sort_order();
+        self.mail_items.sort_by(|a, b| {
+            let unread = if order.unread_first {
+                Reverse(a.is_unread()).cmp(&Reverse(b.is_unread()))
+            } else {
+                Ordering::Equal
+            };
+            let by_key = compare(order.key, a, b);
+            let by_key = if order.descending {
+                by_key.reverse()
+            } else {
+                by_key
+            };
+            unread.then(by_key).then_with(|| b.date.cmp(&a.date))
+        });
+    }
+
+    /// ### `change_sort_order`
+    ///
+    /// Apply and remember a new sort order for the current folder
+    pub(super) fn change_sort_order(&mut self, change: fn(&mut SortOrder)) {
+        let mut order = self.sort_order();
+        change(&mut order);
+        let folder = self.current_maildir.path().to_string_lossy().to_string();
+        self.config.sort_orders.insert(folder, order);
+
+        // keep the selected mail selected
+        let selected = match self.view.get_state(COMPONENT_TABLE_MAILLIST) {
+            Some(Payload::One(Value::Usize(row))) => self
+                .rows
+                .get(row)
+                .map(|r| self.mail_items[r.index].item.id().to_string()),
+            _ => None,
+        };
+        self.sort_mail_items();
+        self.match_limit();
+        self.build_threads();
+        self.sync_maillist();
+        if let Some(id) = selected {
+            let position = self.rows.iter().position(|r| {
+                r.collapsed.as_ref().map_or_else(
+                    || self.mail_items[r.index].item.id() == id,
+                    |indexes| indexes.iter().any(|i| self.mail_items[*i].item.id() == id),
+                )
+            });
+            if let Some(row) = position {
+                self.select_maillist_row(row);
+            }
+        }
+
+        let direction = if order.descending {
+            "descending"
+        } else {
+            "ascending"
+        };
+        let unread = if order.unread_first {
+            ", unread first"
+        } else {
+            ""
+        };
+        self.show_message("Sort", &format!("{} {direction}{unread}", order.key.name()));
+    }
+}
+
+fn compare(key: SortKey, a: &MailEntryNewOrRead, b: &MailEntryNewOrRead) -> Ordering {
+    match key {
+        SortKey::Date => a.date.cmp(&b.date),
+        SortKey::Sender => {
+            // "Name" <address> sorts by name
+            let sender = |m: &MailEntryNewOrRead| m.from.trim_start_matches('"').to_lowercase();
+            sender(a).cmp(&sender(b))
+        }
+        SortKey::Subject => {
+            let subject = |m: &MailEntryNewOrRead| normalize_subject(&m.subject).0.to_lowercase();
+            subject(a).cmp(&subject(b))
+        }
+        SortKey::Size => a.size.cmp(&b.size),
+        SortKey::Recipient => a.to.to_lowercase().cmp(&b.to.to_lowercase()),
+        SortKey::Flagged => a.item.is_flagged().cmp(&b.item.is_flagged()),
+    }
+}
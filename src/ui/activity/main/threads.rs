@@ -0,0 +1,157 @@
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use super::{TermailActivity, COMPONENT_TABLE_MAILLIST};
+use crate::threading::{self, ThreadMessage, ThreadRow};
+use tuirealm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tuirealm::{Payload, Value};
+
+impl TermailActivity {
+    /// ### `build_threads`
+    ///
+    /// Thread the mails of the mailbox, after it has been loaded
+    pub(super) fn build_threads(&mut self) {
+        let messages: Vec<ThreadMessage> = self
+            .mail_items
+            .iter()
+            .map(|item| {
+                let mut references: Vec<&str> =
+                    item.references.iter().map(String::as_str).collect();
+                if let Some(in_reply_to) = &item.in_reply_to {
+                    if references.last() != Some(&in_reply_to.as_str()) {
+                        references.push(in_reply_to);
+                    }
+                }
+                ThreadMessage {
+                    message_id: item.message_id.as_deref(),
+                    references,
+                    subject: &item.subject,
+                    date: item.date,
+                }
+            })
+            .collect();
+        self.threads = threading::thread(&messages);
+        // a thread comes where its first mail in the sort order is
+        self.threads
+            .sort_by_key(|thread| thread.indexes().into_iter().min());
+        for thread in &self.threads {
+            let indexes = thread.indexes();
+            for index in &indexes {
+                self.mail_items[*index].thread_count = indexes.len();
+            }
+        }
+        self.build_rows();
+    }
+
+    /// ### `build_rows`
+    ///
+    /// Lines of the mail list, as threads or in the mailbox order
+    pub(super) fn build_rows(&mut self) {
+        // search results keep their rank
+        self.rows = if self.config.maillist.threaded && self.fulltext.is_none() {
+            let mail_items = &self.mail_items;
+            let collapsed = &self.collapsed;
+            let is_collapsed = |index: usize| collapsed.contains(mail_items[index].item.id());
+            if self.limit.is_some() {
+                let threads = threading::filter(&self.threads, &|index| self.in_limit(index));
+                threading::flatten(&threads, &is_collapsed)
+            } else {
+                threading::flatten(&self.threads, &is_collapsed)
+            }
+        } else {
+            (0..self.mail_items.len())
+                .filter(|index| self.in_limit(*index))
+                .map(|index| ThreadRow {
+                    index,
+                    prefix: String::new(),
+                    root: index,
+                    collapsed: None,
+                })
+                .collect()
+        };
+    }
+
+    /// ### `toggle_thread`
+    ///
+    /// Collapse or expand the thread of the selected mail
+    pub(super) fn toggle_thread(&mut self) {
+        if !self.config.maillist.threaded {
+            return;
+        }
+        let Some(Payload::One(Value::Usize(row))) = self.view.get_state(COMPONENT_TABLE_MAILLIST)
+        else {
+            return;
+        };
+        let Some(root) = self.rows.get(row).map(|r| r.root) else {
+            return;
+        };
+        let id = self.mail_items[root].item.id().to_string();
+        if !self.collapsed.remove(&id) {
+            self.collapsed.insert(id);
+        }
+        self.build_rows();
+        self.sync_maillist();
+        if let Some(row) = self.rows.iter().position(|r| r.index == root) {
+            self.select_maillist_row(row);
+        }
+    }
+
+    /// ### `toggle_threaded`
+    ///
+    /// Switch between the threaded and the flat mail list
+    pub(super) fn toggle_threaded(&mut self) {
+        let selected = match self.view.get_state(COMPONENT_TABLE_MAILLIST) {
+            Some(Payload::One(Value::Usize(row))) => self.rows.get(row).map(|r| r.index),
+            _ => None,
+        };
+        self.config.maillist.threaded = !self.config.maillist.threaded;
+        self.build_rows();
+        self.sync_maillist();
+        // keep the selected mail, or its collapsed thread
+        if let Some(index) = selected {
+            let position = self.rows.iter().position(|r| {
+                r.index == index || r.collapsed.as_ref().is_some_and(|c| c.contains(&index))
+            });
+            if let Some(row) = position {
+                self.select_maillist_row(row);
+            }
+        }
+    }
+
+    /// ### `select_maillist_row`
+    ///
+    /// The table has no way to set its position, so move there with key events
+    pub(super) fn select_maillist_row(&mut self, row: usize) {
+        self.view.active(COMPONENT_TABLE_MAILLIST);
+        self.view.on(Event::Key(KeyEvent {
+            code: KeyCode::Home,
+            modifiers: KeyModifiers::NONE,
+        }));
+        for _ in 0..row {
+            self.view.on(Event::Key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+    }
+}
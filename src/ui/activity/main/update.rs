@@ -22,13 +22,25 @@
  * SOFTWARE.
  */
 use super::{
-    ExitReason, TermailActivity, COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL,
-    COMPONENT_TEXT_ERROR, COMPONENT_TEXT_HELP, COMPONENT_TREEVIEW_MAILBOXES,
+    ExitReason, TermailActivity, COMPONENT_INPUT_CONTACT_NAME, COMPONENT_INPUT_FULLTEXT,
+    COMPONENT_INPUT_LIMIT, COMPONENT_INPUT_RECIPIENTS, COMPONENT_INPUT_SEARCH,
+    COMPONENT_INPUT_TAGS, COMPONENT_TABLE_COMPOSE, COMPONENT_TABLE_CONTACTS, COMPONENT_TABLE_FILES,
+    COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL, COMPONENT_TEXT_ERROR, COMPONENT_TEXT_HELP,
+    COMPONENT_TEXT_MESSAGE, COMPONENT_TREEVIEW_MAILBOXES,
 };
+use crate::compose::ReplyKind;
+use crate::ical::PartStat;
 use crate::ui::keymap::{
-    MSG_KEY_CHAR_CAPITAL_Q, MSG_KEY_CHAR_H, MSG_KEY_CHAR_J, MSG_KEY_CHAR_K, MSG_KEY_CHAR_L,
-    MSG_KEY_CTRL_H, MSG_KEY_ENTER, MSG_KEY_ESC, MSG_KEY_TAB,
+    MSG_KEY_CHAR_A, MSG_KEY_CHAR_B, MSG_KEY_CHAR_C, MSG_KEY_CHAR_CAPITAL_A, MSG_KEY_CHAR_CAPITAL_F,
+    MSG_KEY_CHAR_CAPITAL_L, MSG_KEY_CHAR_CAPITAL_M, MSG_KEY_CHAR_CAPITAL_N, MSG_KEY_CHAR_CAPITAL_O,
+    MSG_KEY_CHAR_CAPITAL_Q, MSG_KEY_CHAR_CAPITAL_R, MSG_KEY_CHAR_CAPITAL_T, MSG_KEY_CHAR_D,
+    MSG_KEY_CHAR_E, MSG_KEY_CHAR_F, MSG_KEY_CHAR_H, MSG_KEY_CHAR_I, MSG_KEY_CHAR_J, MSG_KEY_CHAR_K,
+    MSG_KEY_CHAR_L, MSG_KEY_CHAR_M, MSG_KEY_CHAR_N, MSG_KEY_CHAR_O, MSG_KEY_CHAR_P,
+    MSG_KEY_CHAR_PLUS, MSG_KEY_CHAR_Q, MSG_KEY_CHAR_R, MSG_KEY_CHAR_T, MSG_KEY_CHAR_U,
+    MSG_KEY_CHAR_X, MSG_KEY_CHAR_Y, MSG_KEY_CTRL_H, MSG_KEY_CTRL_R, MSG_KEY_ENTER, MSG_KEY_ESC,
+    MSG_KEY_SLASH, MSG_KEY_SPACE, MSG_KEY_TAB,
 };
+use std::time::Instant;
 use tuirealm::{
     event::{Event, KeyCode, KeyEvent, KeyModifiers},
     Msg, Payload, Value,
@@ -39,14 +51,15 @@ impl TermailActivity {
     ///
     /// Update auth activity model based on msg
     /// The function exits when returns None
-    pub(super) fn update(&mut self, msg: &Option<(String, Msg)>) -> Option<(String, Msg)> {
-        let ref_msg: Option<(&str, &Msg)> = msg.as_ref().map(|(s, msg)| (s.as_str(), msg));
+    #[allow(clippy::too_many_lines)]
+    pub(super) fn update(&mut self, msg: Option<&(String, Msg)>) -> Option<(String, Msg)> {
+        let ref_msg: Option<(&str, &Msg)> = msg.map(|(s, msg)| (s.as_str(), msg));
         ref_msg.and_then(|msg| match msg {
             // -- help
             (COMPONENT_TEXT_HELP, key)
                 if (key == &MSG_KEY_ENTER)
-                    | (key == &MSG_KEY_ESC)
-                    | (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
+                    || (key == &MSG_KEY_ESC)
+                    || (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
             {
                 self.umount_help();
                 None
@@ -54,8 +67,8 @@ impl TermailActivity {
             // -- error
             (COMPONENT_TEXT_ERROR, key)
                 if (key == &MSG_KEY_ESC)
-                    | (key == &MSG_KEY_ENTER)
-                    | (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
+                    || (key == &MSG_KEY_ENTER)
+                    || (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
             {
                 self.umount_error();
                 None
@@ -113,8 +126,14 @@ impl TermailActivity {
                 if let Some(Payload::One(Value::Usize(index))) =
                     self.view.get_state(COMPONENT_TABLE_MAILLIST)
                 {
+                    if self.is_draft(index) {
+                        if let Err(e) = self.resume_draft(index) {
+                            self.mount_error(&e.to_string());
+                        }
+                        return None;
+                    }
                     match self.load_mail(index) {
-                        Ok(_) => {
+                        Ok(()) => {
                             self.view.active(COMPONENT_TEXTAREA_MAIL);
                         }
                         Err(e) => self.mount_error(&e.to_string()),
@@ -123,10 +142,314 @@ impl TermailActivity {
                 None
             }
 
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_SPACE) => {
+                self.toggle_thread();
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_CAPITAL_T) => {
+                self.toggle_threaded();
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST | COMPONENT_TREEVIEW_MAILBOXES, key)
+                if (key == &MSG_KEY_SLASH) =>
+            {
+                self.mount_fulltext();
+                None
+            }
+
+            (COMPONENT_INPUT_FULLTEXT, Msg::OnSubmit(Payload::One(Value::Str(query)))) => {
+                self.umount_fulltext();
+                self.fulltext_search(query);
+                None
+            }
+
+            (COMPONENT_INPUT_FULLTEXT, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_fulltext();
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST | COMPONENT_TREEVIEW_MAILBOXES, key)
+                if (key == &MSG_KEY_CHAR_M) =>
+            {
+                if let Err(e) = self.compose_new("") {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key)
+                if (key == &MSG_KEY_CHAR_Y) || (key == &MSG_KEY_CHAR_Q) =>
+            {
+                let queue = key == &MSG_KEY_CHAR_Q;
+                match self.send_draft(queue) {
+                    Ok(done) => {
+                        self.discard_draft();
+                        self.show_message("Compose", done);
+                    }
+                    Err(e) => self.mount_error(&e.to_string()),
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_E) => {
+                if let Err(e) = self.edit_again() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_D) => {
+                if let Err(e) = self.save_draft() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_I) => {
+                if let Err(e) = self.cycle_identity() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key)
+                if (key == &MSG_KEY_CHAR_T)
+                    || (key == &MSG_KEY_CHAR_C)
+                    || (key == &MSG_KEY_CHAR_B) =>
+            {
+                self.mount_recipients(if key == &MSG_KEY_CHAR_C {
+                    "Cc"
+                } else if key == &MSG_KEY_CHAR_B {
+                    "Bcc"
+                } else {
+                    "To"
+                });
+                None
+            }
+
+            (COMPONENT_INPUT_RECIPIENTS, Msg::OnSubmit(Payload::One(Value::Str(value)))) => {
+                if let Some(mail) = self.bouncing.clone() {
+                    self.umount_recipients();
+                    match self.bounce_mail(mail, value) {
+                        Ok(done) => self.show_message("Bounce", done),
+                        Err(e) => self.mount_error(&e.to_string()),
+                    }
+                } else if let Err(e) = self.set_recipients(value) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_INPUT_RECIPIENTS, key) if (key == &MSG_KEY_TAB) => {
+                if let Some(Payload::One(Value::Str(value))) =
+                    self.view.get_state(COMPONENT_INPUT_RECIPIENTS)
+                {
+                    self.complete_recipient(&value);
+                }
+                None
+            }
+
+            (COMPONENT_INPUT_RECIPIENTS, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_recipients();
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_CHAR_E) => {
+                if let Err(e) = self.mount_contact_name() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_CHAR_M) => {
+                if let Err(e) = self.write_to_contact() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key)
+                if (key == &MSG_KEY_CHAR_L) || (key == &MSG_KEY_ENTER) =>
+            {
+                if let Err(e) = self.mails_of_contact() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_CHAR_I) => {
+                if let Err(e) = self.import_vcards() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_CHAR_X) => {
+                if let Err(e) = self.export_vcards() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_CHAR_D) => {
+                if let Err(e) = self.remove_contact() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_CONTACTS, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_contacts();
+                None
+            }
+
+            (COMPONENT_INPUT_CONTACT_NAME, Msg::OnSubmit(Payload::One(Value::Str(name)))) => {
+                if let Err(e) = self.rename_contact(name) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_INPUT_CONTACT_NAME, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_contact_name();
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_A) => {
+                self.mount_picker();
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_X) => {
+                if let Err(e) = self.remove_attachment() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_CAPITAL_M) => {
+                if let Err(e) = self.toggle_markdown() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_CHAR_P) => {
+                if let Err(e) = self.preview_draft() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            // the preview stays until it is closed, the draft is kept meanwhile
+            (COMPONENT_TEXTAREA_MAIL, key) if self.previewing => {
+                if (key == &MSG_KEY_ESC) || (key == &MSG_KEY_CHAR_P) {
+                    self.end_preview();
+                } else if (key == &MSG_KEY_CHAR_J) || (key == &MSG_KEY_CHAR_K) {
+                    self.update_on_global_key(key);
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_FILES, key) if (key == &MSG_KEY_CHAR_L) || (key == &MSG_KEY_ENTER) => {
+                if let Err(e) = self.picker_open() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_FILES, key) if (key == &MSG_KEY_CHAR_H) => {
+                self.picker_up();
+                None
+            }
+
+            (COMPONENT_TABLE_FILES, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_picker();
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE | COMPONENT_TABLE_FILES | COMPONENT_TABLE_CONTACTS, key)
+                if (key == &MSG_KEY_CHAR_J) || (key == &MSG_KEY_CHAR_K) =>
+            {
+                self.update_on_global_key(key);
+                None
+            }
+
+            (COMPONENT_TABLE_COMPOSE, key) if (key == &MSG_KEY_ESC) => {
+                self.discard_draft();
+                self.show_message("Compose", "Mail discarded");
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_B) => {
+                if let Err(e) = self.mount_bounce() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_CAPITAL_F) => {
+                if let Err(e) = self.toggle_flag() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_PLUS) => {
+                self.mount_tags();
+                None
+            }
+
+            (COMPONENT_INPUT_TAGS, Msg::OnSubmit(Payload::One(Value::Str(changes)))) => {
+                self.umount_tags();
+                if let Err(e) = self.change_tags(changes) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_INPUT_TAGS, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_tags();
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_CAPITAL_L) => {
+                self.mount_limit();
+                None
+            }
+
+            (COMPONENT_INPUT_LIMIT, Msg::OnSubmit(Payload::One(Value::Str(text)))) => {
+                self.umount_limit();
+                self.set_limit(text);
+                None
+            }
+
+            (COMPONENT_INPUT_LIMIT, key) if (key == &MSG_KEY_ESC) => {
+                self.umount_limit();
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_O) => {
+                self.change_sort_order(|order| order.key = order.key.next());
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_CAPITAL_O) => {
+                self.change_sort_order(|order| order.descending = !order.descending);
+                None
+            }
+
+            (COMPONENT_TABLE_MAILLIST, key) if (key == &MSG_KEY_CHAR_U) => {
+                self.change_sort_order(|order| order.unread_first = !order.unread_first);
+                None
+            }
+
             (COMPONENT_TEXT_ERROR, key)
                 if (key == &MSG_KEY_ESC)
-                    | (key == &MSG_KEY_ENTER)
-                    | (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
+                    || (key == &MSG_KEY_ENTER)
+                    || (key == &MSG_KEY_CHAR_CAPITAL_Q) =>
             {
                 self.umount_error();
                 None
@@ -142,6 +465,110 @@ impl TermailActivity {
                 None
             }
 
+            (COMPONENT_TEXTAREA_MAIL, key) if (key == &MSG_KEY_CHAR_I) => {
+                self.next_image();
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key)
+                if (key == &MSG_KEY_CHAR_A)
+                    || (key == &MSG_KEY_CHAR_D)
+                    || (key == &MSG_KEY_CHAR_T) =>
+            {
+                let partstat = if key == &MSG_KEY_CHAR_A {
+                    PartStat::Accepted
+                } else if key == &MSG_KEY_CHAR_D {
+                    PartStat::Declined
+                } else {
+                    PartStat::Tentative
+                };
+                if let Err(e) = self.reply_invite(partstat) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key)
+                if (key == &MSG_KEY_CHAR_R)
+                    || (key == &MSG_KEY_CHAR_CAPITAL_R)
+                    || (key == &MSG_KEY_CHAR_CAPITAL_L) =>
+            {
+                let kind = if key == &MSG_KEY_CHAR_R {
+                    ReplyKind::Sender
+                } else if key == &MSG_KEY_CHAR_CAPITAL_R {
+                    ReplyKind::All
+                } else {
+                    ReplyKind::List
+                };
+                if let Err(e) = self.reply_mail(kind) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key)
+                if (key == &MSG_KEY_CHAR_F) || (key == &MSG_KEY_CHAR_CAPITAL_F) =>
+            {
+                if let Err(e) = self.forward_mail(key == &MSG_KEY_CHAR_CAPITAL_F) {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key) if (key == &MSG_KEY_CHAR_O) => {
+                if let Err(e) = self.open_attachment() {
+                    self.mount_error(&e.to_string());
+                }
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key) if (key == &MSG_KEY_SLASH) => {
+                self.mount_search();
+                None
+            }
+
+            (COMPONENT_TEXTAREA_MAIL, key)
+                if (key == &MSG_KEY_CHAR_N) || (key == &MSG_KEY_CHAR_CAPITAL_N) =>
+            {
+                self.search_next(key == &MSG_KEY_CHAR_N);
+                None
+            }
+
+            (COMPONENT_INPUT_SEARCH, Msg::OnChange(Payload::One(Value::Str(query)))) => {
+                self.update_search(query);
+                None
+            }
+
+            (COMPONENT_INPUT_SEARCH, Msg::OnSubmit(_)) => {
+                self.umount_search();
+                None
+            }
+
+            (COMPONENT_INPUT_SEARCH, key) if (key == &MSG_KEY_ESC) => {
+                self.clear_search();
+                self.umount_search();
+                None
+            }
+
+            (COMPONENT_INPUT_SEARCH, key) if (key == &MSG_KEY_CTRL_R) => {
+                self.toggle_search_regex();
+                None
+            }
+
+            // keep the global keys away while typing
+            (
+                COMPONENT_INPUT_SEARCH
+                | COMPONENT_INPUT_LIMIT
+                | COMPONENT_INPUT_FULLTEXT
+                | COMPONENT_INPUT_TAGS
+                | COMPONENT_INPUT_RECIPIENTS
+                | COMPONENT_INPUT_CONTACT_NAME
+                | COMPONENT_TABLE_COMPOSE
+                | COMPONENT_TABLE_FILES
+                | COMPONENT_TABLE_CONTACTS,
+                _,
+            ) => None,
+
             (_, key) => {
                 self.update_on_global_key(key);
                 None
@@ -168,17 +595,42 @@ impl TermailActivity {
 
             key if key == &MSG_KEY_CTRL_H => self.mount_help(),
 
-            key if (key == &MSG_KEY_ESC) | (key == &MSG_KEY_CHAR_CAPITAL_Q) => {
+            key if key == &MSG_KEY_CHAR_CAPITAL_A => self.mount_contacts(),
+
+            key if (key == &MSG_KEY_ESC) || (key == &MSG_KEY_CHAR_CAPITAL_Q) => {
                 self.exit_reason = Some(ExitReason::Quit);
             }
 
             &_ => {}
         }
     }
+    pub fn update_message(&mut self) {
+        if let Some(expire) = self.message_expire {
+            if expire <= Instant::now() {
+                self.message_expire = None;
+                self.view.umount(COMPONENT_TEXT_MESSAGE);
+                self.redraw = true;
+            }
+        }
+    }
+
     pub fn update_maillist(&mut self) {
         if let Ok(mail_items) = self.receiver_mail_items.try_recv() {
+            let mail_items = match mail_items {
+                Ok(mail_items) => mail_items,
+                Err(e) => {
+                    self.mount_error(&e.to_string());
+                    self.redraw = true;
+                    return;
+                }
+            };
             self.mail_items = mail_items;
+            self.collect_addresses();
+            self.sort_mail_items();
+            self.match_limit();
+            self.build_threads();
             self.sync_maillist();
+            self.view.active(COMPONENT_TABLE_MAILLIST);
             self.redraw = true;
         }
     }
@@ -1,3 +1,4 @@
+use super::reader::MailReader;
 /**
  * MIT License
  *
@@ -23,14 +24,18 @@
  */
 // Locals
 use super::{
-    TermailActivity, COMPONENT_LABEL_HELP, COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL,
-    COMPONENT_TEXT_ERROR, COMPONENT_TEXT_HELP, COMPONENT_TEXT_MESSAGE,
-    COMPONENT_TREEVIEW_MAILBOXES,
+    TermailActivity, COMPONENT_INPUT_CONTACT_NAME, COMPONENT_INPUT_FULLTEXT, COMPONENT_INPUT_LIMIT,
+    COMPONENT_INPUT_RECIPIENTS, COMPONENT_INPUT_SEARCH, COMPONENT_INPUT_TAGS, COMPONENT_LABEL_HELP,
+    COMPONENT_TABLE_COMPOSE, COMPONENT_TABLE_CONTACTS, COMPONENT_TABLE_FILES,
+    COMPONENT_TABLE_MAILLIST, COMPONENT_TEXTAREA_MAIL, COMPONENT_TEXT_ERROR, COMPONENT_TEXT_HELP,
+    COMPONENT_TEXT_MESSAGE, COMPONENT_TREEVIEW_MAILBOXES,
 };
+use crate::config::ImageProtocol;
+use crate::ui::image::HalfBlockImage;
 use crate::ui::{draw_area_in, draw_area_top_right};
 // Ext
 use tui_realm_stdlib::{
-    Label, LabelPropsBuilder, Paragraph, ParagraphPropsBuilder, Table, TablePropsBuilder, Textarea,
+    Label, LabelPropsBuilder, Paragraph, ParagraphPropsBuilder, Table, TablePropsBuilder,
     TextareaPropsBuilder,
 };
 use tuirealm::{
@@ -39,13 +44,15 @@ use tuirealm::{
         TableBuilder, TextSpan,
     },
     tui::{
+        layout::Rect,
         layout::{Alignment, Constraint, Direction, Layout},
-        style::Color,
-        widgets::Clear,
+        style::{Color, Style},
+        widgets::{Block, Clear},
     },
     PropPayload, PropsBuilder, View,
 };
 // tui
+use std::time::{Duration, Instant};
 use tui_realm_treeview::{TreeView, TreeViewPropsBuilder};
 
 #[allow(unused)]
@@ -64,16 +71,13 @@ impl TermailActivity {
             Box::new(Label::new(
                 LabelPropsBuilder::default()
                     .with_foreground(Color::Cyan)
-                    .with_text(format!(
-                        "Press <CTRL+H> for help. Version: {}",
-                        crate::VERSION,
-                    ))
+                    .with_text(self.status_line())
                     .build(),
             )),
         );
         self.view.mount(
             COMPONENT_TEXTAREA_MAIL,
-            Box::new(Textarea::new(
+            Box::new(MailReader::new(
                 TextareaPropsBuilder::default()
                     .with_background(Color::Black)
                     .with_borders(Borders::ALL, BorderType::Rounded, Color::Green)
@@ -88,6 +92,14 @@ impl TermailActivity {
         );
 
         // Scrolltable
+        let columns = &self.config.maillist.columns;
+        let header: Vec<&str> = columns.iter().map(|c| c.column.title()).collect();
+        let widths: Vec<u16> = columns.iter().map(|c| c.width).collect();
+        let mut empty = TableBuilder::default();
+        empty.add_col(TextSpan::from("Empty.."));
+        for _ in 1..columns.len() {
+            empty.add_col(TextSpan::from(""));
+        }
         self.view.mount(
             COMPONENT_TABLE_MAILLIST,
             Box::new(Table::new(
@@ -99,16 +111,9 @@ impl TermailActivity {
                     .with_borders(Borders::ALL, BorderType::Thick, Color::Blue)
                     .scrollable(true)
                     .with_title("Mail List", Alignment::Left)
-                    .with_header(&["Idx", "Time", "From", "Title"])
-                    .with_widths(&[5, 18, 22, 55])
-                    .with_table(
-                        TableBuilder::default()
-                            .add_col(TextSpan::from("Empty.."))
-                            .add_col(TextSpan::from(""))
-                            .add_col(TextSpan::from(""))
-                            .add_col(TextSpan::from(""))
-                            .build(),
-                    )
+                    .with_header(&header)
+                    .with_widths(&widths)
+                    .with_table(empty.build())
                     .build(),
             )),
         );
@@ -130,11 +135,22 @@ impl TermailActivity {
 
         // We need to initialize the focus
         self.view.active(COMPONENT_TREEVIEW_MAILBOXES);
+        if let Some(error) = self.address_book_error.take() {
+            self.mount_error(&error);
+        }
     }
 
     /// View gui
+    #[allow(clippy::too_many_lines)]
     pub(super) fn view(&mut self) {
         if let Some(mut ctx) = self.context.take() {
+            let image = if self.image_protocol == ImageProtocol::None {
+                None
+            } else {
+                self.images.get(self.image_index)
+            };
+            let mut image_area: Option<Rect> = None;
+            let mut maillist_width = self.maillist_width;
             let _drop = ctx.context.draw(|f| {
                 // Prepare chunks
                 let chunks_main = Layout::default()
@@ -158,8 +174,139 @@ impl TermailActivity {
                 self.view.render(COMPONENT_LABEL_HELP, f, chunks_main[1]);
                 self.view
                     .render(COMPONENT_TABLE_MAILLIST, f, chunks_right[0]);
-                self.view
-                    .render(COMPONENT_TEXTAREA_MAIL, f, chunks_right[1]);
+                maillist_width = chunks_right[0].width;
+                if let Some(image) = image {
+                    let chunks_reader = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .margin(0)
+                        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+                        .split(chunks_right[1]);
+                    self.view
+                        .render(COMPONENT_TEXTAREA_MAIL, f, chunks_reader[0]);
+                    let block = Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(Color::Green))
+                        .title(format!(
+                            "Image {}/{}",
+                            self.image_index + 1,
+                            self.images.len()
+                        ));
+                    let inner = block.inner(chunks_reader[1]);
+                    f.render_widget(block, chunks_reader[1]);
+                    if self.image_protocol == ImageProtocol::Halfblocks {
+                        f.render_widget(HalfBlockImage::new(image), inner);
+                    } else {
+                        image_area = Some(inner);
+                    }
+                } else {
+                    self.view
+                        .render(COMPONENT_TEXTAREA_MAIL, f, chunks_right[1]);
+                }
+
+                if let Some(props) = self.view.get_props(COMPONENT_INPUT_LIMIT) {
+                    if props.visible {
+                        // limit prompt over the bottom of the mail list
+                        let list = chunks_right[0];
+                        let height = list.height.min(3);
+                        let bar =
+                            Rect::new(list.x, list.y + list.height - height, list.width, height);
+                        f.render_widget(Clear, bar);
+                        self.view.render(COMPONENT_INPUT_LIMIT, f, bar);
+                    }
+                }
+
+                for component in &[COMPONENT_INPUT_FULLTEXT, COMPONENT_INPUT_TAGS] {
+                    if let Some(props) = self.view.get_props(component) {
+                        if props.visible {
+                            let list = chunks_right[0];
+                            let height = list.height.min(3);
+                            let bar = Rect::new(
+                                list.x,
+                                list.y + list.height - height,
+                                list.width,
+                                height,
+                            );
+                            f.render_widget(Clear, bar);
+                            self.view.render(component, f, bar);
+                        }
+                    }
+                }
+
+                if let Some(props) = self.view.get_props(COMPONENT_INPUT_SEARCH) {
+                    if props.visible {
+                        // search bar over the bottom of the reader
+                        let reader = chunks_right[1];
+                        let height = reader.height.min(3);
+                        let bar = Rect::new(
+                            reader.x,
+                            reader.y + reader.height - height,
+                            reader.width,
+                            height,
+                        );
+                        f.render_widget(Clear, bar);
+                        self.view.render(COMPONENT_INPUT_SEARCH, f, bar);
+                    }
+                }
+
+                if let Some(props) = self.view.get_props(COMPONENT_TABLE_COMPOSE) {
+                    if props.visible {
+                        let popup = draw_area_in(f.size(), 60, 30);
+                        f.render_widget(Clear, popup);
+                        self.view.render(COMPONENT_TABLE_COMPOSE, f, popup);
+                        if self
+                            .view
+                            .get_props(COMPONENT_INPUT_RECIPIENTS)
+                            .is_some_and(|p| p.visible)
+                        {
+                            // input over the bottom of the popup
+                            let height = popup.height.min(3);
+                            let bar = Rect::new(
+                                popup.x,
+                                popup.y + popup.height - height,
+                                popup.width,
+                                height,
+                            );
+                            f.render_widget(Clear, bar);
+                            self.view.render(COMPONENT_INPUT_RECIPIENTS, f, bar);
+                        }
+                        image_area = None;
+                    }
+                }
+
+                if let Some(props) = self.view.get_props(COMPONENT_TABLE_CONTACTS) {
+                    if props.visible {
+                        let popup = draw_area_in(f.size(), 70, 70);
+                        f.render_widget(Clear, popup);
+                        self.view.render(COMPONENT_TABLE_CONTACTS, f, popup);
+                        if self
+                            .view
+                            .get_props(COMPONENT_INPUT_CONTACT_NAME)
+                            .is_some_and(|p| p.visible)
+                        {
+                            // input over the bottom of the popup
+                            let height = popup.height.min(3);
+                            let bar = Rect::new(
+                                popup.x,
+                                popup.y + popup.height - height,
+                                popup.width,
+                                height,
+                            );
+                            f.render_widget(Clear, bar);
+                            self.view.render(COMPONENT_INPUT_CONTACT_NAME, f, bar);
+                        }
+                        image_area = None;
+                    }
+                }
+
+                if let Some(props) = self.view.get_props(COMPONENT_TABLE_FILES) {
+                    if props.visible {
+                        let popup = draw_area_in(f.size(), 60, 60);
+                        f.render_widget(Clear, popup);
+                        self.view.render(COMPONENT_TABLE_FILES, f, popup);
+                        image_area = None;
+                    }
+                }
 
                 if let Some(props) = self.view.get_props(COMPONENT_TEXT_HELP) {
                     if props.visible {
@@ -167,6 +314,7 @@ impl TermailActivity {
                         let popup = draw_area_in(f.size(), 50, 90);
                         f.render_widget(Clear, popup);
                         self.view.render(COMPONENT_TEXT_HELP, f, popup);
+                        image_area = None;
                     }
                 }
 
@@ -176,6 +324,7 @@ impl TermailActivity {
                         f.render_widget(Clear, popup);
                         // make popup
                         self.view.render(COMPONENT_TEXT_ERROR, f, popup);
+                        image_area = None;
                     }
                 }
 
@@ -185,10 +334,26 @@ impl TermailActivity {
                         f.render_widget(Clear, popup);
                         // make popup
                         self.view.render(COMPONENT_TEXT_MESSAGE, f, popup);
+                        image_area = None;
                     }
                 }
             });
+            if self.image_protocol == ImageProtocol::Sixel && self.image_changed(image_area) {
+                // Sixels stay on screen until overwritten, so repaint everything once
+                let _drop = ctx.context.clear();
+                self.image_on_screen = None;
+                self.context = Some(ctx);
+                self.view();
+                return;
+            }
+            self.draw_image_sequence(&mut ctx, image_area);
             self.context = Some(ctx);
+            if maillist_width != self.maillist_width {
+                // columns are truncated to the list width
+                self.maillist_width = maillist_width;
+                self.sync_maillist();
+                self.redraw = true;
+            }
         }
     }
 
@@ -243,13 +408,21 @@ impl TermailActivity {
         // self.view.active(COMPONENT_TEXT_MESSAGE);
     }
 
+    /// ### `show_message`
+    ///
+    /// Mount message box, which goes away by itself after a few seconds
+    pub(super) fn show_message(&mut self, title: &str, text: &str) {
+        self.mount_message(title, text);
+        self.message_expire = Some(Instant::now() + Duration::from_secs(3));
+    }
+
     /// ### `umount_message`
     ///
     /// Umount error message
     pub(super) fn umount_message(&mut self, _title: &str, text: &str) {
         if let Some(props) = self.view.get_props(COMPONENT_TEXT_MESSAGE) {
             if let Some(PropPayload::Vec(spans)) = props.own.get("spans") {
-                if let Some(display_text) = spans.get(0) {
+                if let Some(display_text) = spans.first() {
                     if text == display_text.unwrap_text_span().content {
                         self.view.umount(COMPONENT_TEXT_MESSAGE);
                     }
@@ -261,6 +434,7 @@ impl TermailActivity {
     // /// ### mount_help
     // ///
     // /// Mount help
+    #[allow(clippy::too_many_lines)]
     pub(super) fn mount_help(&mut self) {
         self.view.mount(
             COMPONENT_TEXT_HELP,
@@ -281,6 +455,98 @@ impl TermailActivity {
                             .add_col(TextSpan::new("<h,j,k,l,g,G>").bold().fg(Color::Cyan))
                             .add_col(TextSpan::from("Move cursor(vim style)"))
                             .add_row()
+                            .add_col(TextSpan::new("<o/O>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Sort by next key/Reverse sort direction"))
+                            .add_row()
+                            .add_col(TextSpan::new("</> in list").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Search all mailboxes"))
+                            .add_row()
+                            .add_col(TextSpan::new("<L>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Limit mail list with a pattern"))
+                            .add_row()
+                            .add_col(TextSpan::new("<m>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from(
+                                "Compose new mail, in $EDITOR or the built-in editor",
+                            ))
+                            .add_row()
+                            .add_col(TextSpan::new("<a/x> in compose").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Attach a file/Remove attachment"))
+                            .add_row()
+                            .add_col(TextSpan::new("<t/c/b> in compose").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Edit To/Cc/Bcc, <TAB> completes addresses"))
+                            .add_row()
+                            .add_col(TextSpan::new("<M/p> in compose").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Write in Markdown/Preview it rendered"))
+                            .add_row()
+                            .add_col(TextSpan::new("<b>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Bounce the mail unchanged to others"))
+                            .add_row()
+                            .add_col(TextSpan::new("<A>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Address book of everyone you write with"))
+                            .add_row()
+                            .add_col(
+                                TextSpan::new("<m/l> in address book")
+                                    .bold()
+                                    .fg(Color::Cyan),
+                            )
+                            .add_col(TextSpan::from("Write to contact/List mails from contact"))
+                            .add_row()
+                            .add_col(
+                                TextSpan::new("<e/d> in address book")
+                                    .bold()
+                                    .fg(Color::Cyan),
+                            )
+                            .add_col(TextSpan::from("Edit name/Remove contact"))
+                            .add_row()
+                            .add_col(
+                                TextSpan::new("<i/x> in address book")
+                                    .bold()
+                                    .fg(Color::Cyan),
+                            )
+                            .add_col(TextSpan::from("Import/Export the vCards of the config"))
+                            .add_row()
+                            .add_col(TextSpan::new("<i> in compose").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Send from the next identity"))
+                            .add_row()
+                            .add_col(TextSpan::new("<l> on a draft").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Resume editing the draft"))
+                            .add_row()
+                            .add_col(TextSpan::new("<r/R/L> in reader").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Reply/Reply all/Reply to list"))
+                            .add_row()
+                            .add_col(TextSpan::new("<f/F> in reader").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Forward inline/as attachment"))
+                            .add_row()
+                            .add_col(TextSpan::new("<F>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Flag/Unflag mail"))
+                            .add_row()
+                            .add_col(TextSpan::new("<+>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Change notmuch tags"))
+                            .add_row()
+                            .add_col(TextSpan::new("<u>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Toggle unread mails first"))
+                            .add_row()
+                            .add_col(TextSpan::new("<Space>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Collapse/expand thread"))
+                            .add_row()
+                            .add_col(TextSpan::new("<T>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Switch threaded/flat mail list"))
+                            .add_row()
+                            .add_col(TextSpan::new("<o>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Open next attachment with mailcap"))
+                            .add_row()
+                            .add_col(TextSpan::new("<i>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Show next image attachment"))
+                            .add_row()
+                            .add_col(TextSpan::new("<a/d/t>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Accept/Decline/Tentative invitation"))
+                            .add_row()
+                            .add_col(TextSpan::new("</>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Search in mail, <CTRL+R> toggles regex"))
+                            .add_row()
+                            .add_col(TextSpan::new("<n/N>").bold().fg(Color::Cyan))
+                            .add_col(TextSpan::from("Next/previous search match"))
+                            .add_row()
                             .add_col(TextSpan::new("<f/b>").bold().fg(Color::Cyan))
                             .add_col(TextSpan::from("Seek forward/backward 5 seconds"))
                             .add_row()
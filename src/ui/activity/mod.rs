@@ -27,39 +27,22 @@
  * SOFTWARE.
  */
 // Locals
-
 // Activities
+pub mod compose;
 pub mod main;
 
 use crate::ui::context::Context;
-use serde::{Deserialize, Serialize};
 // -- Exit reason
 
 pub enum ExitReason {
     Quit,
+    // write the mail handed over in the built-in editor
+    Compose,
     // NeedRefreshPlaylist(String),
     /* Disconnect,
      * EnterSetup, */
 }
 
-#[derive(Clone, Deserialize, Serialize)]
-pub enum Loop {
-    Single,
-    Playlist,
-    Queue,
-}
-
-impl std::fmt::Display for Loop {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let loop_state = match self {
-            Self::Single => "single loop",
-            Self::Playlist => "loop",
-            Self::Queue => "queue",
-        };
-        write!(f, "{}", loop_state)
-    }
-}
-
 // -- Activity trait
 
 pub trait Activity {
@@ -44,7 +44,7 @@ impl Context {
         assert!(execute!(stdout, EnterAlternateScreen).is_ok());
         let ctx = match TuiTerminal::new(CrosstermBackend::new(stdout)) {
             Ok(c) => c,
-            Err(e) => panic!("error when initializing terminal:{}", e.to_string()),
+            Err(e) => panic!("error when initializing terminal:{}", e),
         };
 
         Self {
@@ -0,0 +1,287 @@
+//! ## Image
+//!
+//! `image` shows picture attachments in the terminal with kitty, sixel or half blocks
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use crate::config::ImageProtocol;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use tuirealm::tui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+/// Pixel size assumed for one terminal cell when the terminal can't tell us
+const CELL_WIDTH: u32 = 10;
+const CELL_HEIGHT: u32 = 20;
+/// Kitty wants the payload split in chunks of at most 4096 bytes
+const KITTY_CHUNK: usize = 4096;
+/// Id of the image kept by kitty, there is one on screen at most
+const KITTY_IMAGE_ID: u32 = 1;
+
+/// ### `detect_protocol`
+///
+/// Resolve `Auto` by looking at the environment the terminal exports
+pub fn detect_protocol(configured: ImageProtocol) -> ImageProtocol {
+    if configured != ImageProtocol::Auto {
+        return configured;
+    }
+    let term = std::env::var("TERM").unwrap_or_default().to_lowercase();
+    let term_program = std::env::var("TERM_PROGRAM")
+        .unwrap_or_default()
+        .to_lowercase();
+    if std::env::var("KITTY_WINDOW_ID").is_ok()
+        || term.contains("kitty")
+        || term.contains("ghostty")
+        || term_program == "wezterm"
+        || term_program == "ghostty"
+    {
+        ImageProtocol::Kitty
+    } else if ["mlterm", "foot", "yaft", "sixel", "contour"]
+        .iter()
+        .any(|t| term.contains(t))
+    {
+        ImageProtocol::Sixel
+    } else {
+        ImageProtocol::Halfblocks
+    }
+}
+
+/// ### `fit_cells`
+///
+/// Size in cells of the largest box with the image's aspect ratio that fits in `area`
+pub fn fit_cells(image: &DynamicImage, area: Rect) -> (u16, u16) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || area.width == 0 || area.height == 0 {
+        return (0, 0);
+    }
+    let max_w = u64::from(area.width) * u64::from(CELL_WIDTH);
+    let max_h = u64::from(area.height) * u64::from(CELL_HEIGHT);
+    let (w, h) = (u64::from(width), u64::from(height));
+    // Scale down only, small images keep their size
+    let (px_w, px_h) = if w <= max_w && h <= max_h {
+        (w, h)
+    } else if w * max_h > h * max_w {
+        (max_w, (h * max_w / w).max(1))
+    } else {
+        ((w * max_h / h).max(1), max_h)
+    };
+    let cols = px_w
+        .div_ceil(u64::from(CELL_WIDTH))
+        .min(u64::from(area.width));
+    let rows = px_h
+        .div_ceil(u64::from(CELL_HEIGHT))
+        .min(u64::from(area.height));
+    (
+        u16::try_from(cols).unwrap_or(area.width),
+        u16::try_from(rows).unwrap_or(area.height),
+    )
+}
+
+/// ### `kitty_transmit_sequence`
+///
+/// Escape sequence handing the image, sized for `area`, to kitty without showing it. It
+/// replaces the image transmitted before.
+pub fn kitty_transmit_sequence(image: &DynamicImage, area: Rect) -> String {
+    let (cols, rows) = fit_cells(image, area);
+    let resized = image.resize(
+        u32::from(cols) * CELL_WIDTH,
+        u32::from(rows) * CELL_HEIGHT,
+        FilterType::Triangle,
+    );
+    let rgba = resized.to_rgba8();
+    let payload = base64::encode(rgba.as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut seq = kitty_delete_sequence(true);
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(idx + 1 < chunks.len());
+        let chunk = String::from_utf8_lossy(chunk);
+        if idx == 0 {
+            let _ = write!(
+                seq,
+                "\x1b_Ga=t,i={KITTY_IMAGE_ID},f=32,s={},v={},q=2,m={more};{chunk}\x1b\\",
+                rgba.width(),
+                rgba.height(),
+            );
+        } else {
+            let _ = write!(seq, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    seq
+}
+
+/// ### `kitty_place_sequence`
+///
+/// Escape sequence showing the transmitted image at the cursor, or moving it there
+pub fn kitty_place_sequence(image: &DynamicImage, area: Rect) -> String {
+    let (cols, rows) = fit_cells(image, area);
+    format!("\x1b_Ga=p,i={KITTY_IMAGE_ID},p=1,c={cols},r={rows},C=1,q=2\x1b\\")
+}
+
+/// ### `kitty_delete_sequence`
+///
+/// Escape sequence taking the image off the screen, and out of kitty's memory with `free`
+pub fn kitty_delete_sequence(free: bool) -> String {
+    let target = if free { 'I' } else { 'i' };
+    format!("\x1b_Ga=d,d={target},i={KITTY_IMAGE_ID},q=2\x1b\\")
+}
+
+/// ### `sixel_sequence`
+///
+/// Escape sequence drawing the image at the cursor as sixels, quantized to a 6x6x6 color cube
+pub fn sixel_sequence(image: &DynamicImage, area: Rect) -> String {
+    let (cols, rows) = fit_cells(image, area);
+    let resized = image.resize(
+        u32::from(cols) * CELL_WIDTH,
+        u32::from(rows) * CELL_HEIGHT,
+        FilterType::Triangle,
+    );
+    let rgba = resized.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let mut seq = String::from("\x1bP0;1q");
+    let _ = write!(seq, "\"1;1;{width};{height}");
+    for idx in 0..216_u32 {
+        let (r, g, b) = (idx / 36, (idx / 6) % 6, idx % 6);
+        let _ = write!(seq, "#{idx};2;{};{};{}", r * 20, g * 20, b * 20);
+    }
+
+    for band in (0..height).step_by(6) {
+        // color index -> sixel bits for every column of this band
+        let mut colors: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                let pixel = rgba.get_pixel(x, y);
+                if pixel[3] < 128 {
+                    continue;
+                }
+                let idx = palette_index(pixel[0], pixel[1], pixel[2]);
+                let bits = colors.entry(idx).or_insert_with(|| vec![0; width as usize]);
+                bits[x as usize] |= 1 << (y - band);
+            }
+        }
+        for (idx, bits) in &colors {
+            let _ = write!(seq, "#{idx}");
+            push_sixel_run(&mut seq, bits);
+            seq.push('$');
+        }
+        seq.push('-');
+    }
+    seq.push_str("\x1b\\");
+    seq
+}
+
+fn palette_index(r: u8, g: u8, b: u8) -> u32 {
+    let level = |v: u8| (u32::from(v) * 5 + 127) / 255;
+    level(r) * 36 + level(g) * 6 + level(b)
+}
+
+fn push_sixel_run(seq: &mut String, bits: &[u8]) {
+    let mut iter = bits.iter().peekable();
+    while let Some(&value) = iter.next() {
+        let mut count = 1;
+        while iter.peek() == Some(&&value) {
+            iter.next();
+            count += 1;
+        }
+        let ch = char::from(63 + value);
+        if count > 3 {
+            let _ = write!(seq, "!{count}{ch}");
+        } else {
+            (0..count).for_each(|_| seq.push(ch));
+        }
+    }
+}
+
+/// ## `HalfBlockImage`
+///
+/// Widget drawing an image with upper half blocks, two pixels per cell
+pub struct HalfBlockImage<'a> {
+    image: &'a DynamicImage,
+}
+
+impl<'a> HalfBlockImage<'a> {
+    pub const fn new(image: &'a DynamicImage) -> Self {
+        Self { image }
+    }
+}
+
+impl Widget for HalfBlockImage<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (cols, rows) = fit_cells(self.image, area);
+        if cols == 0 || rows == 0 {
+            return;
+        }
+        let resized = self
+            .image
+            .resize_exact(u32::from(cols), u32::from(rows) * 2, FilterType::Triangle)
+            .to_rgb8();
+        for row in 0..rows {
+            for col in 0..cols {
+                let upper = resized.get_pixel(u32::from(col), u32::from(row) * 2);
+                let lower = resized.get_pixel(u32::from(col), u32::from(row) * 2 + 1);
+                buf.get_mut(area.x + col, area.y + row)
+                    .set_char('\u{2580}')
+                    .set_fg(Color::Rgb(upper[0], upper[1], upper[2]))
+                    .set_bg(Color::Rgb(lower[0], lower[1], lower[2]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ui_image_fit_cells() {
+        let image = DynamicImage::new_rgb8(800, 400);
+        assert_eq!(fit_cells(&image, Rect::new(0, 0, 40, 40)), (40, 10));
+        let image = DynamicImage::new_rgb8(50, 50);
+        assert_eq!(fit_cells(&image, Rect::new(0, 0, 40, 40)), (5, 3));
+    }
+
+    #[test]
+    fn test_ui_image_kitty() {
+        let image = DynamicImage::new_rgb8(50, 50);
+        let area = Rect::new(0, 0, 40, 40);
+        let transmit = kitty_transmit_sequence(&image, area);
+        assert!(transmit.starts_with("\x1b_Ga=d,d=I,i=1,q=2\x1b\\\x1b_Ga=t,i=1,"));
+        assert_eq!(
+            kitty_place_sequence(&image, area),
+            "\x1b_Ga=p,i=1,p=1,c=5,r=3,C=1,q=2\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_ui_image_sixel_run() {
+        let mut seq = String::new();
+        push_sixel_run(&mut seq, &[1, 1, 1, 1, 1, 0, 63]);
+        assert_eq!(seq, "!5@?~");
+    }
+}
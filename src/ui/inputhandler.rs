@@ -32,19 +32,13 @@ impl InputHandler {
     // }
 
     pub fn read_event() -> Result<Option<Event>, ()> {
-        if let Ok(available) = poll(Duration::from_millis(100)) {
+        poll(Duration::from_millis(100)).map_or(Err(()), |available| {
             if available {
                 // Read event
-                if let Ok(ev) = read() {
-                    Ok(Some(ev))
-                } else {
-                    Err(())
-                }
+                read().map_or(Err(()), |ev| Ok(Some(ev)))
             } else {
                 Ok(None)
             }
-        } else {
-            Err(())
-        }
+        })
     }
 }
@@ -43,23 +43,23 @@ pub const MSG_KEY_TAB: Msg = Msg::OnKey(KeyEvent {
     modifiers: KeyModifiers::NONE,
 });
 
-// pub const MSG_KEY_SHIFT_TAB: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::BackTab,
-//     modifiers: KeyModifiers::SHIFT,
-// });
+pub const MSG_KEY_SHIFT_TAB: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::BackTab,
+    modifiers: KeyModifiers::SHIFT,
+});
 
-// pub const MSG_KEY_DEL: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Delete,
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_DEL: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Delete,
+    modifiers: KeyModifiers::NONE,
+});
 // pub const MSG_KEY_BACKSPACE: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Backspace,
 //     modifiers: KeyModifiers::NONE,
 // });
-// pub const MSG_KEY_DOWN: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Down,
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_DOWN: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Down,
+    modifiers: KeyModifiers::NONE,
+});
 // pub const MSG_KEY_LEFT: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Left,
 //     modifiers: KeyModifiers::NONE,
@@ -68,25 +68,29 @@ pub const MSG_KEY_TAB: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Right,
 //     modifiers: KeyModifiers::NONE,
 // });
-// pub const MSG_KEY_UP: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Up,
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_SPACE: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char(' '),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_UP: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Up,
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_SPACE: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char(' '),
+    modifiers: KeyModifiers::NONE,
+});
 
 // // -- char keys
 
-// pub const MSG_KEY_CHAR_A: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('a'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_B: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('b'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_CAPITAL_A: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('A'),
+    modifiers: KeyModifiers::SHIFT,
+});
+pub const MSG_KEY_CHAR_A: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('a'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_B: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('b'),
+    modifiers: KeyModifiers::NONE,
+});
 // pub const MSG_KEY_CHAR_CAPITAL_B: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('B'),
 //     modifiers: KeyModifiers::SHIFT,
@@ -97,32 +101,32 @@ pub const MSG_KEY_TAB: Msg = Msg::OnKey(KeyEvent {
 //     modifiers: KeyModifiers::SHIFT,
 // });
 
-// pub const MSG_KEY_CHAR_C: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('c'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_D: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('d'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_C: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('c'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_D: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('d'),
+    modifiers: KeyModifiers::NONE,
+});
 
 // pub const MSG_KEY_CHAR_CAPITAL_D: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('D'),
 //     modifiers: KeyModifiers::SHIFT,
 // });
 
-// pub const MSG_KEY_CHAR_E: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('e'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_F: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('f'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_CAPITAL_F: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('F'),
-//     modifiers: KeyModifiers::SHIFT,
-// });
+pub const MSG_KEY_CHAR_E: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('e'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_F: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('f'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_CAPITAL_F: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('F'),
+    modifiers: KeyModifiers::SHIFT,
+});
 
 // pub const MSG_KEY_CHAR_CAPITAL_F: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('F'),
@@ -142,10 +146,10 @@ pub const MSG_KEY_CHAR_H: Msg = Msg::OnKey(KeyEvent {
     code: KeyCode::Char('h'),
     modifiers: KeyModifiers::NONE,
 });
-// pub const MSG_KEY_CHAR_I: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('i'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_I: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('i'),
+    modifiers: KeyModifiers::NONE,
+});
 // /*
 pub const MSG_KEY_CHAR_J: Msg = Msg::OnKey(KeyEvent {
     code: KeyCode::Char('j'),
@@ -160,48 +164,60 @@ pub const MSG_KEY_CHAR_L: Msg = Msg::OnKey(KeyEvent {
     code: KeyCode::Char('l'),
     modifiers: KeyModifiers::NONE,
 });
-// pub const MSG_KEY_CHAR_CAPITAL_L: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('L'),
-//     modifiers: KeyModifiers::SHIFT,
-// });
+pub const MSG_KEY_CHAR_CAPITAL_L: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('L'),
+    modifiers: KeyModifiers::SHIFT,
+});
 
 // // /*
-// pub const MSG_KEY_CHAR_M: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('m'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_M: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('m'),
+    modifiers: KeyModifiers::NONE,
+});
 // // */
-// pub const MSG_KEY_CHAR_N: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('n'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_CAPITAL_N: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('N'),
-//     modifiers: KeyModifiers::SHIFT,
-// });
+pub const MSG_KEY_CHAR_CAPITAL_M: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('M'),
+    modifiers: KeyModifiers::SHIFT,
+});
+pub const MSG_KEY_CHAR_N: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('n'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_CAPITAL_N: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('N'),
+    modifiers: KeyModifiers::SHIFT,
+});
 
-// pub const MSG_KEY_CHAR_O: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('o'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_O: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('o'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_CAPITAL_O: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('O'),
+    modifiers: KeyModifiers::SHIFT,
+});
 // /*
-// pub const MSG_KEY_CHAR_P: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('p'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_P: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('p'),
+    modifiers: KeyModifiers::NONE,
+});
 // */
+pub const MSG_KEY_CHAR_Q: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('q'),
+    modifiers: KeyModifiers::NONE,
+});
 pub const MSG_KEY_CHAR_CAPITAL_Q: Msg = Msg::OnKey(KeyEvent {
     code: KeyCode::Char('Q'),
     modifiers: KeyModifiers::SHIFT,
 });
-// pub const MSG_KEY_CHAR_CAPITAL_T: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('T'),
-//     modifiers: KeyModifiers::SHIFT,
-// });
-// pub const MSG_KEY_CHAR_PLUS: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('+'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_CAPITAL_T: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('T'),
+    modifiers: KeyModifiers::SHIFT,
+});
+pub const MSG_KEY_CHAR_PLUS: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('+'),
+    modifiers: KeyModifiers::NONE,
+});
 // pub const MSG_KEY_CHAR_MINUS: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('-'),
 //     modifiers: KeyModifiers::NONE,
@@ -215,25 +231,29 @@ pub const MSG_KEY_CHAR_CAPITAL_Q: Msg = Msg::OnKey(KeyEvent {
 //     modifiers: KeyModifiers::NONE,
 // });
 
-// pub const MSG_KEY_CHAR_R: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('r'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_R: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('r'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_CAPITAL_R: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('R'),
+    modifiers: KeyModifiers::SHIFT,
+});
 
 // pub const MSG_KEY_CHAR_S: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('s'),
 //     modifiers: KeyModifiers::NONE,
 // });
 // // /*
-// pub const MSG_KEY_CHAR_T: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('t'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_T: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('t'),
+    modifiers: KeyModifiers::NONE,
+});
 // */
-// pub const MSG_KEY_CHAR_U: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('u'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_U: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('u'),
+    modifiers: KeyModifiers::NONE,
+});
 // /*
 // pub const MSG_KEY_CHAR_V: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('v'),
@@ -244,14 +264,14 @@ pub const MSG_KEY_CHAR_CAPITAL_Q: Msg = Msg::OnKey(KeyEvent {
 //     modifiers: KeyModifiers::NONE,
 // });
 // */
-// pub const MSG_KEY_CHAR_X: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('x'),
-//     modifiers: KeyModifiers::NONE,
-// });
-// pub const MSG_KEY_CHAR_Y: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('y'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_CHAR_X: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('x'),
+    modifiers: KeyModifiers::NONE,
+});
+pub const MSG_KEY_CHAR_Y: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('y'),
+    modifiers: KeyModifiers::NONE,
+});
 // /*
 // pub const MSG_KEY_CHAR_Z: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('z'),
@@ -273,10 +293,10 @@ pub const MSG_KEY_CTRL_H: Msg = Msg::OnKey(KeyEvent {
     modifiers: KeyModifiers::CONTROL,
 });
 
-// pub const MSG_KEY_SLASH: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('/'),
-//     modifiers: KeyModifiers::NONE,
-// });
+pub const MSG_KEY_SLASH: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('/'),
+    modifiers: KeyModifiers::NONE,
+});
 
 // pub const MSG_KEY_QUESTION_MARK: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('?'),
@@ -287,11 +307,11 @@ pub const MSG_KEY_CTRL_H: Msg = Msg::OnKey(KeyEvent {
 //     code: KeyCode::Char('n'),
 //     modifiers: KeyModifiers::CONTROL,
 // });
-// pub const MSG_KEY_CTRL_R: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('r'),
-//     modifiers: KeyModifiers::CONTROL,
-// });
-// pub const MSG_KEY_CTRL_S: Msg = Msg::OnKey(KeyEvent {
-//     code: KeyCode::Char('s'),
-//     modifiers: KeyModifiers::CONTROL,
-// });
+pub const MSG_KEY_CTRL_R: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('r'),
+    modifiers: KeyModifiers::CONTROL,
+});
+pub const MSG_KEY_CTRL_S: Msg = Msg::OnKey(KeyEvent {
+    code: KeyCode::Char('s'),
+    modifiers: KeyModifiers::CONTROL,
+});
@@ -27,6 +27,7 @@
  */
 pub mod activity;
 pub mod context;
+pub mod image;
 pub mod inputhandler;
 pub mod keymap;
 
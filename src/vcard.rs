@@ -0,0 +1,443 @@
+//! ## Vcard
+//!
+//! `vcard` reads and writes contacts in the vCard 3.0 and 4.0 formats shared by address books
+
+/**
+ * MIT License
+ *
+ * termail - Copyright (c) 2021 Larry Hao
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// longest line of a vCard in octets, longer ones are folded
+const LINE_LENGTH: usize = 75;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Version {
+    V3,
+    #[default]
+    V4,
+}
+
+/// A contact of a vCard, with the properties termail has no use for kept as they were
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Card {
+    pub version: Version,
+    pub uid: String,
+    /// formatted name
+    pub name: String,
+    /// addresses, the preferred one first
+    pub emails: Vec<String>,
+    /// group and parameters other than the preference each address was read with
+    #[serde(default)]
+    pub email_params: BTreeMap<String, (String, Vec<String>)>,
+    /// other properties, unfolded
+    pub other: Vec<String>,
+}
+
+impl Card {
+    /// ### `to_vcard`
+    ///
+    /// The card as a vCard of its version, with folded lines
+    pub fn to_vcard(&self) -> String {
+        let mut lines = vec!["BEGIN:VCARD".to_string()];
+        lines.push(
+            match self.version {
+                Version::V3 => "VERSION:3.0",
+                Version::V4 => "VERSION:4.0",
+            }
+            .to_string(),
+        );
+        if !self.uid.is_empty() {
+            lines.push(format!("UID:{}", self.uid));
+        }
+        lines.push(format!("FN:{}", escape(&self.name)));
+        let has_n = self.other.iter().any(|line| property(line).0 == "N");
+        if self.version == Version::V3 && !has_n {
+            // required by 3.0
+            lines.push(format!("N:;{};;;", escape(&self.name)));
+        }
+        for (i, email) in self.emails.iter().enumerate() {
+            lines.push(self.email_line(i == 0, email));
+        }
+        lines.extend(self.other.iter().cloned());
+        lines.push("END:VCARD".to_string());
+        lines.iter().map(|line| fold(line)).collect()
+    }
+
+    /// The EMAIL line of `email`, with the group and parameters it was read with
+    fn email_line(&self, preferred: bool, email: &str) -> String {
+        let (group, params) = self
+            .email_params
+            .get(email)
+            .map_or(("", &[][..]), |(group, params)| (group, params));
+        let mut line = if group.is_empty() {
+            "EMAIL".to_string()
+        } else {
+            format!("{group}.EMAIL")
+        };
+        let pref = match (self.version, preferred) {
+            (_, false) => None,
+            (Version::V3, true) => Some("TYPE=PREF"),
+            (Version::V4, true) => Some("PREF=1"),
+        };
+        if params.is_empty() {
+            match (self.version, pref) {
+                (Version::V3, Some(_)) => line.push_str(";TYPE=INTERNET,PREF"),
+                (Version::V3, None) => line.push_str(";TYPE=INTERNET"),
+                (Version::V4, Some(pref)) => {
+                    line.push(';');
+                    line.push_str(pref);
+                }
+                (Version::V4, None) => {}
+            }
+        } else {
+            for param in params.iter().map(String::as_str).chain(pref) {
+                line.push(';');
+                line.push_str(param);
+            }
+        }
+        format!("{line}:{email}")
+    }
+}
+
+/// ### `parse`
+///
+/// Cards of a vCard file, which may hold many
+pub fn parse(text: &str) -> Vec<Card> {
+    let mut cards = Vec::new();
+    let mut card: Option<Card> = None;
+    // addresses with their preference, 1 being the most preferred
+    let mut emails: Vec<(u32, String)> = Vec::new();
+    let mut structured_name = String::new();
+    for line in unfold(text) {
+        let (name, params, value) = property(&line);
+        match name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VCARD") => {
+                card = Some(Card::default());
+                emails.clear();
+                structured_name.clear();
+            }
+            "END" if value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(mut done) = card.take() {
+                    emails.sort_by_key(|(pref, _)| *pref);
+                    done.emails = std::mem::take(&mut emails)
+                        .into_iter()
+                        .map(|(_, email)| email)
+                        .collect();
+                    if done.name.is_empty() {
+                        done.name.clone_from(&structured_name);
+                    }
+                    cards.push(done);
+                }
+            }
+            _ => {
+                let Some(card) = card.as_mut() else {
+                    continue;
+                };
+                match name.as_str() {
+                    "VERSION" => {
+                        card.version = if value.starts_with('3') || value.starts_with('2') {
+                            Version::V3
+                        } else {
+                            Version::V4
+                        };
+                    }
+                    "UID" => card.uid = value.to_string(),
+                    "FN" => card.name = unescape(value).trim().to_string(),
+                    "EMAIL" => {
+                        let email = unescape(value).trim().to_string();
+                        if !email.is_empty() {
+                            let kept = without_preference(&params);
+                            let group = group(&line).to_string();
+                            if !group.is_empty() || !kept.is_empty() {
+                                card.email_params.insert(email.clone(), (group, kept));
+                            }
+                            emails.push((preference(&params), email));
+                        }
+                    }
+                    _ => {
+                        if name == "N" {
+                            structured_name = name_of(value);
+                        }
+                        card.other.push(line.clone());
+                    }
+                }
+            }
+        }
+    }
+    cards
+}
+
+/// ### `write`
+///
+/// Cards as one vCard file
+pub fn write(cards: &[Card]) -> String {
+    cards.iter().map(Card::to_vcard).collect()
+}
+
+/// Join folded lines back together
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Split a line into its name without group, its parameters and its value
+fn property(line: &str) -> (String, Vec<String>, &str) {
+    let mut quoted = false;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(line[start..i].to_string());
+                start = i + 1;
+            }
+            ':' if !quoted => {
+                parts.push(line[start..i].to_string());
+                let name = parts.remove(0);
+                let name = name.rsplit('.').next().unwrap_or_default().to_uppercase();
+                return (name, parts, &line[i + 1..]);
+            }
+            _ => {}
+        }
+    }
+    (String::new(), Vec::new(), "")
+}
+
+/// Group of the property of a line, like `item1` in `item1.EMAIL:...`
+fn group(line: &str) -> &str {
+    line.split([';', ':'])
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map_or("", |(group, _)| group)
+}
+
+/// Parameters without PREF, and without `pref` in their TYPE lists
+fn without_preference(params: &[String]) -> Vec<String> {
+    params
+        .iter()
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or(("TYPE", param));
+            if name.eq_ignore_ascii_case("PREF") {
+                return None;
+            }
+            if !name.eq_ignore_ascii_case("TYPE") {
+                return Some(param.clone());
+            }
+            let types: Vec<&str> = value
+                .trim_matches('"')
+                .split(',')
+                .filter(|t| !t.eq_ignore_ascii_case("pref"))
+                .collect();
+            (!types.is_empty()).then(|| format!("{name}={}", types.join(",")))
+        })
+        .collect()
+}
+
+/// 1 for the preferred address, 100 for the others
+fn preference(params: &[String]) -> u32 {
+    for param in params {
+        let (name, value) = param.split_once('=').unwrap_or(("TYPE", param));
+        if name.eq_ignore_ascii_case("PREF") {
+            return value.trim_matches('"').parse().unwrap_or(1);
+        }
+        if name.eq_ignore_ascii_case("TYPE")
+            && value
+                .trim_matches('"')
+                .split(',')
+                .any(|t| t.eq_ignore_ascii_case("pref"))
+        {
+            return 1;
+        }
+    }
+    100
+}
+
+/// Given and family name of a structured N value
+fn name_of(value: &str) -> String {
+    let parts: Vec<String> = split_escaped(value, ';')
+        .iter()
+        .map(|p| unescape(p))
+        .collect();
+    let family = parts.first().map_or("", |s| s.trim());
+    let given = parts.get(1).map_or("", |s| s.trim());
+    format!("{given} {family}").trim().to_string()
+}
+
+fn split_escaped(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(&value[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+/// Break a line into lines of at most `LINE_LENGTH` octets, the next ones starting with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const CARDS: &str = "BEGIN:VCARD\r
+VERSION:3.0\r
+N:Lee;Ann;;;\r
+item1.EMAIL;TYPE=INTERNET:ann@home.example\r
+item2.EMAIL;TYPE=INTERNET,pref:ann@\r
+ work.example\r
+TEL;TYPE=cell:+1 555 0100\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:4.0\r
+UID:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1\r
+FN:Bob Stone\\, Jr.\r
+EMAIL;PREF=2:bob@example.org\r
+EMAIL;PREF=1:bob@example.com\r
+GEO;VALUE=uri:\"geo:37.386013,-122.082932\"\r
+END:VCARD\r
+";
+
+    #[test]
+    fn test_vcard_parse() {
+        let cards = parse(CARDS);
+        assert_eq!(
+            cards,
+            vec![
+                Card {
+                    version: Version::V3,
+                    uid: String::new(),
+                    name: "Ann Lee".to_string(),
+                    emails: vec![
+                        "ann@work.example".to_string(),
+                        "ann@home.example".to_string()
+                    ],
+                    email_params: BTreeMap::from([
+                        (
+                            "ann@work.example".to_string(),
+                            ("item2".to_string(), vec!["TYPE=INTERNET".to_string()])
+                        ),
+                        (
+                            "ann@home.example".to_string(),
+                            ("item1".to_string(), vec!["TYPE=INTERNET".to_string()])
+                        ),
+                    ]),
+                    other: vec![
+                        "N:Lee;Ann;;;".to_string(),
+                        "TEL;TYPE=cell:+1 555 0100".to_string()
+                    ],
+                },
+                Card {
+                    version: Version::V4,
+                    uid: "urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1".to_string(),
+                    name: "Bob Stone, Jr.".to_string(),
+                    emails: vec!["bob@example.com".to_string(), "bob@example.org".to_string()],
+                    email_params: BTreeMap::new(),
+                    other: vec!["GEO;VALUE=uri:\"geo:37.386013,-122.082932\"".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vcard_write() {
+        let cards = parse(CARDS);
+        assert_eq!(parse(&write(&cards)), cards);
+        assert!(cards[1].to_vcard().contains("FN:Bob Stone\\, Jr.\r\n"));
+        assert!(cards[0]
+            .to_vcard()
+            .contains("item2.EMAIL;TYPE=INTERNET;TYPE=PREF:ann@work.example\r\n"));
+        let work = parse("BEGIN:VCARD\r\nVERSION:4.0\r\nEMAIL;TYPE=work:a@b.c\r\nEND:VCARD\r\n");
+        assert!(work[0]
+            .to_vcard()
+            .contains("EMAIL;TYPE=work;PREF=1:a@b.c\r\n"));
+
+        let card = Card {
+            version: Version::V3,
+            name: "Zoë".repeat(30),
+            emails: vec!["zoe@example.com".to_string()],
+            ..Card::default()
+        };
+        let text = card.to_vcard();
+        assert!(text.lines().all(|line| line.len() <= LINE_LENGTH + 1));
+        assert!(text.contains("EMAIL;TYPE=INTERNET,PREF:zoe@example.com\r\n"));
+        assert_eq!(parse(&text)[0].name, card.name);
+    }
+}